@@ -1,7 +1,7 @@
 use hsa_rs::kfd::device::KfdDevice;
 use hsa_rs::kfd::ioctl::{GetProcessAperturesNewArgs, ProcessDeviceApertures};
 use hsa_rs::kfd::sysfs::{
-    HSA_IOLINKTYPE_NUMA, HSA_IOLINKTYPE_PCIEXPRESS, HSA_IOLINKTYPE_XGMI, Topology,
+    HSA_IOLINKTYPE_NUMA, HSA_IOLINKTYPE_PCIEXPRESS, HSA_IOLINKTYPE_XGMI, HeapType, Topology,
 };
 use std::fs::{self};
 use std::io;
@@ -64,6 +64,13 @@ fn main() -> io::Result<()> {
             );
             println!("    KFD GPU ID:    {}", node.properties.kfd_gpu_id);
             println!("    Location ID:   0x{:x}", node.properties.location_id);
+            let diagnostics = node.properties.diagnostics();
+            if let Some(vbios) = &diagnostics.vbios_version {
+                println!("    VBIOS:         {}", vbios);
+            }
+            for (component, version) in &diagnostics.firmware_versions {
+                println!("    FW {:<10} 0x{:08x}", component, version);
+            }
 
             // Store for VM test later
             gpu_nodes.push(node);
@@ -76,10 +83,16 @@ fn main() -> io::Result<()> {
         if !node.mem_banks.is_empty() {
             println!("\n    Memory Banks:");
             for (i, mem) in node.mem_banks.iter().enumerate() {
-                let type_str = match mem.heap_type {
-                    0 => "System",
-                    1 => "FrameBuffer (VRAM)",
-                    _ => "Unknown",
+                let type_str = match mem.heap() {
+                    Some(HeapType::System) => "System",
+                    Some(HeapType::FrameBufferPublic) => "FrameBuffer (Public)",
+                    Some(HeapType::FrameBufferPrivate) => "FrameBuffer (Private)",
+                    Some(HeapType::GpuGds) => "GDS",
+                    Some(HeapType::GpuLds) => "LDS",
+                    Some(HeapType::GpuScratch) => "Scratch",
+                    Some(HeapType::DeviceSvm) => "SVM",
+                    Some(HeapType::MmioRemap) => "MMIO Remap",
+                    None => "Unknown",
                 };
                 println!(
                     "      [{}] {:<20} Size: {} MB",
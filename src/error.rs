@@ -1,9 +1,11 @@
+use crate::thunk::memory::MemoryError;
+use std::io;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum HsaError {
     #[error("I/O Error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
 
     #[error("KFD Driver Error: {0}")]
     Driver(String),
@@ -14,11 +16,64 @@ pub enum HsaError {
     #[error("Out of GPU Memory")]
     OutOfMemory,
 
+    #[error("Memory Error: {0}")]
+    Memory(#[from] MemoryError),
+
     #[error("Invalid node ID: {0}")]
     InvalidNodeId(u32),
 
+    /// Creating the queue would exceed the node's queue budget.
+    #[error("Node {node_id} already has {limit} live queues of this type")]
+    TooManyQueues { node_id: u32, limit: u32 },
+
     #[error("General Thunk Error: {0}")]
     General(String),
+
+    /// The amdgpu driver was reloaded. Every KFD handle, allocation and cached topology
+    /// from before the reload is stale and the application must reinitialize.
+    #[error("KFD driver was reloaded, all handles are stale")]
+    DriverReloaded,
+}
+
+/// Payload of the `io::Error` the topology functions return once they detect that the
+/// driver behind the cached KFD fd was reloaded. Converts to [`HsaError::DriverReloaded`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("KFD driver was reloaded, all handles are stale")]
+pub struct DriverReloadedError;
+
+impl From<DriverReloadedError> for io::Error {
+    fn from(e: DriverReloadedError) -> Self {
+        Self::other(e)
+    }
+}
+
+impl HsaError {
+    /// Converts the error of an ioctl on an already-open KFD fd.
+    ///
+    /// KFD answers `ENODEV` on every ioctl once the device behind an fd is gone, so there it
+    /// means [`Self::DriverReloaded`]. Elsewhere (e.g. opening `/dev/kfd` on a machine
+    /// without a GPU) `ENODEV` is an ordinary I/O error, see the `From` impl.
+    #[must_use]
+    pub fn from_ioctl(e: io::Error) -> Self {
+        if e.raw_os_error() == Some(libc::ENODEV) {
+            Self::DriverReloaded
+        } else {
+            Self::from(e)
+        }
+    }
+}
+
+impl From<io::Error> for HsaError {
+    /// Only errors built from [`DriverReloadedError`] become `DriverReloaded`.
+    fn from(e: io::Error) -> Self {
+        if e.get_ref()
+            .is_some_and(|inner| inner.is::<DriverReloadedError>())
+        {
+            Self::DriverReloaded
+        } else {
+            Self::Io(e)
+        }
+    }
 }
 
 // A convenient alias
@@ -15,16 +15,19 @@ use crate::kfd::ioctl::{
     AMDKFD_IOC_SET_MEMORY_POLICY, AMDKFD_IOC_SET_SCRATCH_BACKING_VA, AMDKFD_IOC_SET_TRAP_HANDLER,
     AMDKFD_IOC_SET_XNACK_MODE, AMDKFD_IOC_SMI_EVENTS, AMDKFD_IOC_SVM,
     AMDKFD_IOC_UNMAP_MEMORY_FROM_GPU, AMDKFD_IOC_UPDATE_QUEUE, AMDKFD_IOC_WAIT_EVENTS,
-    AcquireVmArgs, AisArgs, AllocMemoryOfGpuArgs, AllocQueueGwsArgs, CreateEventArgs,
+    AcquireVmArgs, AisArgs, AisInArgs, AllocMemoryOfGpuArgs, AllocQueueGwsArgs, CreateEventArgs,
     CreateQueueArgs, CriuArgs, CrossMemoryCopyArgs, DbgAddressWatchArgs, DbgRegisterArgs,
     DbgTrapArgs, DbgUnregisterArgs, DbgWaveControlArgs, DestroyEventArgs, DestroyQueueArgs,
     ExportDmabufArgs, FreeMemoryOfGpuArgs, GetAvailableMemoryArgs, GetClockCountersArgs,
     GetDmabufInfoArgs, GetProcessAperturesArgs, GetProcessAperturesNewArgs, GetQueueWaveStateArgs,
     GetTileConfigArgs, GetVersionArgs, ImportDmabufArgs, IpcExportHandleArgs, IpcImportHandleArgs,
-    MapMemoryToGpuArgs, PcSampleArgs, ProfilerArgs, ResetEventArgs, RuntimeEnableArgs,
+    KFD_IOC_AIS_OP_READ, KFD_IOC_AIS_OP_WRITE, KFD_IOCTL_SVM_ATTR_PREFERRED_LOC,
+    KFD_IOCTL_SVM_OP_GET_ATTR, KFD_MAX_MACRO_TILE_CONFIGS, KFD_MAX_TILE_CONFIGS,
+    KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK, KFD_RUNTIME_ENABLE_MODE_TTMP_SAVE_MASK,
+    MapMemoryToGpuArgs, MemoryRange, PcSampleArgs, ProfilerArgs, ResetEventArgs, RuntimeEnableArgs,
     SetCuMaskArgs, SetEventArgs, SetMemoryPolicyArgs, SetScratchBackingVaArgs, SetTrapHandlerArgs,
-    SetXnackModeArgs, SmiEventsArgs, SpmArgs, SvmArgs, UnmapMemoryFromGpuArgs, UpdateQueueArgs,
-    WaitEventsArgs,
+    SetXnackModeArgs, SmiEventsArgs, SpmArgs, SvmArgs, SvmAttrSupport, SvmAttribute, TileConfig,
+    UnmapMemoryFromGpuArgs, UpdateQueueArgs, WaitEventsArgs,
 };
 use std::fs::{File, OpenOptions};
 use std::io;
@@ -32,6 +35,85 @@ use std::os::fd::RawFd;
 use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
 
+/// Optional KFD ioctl interfaces, each introduced by a minor version of the 1.x ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KfdFeature {
+    SmiEvents,
+    Svm,
+    Criu,
+    AvailableMemory,
+    ExportDmabuf,
+    Debugger,
+    ContiguousVram,
+    SdmaByEngineId,
+}
+
+impl KfdFeature {
+    /// The `(major, minor)` KFD ioctl version that introduced the feature.
+    #[must_use]
+    pub const fn min_version(self) -> (u32, u32) {
+        match self {
+            Self::SmiEvents => (1, 3),
+            Self::Svm => (1, 5),
+            Self::Criu => (1, 7),
+            Self::AvailableMemory => (1, 9),
+            Self::ExportDmabuf => (1, 12),
+            Self::Debugger => (1, 13),
+            Self::ContiguousVram => (1, 16),
+            Self::SdmaByEngineId => (1, 17),
+        }
+    }
+}
+
+/// How the runtime announces itself through `AMDKFD_IOC_RUNTIME_ENABLE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuntimeMode {
+    /// Tear down a previous enable.
+    Disable,
+    /// Enable the runtime without trap temporary setup.
+    Enable,
+    /// Enable the runtime and have the driver save TTMP registers on trap entry.
+    EnableWithTtmpSave,
+}
+
+impl RuntimeMode {
+    /// The raw `mode_mask` passed to the ioctl.
+    #[must_use]
+    pub const fn mode_mask(self) -> u32 {
+        match self {
+            Self::Disable => 0,
+            Self::Enable => KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK,
+            Self::EnableWithTtmpSave => {
+                KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK | KFD_RUNTIME_ENABLE_MODE_TTMP_SAVE_MASK
+            }
+        }
+    }
+}
+
+/// Wraps a failed `AMDKFD_IOC_RUNTIME_ENABLE` in a message naming the likely cause.
+///
+/// The original error kind is kept so callers can still match on it.
+#[must_use]
+pub fn runtime_enable_error(err: &io::Error) -> io::Error {
+    let reason = match err.raw_os_error() {
+        Some(libc::EBUSY) => "the runtime is already enabled for this process",
+        Some(libc::EPERM) => "the process is not allowed to enable the runtime",
+        Some(libc::EINVAL | libc::ENOTTY) => "the driver does not support runtime enable",
+        _ => "unexpected driver error",
+    };
+    io::Error::new(
+        err.kind(),
+        format!("AMDKFD_IOC_RUNTIME_ENABLE failed: {reason} ({err})"),
+    )
+}
+
+/// Whether a driver at `actual` provides the ABI of `required`. Majors must match: a new
+/// major version is not backwards compatible.
+#[must_use]
+pub const fn version_satisfies(actual: (u32, u32), required: (u32, u32)) -> bool {
+    actual.0 == required.0 && actual.1 >= required.1
+}
+
 /// A handle to the KFD driver character device (`/dev/kfd`).
 ///
 /// This struct provides methods to issue IOCTLs to the kernel driver.
@@ -55,11 +137,52 @@ impl KfdDevice {
         })
     }
 
+    /// Opens a KFD device node at a path other than `/dev/kfd`, e.g. one bind-mounted into a
+    /// sandbox.
+    ///
+    /// # Errors
+    /// Returns an error if `path` cannot be opened for reading and writing.
+    pub fn open_path(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        Ok(Self {
+            file: Arc::new(file),
+        })
+    }
+
+    /// Opens the KFD driver device read-only, for introspection tools.
+    ///
+    /// Queries such as `get_version`, `get_clock_counters`, `get_process_apertures_new` and
+    /// `get_available_memory` work on a read-only handle. Anything that maps KFD memory into
+    /// the CPU address space does not: CPU-visible allocations, doorbells, the events page
+    /// and MMIO remapping all need a shared writable mapping of this fd and fail with
+    /// `EACCES`. In practice that rules out queues, signals and host-accessible memory.
+    ///
+    /// # Errors
+    /// Returns an error if `/dev/kfd` cannot be opened for reading.
+    pub fn open_readonly() -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).open("/dev/kfd")?;
+
+        Ok(Self {
+            file: Arc::new(file),
+        })
+    }
+
     /// Generic unsafe helper to execute an IOCTL.
     ///
     /// # Safety
     /// The caller must ensure that `arg` points to valid memory appropriate for the specific `cmd`.
     pub unsafe fn ioctl<T>(&self, cmd: u32, arg: &mut T) -> io::Result<()> {
+        #[cfg(test)]
+        if let Some(result) = unsafe {
+            crate::kfd::mock::dispatch(
+                self.file.as_raw_fd(),
+                cmd,
+                std::ptr::from_mut::<T>(arg).cast(),
+            )
+        } {
+            return result;
+        }
         let ret = unsafe {
             libc::ioctl(
                 self.file.as_raw_fd(),
@@ -73,6 +196,21 @@ impl KfdDevice {
         Ok(())
     }
 
+    /// Issues an arbitrary KFD ioctl on this device.
+    ///
+    /// An escape hatch for ioctls the crate does not wrap yet. It is not part of the stable
+    /// API and is only available with the `unstable-raw-ioctl` feature.
+    ///
+    /// # Safety
+    /// `cmd` must be a KFD ioctl number whose encoded size and direction match `T`, and `T`
+    /// must have the exact `repr(C)` layout the kernel expects. Any pointers embedded in `arg`
+    /// must be valid for the accesses the kernel performs. Ioctls that create or destroy
+    /// objects the crate tracks (memory, queues, events) bypass that tracking.
+    #[cfg(feature = "unstable-raw-ioctl")]
+    pub unsafe fn raw_ioctl<T>(&self, cmd: u32, arg: &mut T) -> io::Result<()> {
+        unsafe { self.ioctl(cmd, arg) }
+    }
+
     // ===========================================================================================
     // Versioning
     // ===========================================================================================
@@ -86,6 +224,33 @@ impl KfdDevice {
         Ok(args)
     }
 
+    /// Checks that the running driver provides at least KFD ioctl version `major.minor`.
+    ///
+    /// # Errors
+    /// Fails with `InvalidData` if the driver is older (or of another major version), or
+    /// with the ioctl error if the version cannot be queried.
+    pub fn require_version(&self, major: u32, minor: u32) -> io::Result<()> {
+        let v = self.get_version()?;
+        if version_satisfies((v.major_version, v.minor_version), (major, minor)) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "KFD ioctl version {}.{} is older than the required {major}.{minor}",
+                    v.major_version, v.minor_version
+                ),
+            ))
+        }
+    }
+
+    /// Whether the running driver implements `feature`. `false` if the version query fails.
+    #[must_use]
+    pub fn supports_feature(&self, feature: KfdFeature) -> bool {
+        let (major, minor) = feature.min_version();
+        self.require_version(major, minor).is_ok()
+    }
+
     // ===========================================================================================
     // Queue Management
     // ===========================================================================================
@@ -203,6 +368,27 @@ impl KfdDevice {
         unsafe { self.ioctl(AMDKFD_IOC_GET_TILE_CONFIG, args) }
     }
 
+    /// Reads the tiling registers of `gpu_id`.
+    ///
+    /// KFD has no count query: it copies at most as many entries as requested and reports
+    /// how many it wrote, so the buffers start at the largest table size and are truncated.
+    pub fn read_tile_config(&self, gpu_id: u32) -> io::Result<TileConfig> {
+        let mut tile_config = vec![0u32; KFD_MAX_TILE_CONFIGS];
+        let mut macro_tile_config = vec![0u32; KFD_MAX_MACRO_TILE_CONFIGS];
+
+        let mut args = GetTileConfigArgs {
+            tile_config_ptr: tile_config.as_mut_ptr() as u64,
+            macro_tile_config_ptr: macro_tile_config.as_mut_ptr() as u64,
+            num_tile_configs: KFD_MAX_TILE_CONFIGS as u32,
+            num_macro_tile_configs: KFD_MAX_MACRO_TILE_CONFIGS as u32,
+            gpu_id,
+            ..Default::default()
+        };
+        self.get_tile_config(&mut args)?;
+
+        Ok(TileConfig::from_args(&args, tile_config, macro_tile_config))
+    }
+
     /// Retrieve GPU and System clock counters.
     pub fn get_clock_counters(&self, args: &mut GetClockCountersArgs) -> io::Result<()> {
         unsafe { self.ioctl(AMDKFD_IOC_GET_CLOCK_COUNTERS, args) }
@@ -311,6 +497,71 @@ impl KfdDevice {
         unsafe { self.ioctl(AMDKFD_IOC_SVM, args) }
     }
 
+    /// Reports which SVM attribute types the driver honors.
+    ///
+    /// Issues a `GET_ATTR` probe on a scratch page to check that the SVM ioctl is usable,
+    /// then derives the attribute set from the KFD version. Returns an
+    /// [`io::ErrorKind::Unsupported`] error when SVM is unavailable (e.g. non-HMM kernels).
+    pub fn svm_supported_attributes(&self) -> io::Result<SvmAttrSupport> {
+        #[repr(C)]
+        struct SvmGetAttr {
+            args: SvmArgs,
+            attrs: [SvmAttribute; 1],
+        }
+
+        let version = self.get_version()?;
+        let support = SvmAttrSupport::from_version(version.major_version, version.minor_version);
+        if support == SvmAttrSupport::default() {
+            return Err(io::Error::from(io::ErrorKind::Unsupported));
+        }
+
+        let page_size = 4096;
+        let page = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if page == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut probe = SvmGetAttr {
+            args: SvmArgs {
+                start_addr: page as u64,
+                size: page_size as u64,
+                op: KFD_IOCTL_SVM_OP_GET_ATTR,
+                nattr: 1,
+                attrs: [],
+            },
+            attrs: [SvmAttribute {
+                type_: KFD_IOCTL_SVM_ATTR_PREFERRED_LOC,
+                value: 0,
+            }],
+        };
+        let res = unsafe { self.ioctl(AMDKFD_IOC_SVM, &mut probe) };
+        unsafe {
+            libc::munmap(page, page_size);
+        }
+
+        match res {
+            Ok(()) => Ok(support),
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(libc::EPERM | libc::ENOTTY | libc::ENOSYS | libc::EOPNOTSUPP)
+                ) =>
+            {
+                Err(io::Error::from(io::ErrorKind::Unsupported))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Configure XNACK mode (retry on page fault).
     pub fn set_xnack_mode(&self, xnack_enabled: bool) -> io::Result<()> {
         let mut args = SetXnackModeArgs {
@@ -319,6 +570,15 @@ impl KfdDevice {
         unsafe { self.ioctl(AMDKFD_IOC_SET_XNACK_MODE, &mut args) }
     }
 
+    /// Reads the XNACK mode of this process without changing it.
+    ///
+    /// KFD treats a negative `xnack_enabled` as a query and writes the current mode back.
+    pub fn get_xnack_mode(&self) -> io::Result<bool> {
+        let mut args = SetXnackModeArgs { xnack_enabled: -1 };
+        unsafe { self.ioctl(AMDKFD_IOC_SET_XNACK_MODE, &mut args)? };
+        Ok(args.xnack_enabled > 0)
+    }
+
     /// System Management Interface (SMI) events.
     pub fn smi_events(&self, args: &mut SmiEventsArgs) -> io::Result<()> {
         unsafe { self.ioctl(AMDKFD_IOC_SMI_EVENTS, args) }
@@ -348,11 +608,56 @@ impl KfdDevice {
         unsafe { self.ioctl(AMDKFD_IOC_CROSS_MEMORY_COPY, args) }
     }
 
+    /// Copies memory out of process `pid` into this one and returns the bytes copied.
+    ///
+    /// `src_ranges` are `(address, size)` pairs in the target process, `dst_ranges` in the
+    /// calling process. Both are marshalled into `MemoryRange` arrays owned by this call, so
+    /// KFD only reads them for the duration of the ioctl. The read direction is forced by
+    /// clearing [`KFD_CROSS_MEMORY_RW_BIT`](crate::kfd::ioctl::KFD_CROSS_MEMORY_RW_BIT) in
+    /// `flags`.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails, e.g. without ptrace rights on `pid`.
+    pub fn copy_from_process(
+        &self,
+        pid: u32,
+        src_ranges: &[(u64, u64)],
+        dst_ranges: &[(u64, u64)],
+        flags: u32,
+    ) -> io::Result<u64> {
+        let src = MemoryRange::from_pairs(src_ranges);
+        let dst = MemoryRange::from_pairs(dst_ranges);
+
+        let mut args = CrossMemoryCopyArgs::read_from(pid, &src, &dst, flags);
+        self.cross_memory_copy(&mut args)?;
+        Ok(args.bytes_copied)
+    }
+
     /// Runtime enable (coordinates with debuggers).
     pub fn runtime_enable(&self, args: &mut RuntimeEnableArgs) -> io::Result<()> {
         unsafe { self.ioctl(AMDKFD_IOC_RUNTIME_ENABLE, args) }
     }
 
+    /// Enables (or disables) the runtime for debugger coordination.
+    ///
+    /// `r_debug_addr` is the address of the loader's `r_debug` structure, which an
+    /// attaching debugger reads to find code objects. The ioctl reports nothing back; the
+    /// state the driver records (which is `ENABLED_BUSY` or `ENABLED_ERROR` while a debugger
+    /// is attached) is only visible to the debugger through `DBG_TRAP_ENABLE`.
+    ///
+    /// # Errors
+    /// Returns the ioctl error with a message describing the likely cause, e.g. when the
+    /// runtime is already enabled.
+    pub fn enable_runtime(&self, r_debug_addr: u64, mode: RuntimeMode) -> io::Result<()> {
+        let mut args = RuntimeEnableArgs {
+            r_debug: r_debug_addr,
+            mode_mask: mode.mode_mask(),
+            capabilities_mask: 0,
+        };
+        self.runtime_enable(&mut args)
+            .map_err(|e| runtime_enable_error(&e))
+    }
+
     /// Streaming Performance Monitor (SPM).
     pub fn spm(&self, args: &mut SpmArgs) -> io::Result<()> {
         unsafe { self.ioctl(AMDKFD_IOC_RLC_SPM, args) }
@@ -372,6 +677,74 @@ impl KfdDevice {
     pub fn ais_op(&self, args: &mut AisArgs) -> io::Result<()> {
         unsafe { self.ioctl(AMDKFD_IOC_AIS_OP, args) }
     }
+
+    /// Reads `size` bytes at `file_offset` of `fd` straight into the buffer object `handle`
+    /// at `handle_offset`, returning the bytes transferred.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails or KFD reports a negative status.
+    pub fn ais_read(
+        &self,
+        handle: u64,
+        handle_offset: u64,
+        file_offset: i64,
+        size: u64,
+        fd: RawFd,
+    ) -> io::Result<u64> {
+        self.ais_transfer(
+            KFD_IOC_AIS_OP_READ,
+            handle,
+            handle_offset,
+            file_offset,
+            size,
+            fd,
+        )
+    }
+
+    /// Writes `size` bytes of the buffer object `handle` at `handle_offset` to `fd` at
+    /// `file_offset`, returning the bytes transferred.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails or KFD reports a negative status.
+    pub fn ais_write(
+        &self,
+        handle: u64,
+        handle_offset: u64,
+        file_offset: i64,
+        size: u64,
+        fd: RawFd,
+    ) -> io::Result<u64> {
+        self.ais_transfer(
+            KFD_IOC_AIS_OP_WRITE,
+            handle,
+            handle_offset,
+            file_offset,
+            size,
+            fd,
+        )
+    }
+
+    fn ais_transfer(
+        &self,
+        op: u32,
+        handle: u64,
+        handle_offset: u64,
+        file_offset: i64,
+        size: u64,
+        fd: RawFd,
+    ) -> io::Result<u64> {
+        let mut args = AisArgs::new(AisInArgs {
+            handle,
+            handle_offset,
+            file_offset,
+            size,
+            op,
+            fd,
+        });
+        self.ais_op(&mut args)?;
+        // KFD overwrites the input half with the result.
+        args.result()
+    }
 }
 
 impl AsRawFd for KfdDevice {
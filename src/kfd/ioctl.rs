@@ -1,4 +1,5 @@
 use crate::utils::{ior, iow, iowr};
+use std::{io, mem};
 
 // ===============================================================================================
 // Constants and Versioning
@@ -195,6 +196,15 @@ pub struct GetProcessAperturesArgs {
     pub pad: u32,
 }
 
+impl GetProcessAperturesArgs {
+    /// Apertures KFD filled in, clamped to the fixed array.
+    #[must_use]
+    pub fn filled(&self) -> &[ProcessDeviceApertures] {
+        let count = (self.num_of_nodes as usize).min(NUM_OF_SUPPORTED_GPUS);
+        &self.process_apertures[..count]
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct GetProcessAperturesNewArgs {
@@ -252,6 +262,16 @@ pub struct RuntimeInfo {
     pub ttmp_setup: u32,
 }
 
+// Runtime enable mode mask bits
+pub const KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK: u32 = 1;
+pub const KFD_RUNTIME_ENABLE_MODE_TTMP_SAVE_MASK: u32 = 2;
+
+// RuntimeInfo::runtime_state values
+pub const DEBUG_RUNTIME_STATE_DISABLED: u32 = 0;
+pub const DEBUG_RUNTIME_STATE_ENABLED: u32 = 1;
+pub const DEBUG_RUNTIME_STATE_ENABLED_BUSY: u32 = 2;
+pub const DEBUG_RUNTIME_STATE_ENABLED_ERROR: u32 = 3;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct RuntimeEnableArgs {
@@ -577,6 +597,44 @@ pub struct GetTileConfigArgs {
     pub num_ranks: u32,
 }
 
+/// Upper bound on tile mode registers across ASICs (amdgpu's `tile_mode_array`).
+pub const KFD_MAX_TILE_CONFIGS: usize = 32;
+/// Upper bound on macro tile mode registers (amdgpu's `macrotile_mode_array`).
+pub const KFD_MAX_MACRO_TILE_CONFIGS: usize = 16;
+
+/// Tiling registers of a GPU, as returned by [`AMDKFD_IOC_GET_TILE_CONFIG`].
+///
+/// Both arrays are empty on GFX9 and later, which no longer use tile mode tables.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TileConfig {
+    pub tile_config: Vec<u32>,
+    pub macro_tile_config: Vec<u32>,
+    pub gb_addr_config: u32,
+    pub num_banks: u32,
+    pub num_ranks: u32,
+}
+
+impl TileConfig {
+    /// Builds the result of a completed `GET_TILE_CONFIG`, keeping only the entries KFD
+    /// reported writing into the two buffers.
+    #[must_use]
+    pub fn from_args(
+        args: &GetTileConfigArgs,
+        mut tile_config: Vec<u32>,
+        mut macro_tile_config: Vec<u32>,
+    ) -> Self {
+        tile_config.truncate(args.num_tile_configs as usize);
+        macro_tile_config.truncate(args.num_macro_tile_configs as usize);
+        Self {
+            tile_config,
+            macro_tile_config,
+            gb_addr_config: args.gb_addr_config,
+            num_banks: args.num_banks,
+            num_ranks: args.num_ranks,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct SetTrapHandlerArgs {
@@ -704,10 +762,41 @@ pub struct SmiEventsArgs {
     pub anon_fd: u32,
 }
 
+pub const KFD_SMI_EVENT_NONE: u32 = 0;
+pub const KFD_SMI_EVENT_VMFAULT: u32 = 1;
+pub const KFD_SMI_EVENT_THERMAL_THROTTLE: u32 = 2;
+pub const KFD_SMI_EVENT_GPU_PRE_RESET: u32 = 3;
+pub const KFD_SMI_EVENT_GPU_POST_RESET: u32 = 4;
+pub const KFD_SMI_EVENT_MIGRATE_START: u32 = 5;
+pub const KFD_SMI_EVENT_MIGRATE_END: u32 = 6;
+pub const KFD_SMI_EVENT_PAGE_FAULT_START: u32 = 7;
+pub const KFD_SMI_EVENT_PAGE_FAULT_END: u32 = 8;
+pub const KFD_SMI_EVENT_QUEUE_EVICTION: u32 = 9;
+pub const KFD_SMI_EVENT_QUEUE_RESTORE: u32 = 10;
+pub const KFD_SMI_EVENT_UNMAP_FROM_GPU: u32 = 11;
+pub const KFD_SMI_EVENT_ALL_PROCESS: u32 = 64;
+
+/// `KFD_SMI_EVENT_MASK_FROM_INDEX`: the mask bit enabling event `i`.
+///
+/// Event ids start at 1; 0 (`KFD_SMI_EVENT_NONE`) and ids past 64 have no bit and give 0.
+#[must_use]
+pub const fn kfd_smi_event_mask_from_index(i: u32) -> u64 {
+    match i.checked_sub(1) {
+        Some(bit) if bit < u64::BITS => 1 << bit,
+        _ => 0,
+    }
+}
+
 // ===============================================================================================
 // SPM (Streaming Performance Monitor)
 // ===============================================================================================
 
+/// Takes exclusive ownership of the RLC SPM of a GPU.
+pub const KFD_IOCTL_SPM_OP_ACQUIRE: u32 = 0;
+pub const KFD_IOCTL_SPM_OP_RELEASE: u32 = 1;
+/// Hands KFD the next destination buffer and returns what was streamed into the previous one.
+pub const KFD_IOCTL_SPM_OP_SET_DEST_BUF: u32 = 2;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct SpmArgs {
@@ -733,6 +822,12 @@ pub struct SpmBufferHeader {
 // CRIU (Checkpoint Restore In Userspace)
 // ===============================================================================================
 
+pub const KFD_CRIU_OP_PROCESS_INFO: u32 = 0;
+pub const KFD_CRIU_OP_CHECKPOINT: u32 = 1;
+pub const KFD_CRIU_OP_UNPAUSE: u32 = 2;
+pub const KFD_CRIU_OP_RESTORE: u32 = 3;
+pub const KFD_CRIU_OP_RESUME: u32 = 4;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct CriuArgs {
@@ -797,6 +892,28 @@ pub struct IpcImportHandleArgs {
 // Cross Memory Copy
 // ===============================================================================================
 
+/// Set for a write into the target process, clear for a read out of it.
+pub const KFD_CROSS_MEMORY_RW_BIT: u32 = 1 << 0;
+
+/// One `(address, size)` entry of the range arrays in [`CrossMemoryCopyArgs`].
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryRange {
+    pub va_addr: u64,
+    pub size: u64,
+}
+
+impl MemoryRange {
+    /// Converts `(address, size)` pairs into a range array.
+    #[must_use]
+    pub fn from_pairs(ranges: &[(u64, u64)]) -> Vec<Self> {
+        ranges
+            .iter()
+            .map(|&(va_addr, size)| Self { va_addr, size })
+            .collect()
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct CrossMemoryCopyArgs {
@@ -809,6 +926,25 @@ pub struct CrossMemoryCopyArgs {
     pub bytes_copied: u64,
 }
 
+impl CrossMemoryCopyArgs {
+    /// Args for a read out of process `pid`, with [`KFD_CROSS_MEMORY_RW_BIT`] cleared from
+    /// `flags`.
+    ///
+    /// The args point into `src` and `dst`, which must outlive the ioctl.
+    #[must_use]
+    pub fn read_from(pid: u32, src: &[MemoryRange], dst: &[MemoryRange], flags: u32) -> Self {
+        Self {
+            pid,
+            flags: flags & !KFD_CROSS_MEMORY_RW_BIT,
+            src_mem_range_array: src.as_ptr() as u64,
+            src_mem_array_size: src.len() as u64,
+            dst_mem_range_array: dst.as_ptr() as u64,
+            dst_mem_array_size: dst.len() as u64,
+            bytes_copied: 0,
+        }
+    }
+}
+
 // ===============================================================================================
 // SVM (Shared Virtual Memory)
 // ===============================================================================================
@@ -822,6 +958,21 @@ pub const KFD_IOCTL_SVM_FLAG_GPU_READ_MOSTLY: u32 = 0x0000_0020;
 pub const KFD_IOCTL_SVM_FLAG_GPU_ALWAYS_MAPPED: u32 = 0x0000_0040;
 pub const KFD_IOCTL_SVM_FLAG_EXT_COHERENT: u32 = 0x0000_0080;
 
+pub const KFD_IOCTL_SVM_OP_SET_ATTR: u32 = 0;
+pub const KFD_IOCTL_SVM_OP_GET_ATTR: u32 = 1;
+
+pub const KFD_IOCTL_SVM_ATTR_PREFERRED_LOC: u32 = 0;
+pub const KFD_IOCTL_SVM_ATTR_PREFETCH_LOC: u32 = 1;
+pub const KFD_IOCTL_SVM_ATTR_ACCESS: u32 = 2;
+pub const KFD_IOCTL_SVM_ATTR_ACCESS_IN_PLACE: u32 = 3;
+pub const KFD_IOCTL_SVM_ATTR_NO_ACCESS: u32 = 4;
+pub const KFD_IOCTL_SVM_ATTR_SET_FLAGS: u32 = 5;
+pub const KFD_IOCTL_SVM_ATTR_CLR_FLAGS: u32 = 6;
+pub const KFD_IOCTL_SVM_ATTR_GRANULARITY: u32 = 7;
+
+pub const KFD_IOCTL_SVM_LOCATION_SYSMEM: u32 = 0;
+pub const KFD_IOCTL_SVM_LOCATION_UNDEFINED: u32 = 0xffff_ffff;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct SvmAttribute {
@@ -842,6 +993,90 @@ pub struct SvmArgs {
     pub attrs: [SvmAttribute; 0],
 }
 
+impl SvmArgs {
+    /// Builds the variable-length ioctl buffer: the `SvmArgs` header with `nattr` set,
+    /// immediately followed by `attrs`.
+    ///
+    /// The buffer is made of u64 words so the header is aligned; pass
+    /// `buf.as_mut_ptr().cast::<SvmArgs>()` to the ioctl.
+    #[must_use]
+    pub fn buffer(start_addr: u64, size: u64, op: u32, attrs: &[SvmAttribute]) -> Vec<u64> {
+        let bytes = mem::size_of::<Self>() + mem::size_of_val(attrs);
+        let mut buf = vec![0u64; bytes.div_ceil(mem::size_of::<u64>())];
+        let args = buf.as_mut_ptr().cast::<Self>();
+        unsafe {
+            args.write(Self {
+                start_addr,
+                size,
+                op,
+                nattr: attrs.len() as u32,
+                attrs: [],
+            });
+            std::ptr::copy_nonoverlapping(
+                attrs.as_ptr(),
+                (&raw mut (*args).attrs).cast::<SvmAttribute>(),
+                attrs.len(),
+            );
+        }
+        buf
+    }
+}
+
+/// Which SVM attribute types the running driver honors.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SvmAttrSupport {
+    pub preferred_location: bool,
+    pub prefetch_location: bool,
+    /// `ACCESS`, `ACCESS_IN_PLACE` and `NO_ACCESS`.
+    pub access: bool,
+    /// `SET_FLAGS` and `CLR_FLAGS`.
+    pub flags: bool,
+    pub granularity: bool,
+    /// `GET_ATTR` reports the cleared flags through `CLR_FLAGS` (KFD 1.6+).
+    pub get_clear_flags: bool,
+}
+
+impl SvmAttrSupport {
+    /// Derives support from the KFD ioctl version. The SVM API was added in 1.5.
+    #[must_use]
+    pub const fn from_version(major: u32, minor: u32) -> Self {
+        if major != KFD_IOCTL_MAJOR_VERSION || minor < 5 {
+            return Self {
+                preferred_location: false,
+                prefetch_location: false,
+                access: false,
+                flags: false,
+                granularity: false,
+                get_clear_flags: false,
+            };
+        }
+        Self {
+            preferred_location: true,
+            prefetch_location: true,
+            access: true,
+            flags: true,
+            granularity: true,
+            get_clear_flags: minor >= 6,
+        }
+    }
+
+    /// Returns whether an attribute of `attr_type` (`KFD_IOCTL_SVM_ATTR_*`) is honored.
+    #[must_use]
+    pub const fn supports(&self, attr_type: u32) -> bool {
+        match attr_type {
+            KFD_IOCTL_SVM_ATTR_PREFERRED_LOC => self.preferred_location,
+            KFD_IOCTL_SVM_ATTR_PREFETCH_LOC => self.prefetch_location,
+            KFD_IOCTL_SVM_ATTR_ACCESS
+            | KFD_IOCTL_SVM_ATTR_ACCESS_IN_PLACE
+            | KFD_IOCTL_SVM_ATTR_NO_ACCESS => self.access,
+            KFD_IOCTL_SVM_ATTR_SET_FLAGS | KFD_IOCTL_SVM_ATTR_CLR_FLAGS => self.flags,
+            KFD_IOCTL_SVM_ATTR_GRANULARITY => self.granularity,
+            _ => false,
+        }
+    }
+}
+
 // ===============================================================================================
 // XNACK
 // ===============================================================================================
@@ -856,6 +1091,21 @@ pub struct SetXnackModeArgs {
 // PC Sampling & Profiler
 // ===============================================================================================
 
+pub const KFD_IOCTL_PCS_OP_QUERY_CAPABILITIES: u32 = 0;
+pub const KFD_IOCTL_PCS_OP_CREATE: u32 = 1;
+pub const KFD_IOCTL_PCS_OP_DESTROY: u32 = 2;
+pub const KFD_IOCTL_PCS_OP_START: u32 = 3;
+pub const KFD_IOCTL_PCS_OP_STOP: u32 = 4;
+
+/// Samples taken by the host at a fixed interval through the trap handler.
+pub const KFD_IOCTL_PCS_METHOD_HOSTTRAP: u32 = 1;
+/// Samples taken by the hardware (MI300 and later).
+pub const KFD_IOCTL_PCS_METHOD_STOCHASTIC: u32 = 2;
+
+pub const KFD_IOCTL_PCS_TYPE_TIME_US: u32 = 0;
+pub const KFD_IOCTL_PCS_TYPE_CLOCK_CYCLES: u32 = 1;
+pub const KFD_IOCTL_PCS_TYPE_INSTRUCTIONS: u32 = 2;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct PcSampleInfo {
@@ -906,6 +1156,11 @@ pub struct ProfilerArgs {
 // AIS (AMD Infinity Storage)
 // ===============================================================================================
 
+/// File to buffer object.
+pub const KFD_IOC_AIS_OP_READ: u32 = 1;
+/// Buffer object to file.
+pub const KFD_IOC_AIS_OP_WRITE: u32 = 2;
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct AisInArgs {
@@ -938,6 +1193,31 @@ pub struct AisArgs {
     pub data: AisArgsUnion,
 }
 
+impl AisArgs {
+    /// Args for one transfer, with the input half filled in.
+    #[must_use]
+    pub const fn new(input: AisInArgs) -> Self {
+        Self {
+            data: AisArgsUnion { in_: input },
+        }
+    }
+
+    /// Decodes the output half KFD wrote over the input: the bytes copied, or the negated
+    /// errno in `status`.
+    ///
+    /// # Errors
+    /// Returns the OS error for a negative `status`.
+    pub fn result(&self) -> io::Result<u64> {
+        // Only meaningful after the ioctl; both halves are plain integers, so reading
+        // `out` is always sound.
+        let out = unsafe { self.data.out };
+        if out.status < 0 {
+            return Err(io::Error::from_raw_os_error(-out.status));
+        }
+        Ok(out.size_copied)
+    }
+}
+
 // ===============================================================================================
 // IOCTL Command Definitions
 // ===============================================================================================
@@ -0,0 +1,347 @@
+//! In-process stand-in for `/dev/kfd`, so unit tests can drive the ioctl paths.
+//!
+//! [`KfdDevice::ioctl`] hands every ioctl issued on a [`Mock`]'s file to the mock instead of
+//! the kernel. The file is a sparse memfd, so CPU mappings at the `mmap_offset`s the mock
+//! hands out behave like real buffer mappings.
+
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::{
+    AMDKFD_IOC_ALLOC_MEMORY_OF_GPU, AMDKFD_IOC_AVAILABLE_MEMORY, AMDKFD_IOC_CREATE_EVENT,
+    AMDKFD_IOC_CREATE_QUEUE, AMDKFD_IOC_DBG_TRAP, AMDKFD_IOC_DESTROY_EVENT,
+    AMDKFD_IOC_DESTROY_QUEUE, AMDKFD_IOC_FREE_MEMORY_OF_GPU, AMDKFD_IOC_GET_PROCESS_APERTURES,
+    AMDKFD_IOC_GET_PROCESS_APERTURES_NEW, AMDKFD_IOC_GET_VERSION, AMDKFD_IOC_MAP_MEMORY_TO_GPU,
+    AMDKFD_IOC_SET_EVENT, AMDKFD_IOC_UNMAP_MEMORY_FROM_GPU, AMDKFD_IOC_WAIT_EVENTS,
+    AllocMemoryOfGpuArgs, CreateEventArgs, CreateQueueArgs, DEBUG_RUNTIME_STATE_ENABLED,
+    DbgTrapArgs, DestroyEventArgs, DestroyQueueArgs, FreeMemoryOfGpuArgs, GetAvailableMemoryArgs,
+    GetProcessAperturesNewArgs, GetVersionArgs, KFD_DBG_QUEUE_INVALID_MASK,
+    KFD_IOC_ALLOC_MEM_FLAGS_USERPTR, KFD_IOC_DBG_TRAP_ENABLE, KFD_IOC_DBG_TRAP_RESUME_QUEUES,
+    KFD_IOC_DBG_TRAP_SUSPEND_QUEUES, KFD_IOC_WAIT_RESULT_COMPLETE, KFD_IOC_WAIT_RESULT_TIMEOUT,
+    MapMemoryToGpuArgs, ProcessDeviceApertures, RuntimeInfo, SetEventArgs, WaitEventsArgs,
+};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::{slice, thread, time::Duration};
+
+/// Size of the memfd behind every mapping. Sparse: only touched pages use memory.
+const FILE_SIZE: u64 = 1 << 32;
+const PAGE_SIZE: u64 = 4096;
+
+/// Live mocks, keyed by the fd of their file.
+static MOCKS: Mutex<Vec<(RawFd, Arc<Mutex<MockState>>)>> = Mutex::new(Vec::new());
+
+/// Held by every [`Mock`]: the topology, the MMIO apertures and the events page are process
+/// globals, so tests using them must not overlap.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Routes an ioctl on `fd` to its mock. `None` if `fd` is not a mock's file.
+///
+/// # Safety
+/// `arg` must point to the argument struct `cmd` encodes.
+pub(crate) unsafe fn dispatch(fd: RawFd, cmd: u32, arg: *mut u8) -> Option<io::Result<()>> {
+    let state = {
+        let mocks = MOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&mocks.iter().find(|(mock_fd, _)| *mock_fd == fd)?.1)
+    };
+    let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+    state.calls.push(cmd);
+    Some(unsafe { state.handle(cmd, arg) })
+}
+
+/// One `DBG_TRAP` call as the mock received it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DbgTrapCall {
+    pub pid: u32,
+    pub op: u32,
+    /// `exception_mask` of enable and suspend calls.
+    pub exception_mask: u64,
+    /// `dbg_fd` of an enable call.
+    pub dbg_fd: u32,
+    /// `rinfo_size` of an enable call.
+    pub rinfo_size: u32,
+    /// `grace_period` of a suspend call.
+    pub grace_period: u32,
+    /// Queue ids passed to suspend and resume calls.
+    pub queues: Vec<u32>,
+}
+
+/// What the mock driver has seen and how it answers.
+#[derive(Debug, Default)]
+pub(crate) struct MockState {
+    /// Every ioctl received, by command.
+    pub calls: Vec<u32>,
+    /// Live buffers by handle, with the arguments they were allocated with.
+    pub buffers: HashMap<u64, AllocMemoryOfGpuArgs>,
+    /// Handles freed so far, in order.
+    pub freed: Vec<u64>,
+    /// Fails `ALLOC_MEMORY_OF_GPU` with the errno returned for a request.
+    pub fail_alloc: Option<fn(&AllocMemoryOfGpuArgs) -> Option<i32>>,
+    /// Reported by `AVAILABLE_MEMORY`.
+    pub available_memory: u64,
+    /// Reported by `GET_PROCESS_APERTURES_NEW`.
+    pub apertures: Vec<ProcessDeviceApertures>,
+    /// Events page handles registered through `CREATE_EVENT`.
+    pub events_pages: Vec<u64>,
+    /// Live event ids.
+    pub events: Vec<u32>,
+    /// Replaces the events page slot `CREATE_EVENT` hands out, which is the event id.
+    pub event_slot: Option<u32>,
+    /// Queues created and not destroyed yet.
+    pub queues: HashMap<u32, CreateQueueArgs>,
+    pub dbg_traps: Vec<DbgTrapCall>,
+    /// Queue ids the debug trap flags with `KFD_DBG_QUEUE_INVALID_MASK`.
+    pub invalid_queues: Vec<u32>,
+    next_handle: u64,
+    next_offset: u64,
+    next_event: u32,
+    next_queue: u32,
+}
+
+impl MockState {
+    /// Number of ioctls of `cmd` received so far.
+    pub(crate) fn count(&self, cmd: u32) -> usize {
+        self.calls.iter().filter(|&&c| c == cmd).count()
+    }
+
+    /// Hands out a page-aligned range of the backing file.
+    fn take_offset(&mut self, size: u64) -> u64 {
+        let offset = self.next_offset;
+        self.next_offset += size.next_multiple_of(PAGE_SIZE);
+        assert!(self.next_offset <= FILE_SIZE, "mock KFD file exhausted");
+        offset
+    }
+
+    unsafe fn handle(&mut self, cmd: u32, arg: *mut u8) -> io::Result<()> {
+        let einval = || Err(io::Error::from_raw_os_error(libc::EINVAL));
+        unsafe {
+            match cmd {
+                AMDKFD_IOC_GET_VERSION => {
+                    *arg.cast::<GetVersionArgs>() = GetVersionArgs {
+                        major_version: 1,
+                        minor_version: 17,
+                    };
+                }
+                AMDKFD_IOC_GET_PROCESS_APERTURES_NEW => {
+                    let args = &mut *arg.cast::<GetProcessAperturesNewArgs>();
+                    let n = self.apertures.len().min(args.num_of_nodes as usize);
+                    let out = slice::from_raw_parts_mut(
+                        args.kfd_process_device_apertures_ptr as *mut ProcessDeviceApertures,
+                        n,
+                    );
+                    out.copy_from_slice(&self.apertures[..n]);
+                    args.num_of_nodes = n as u32;
+                }
+                AMDKFD_IOC_GET_PROCESS_APERTURES => {
+                    return Err(io::Error::from_raw_os_error(libc::ENOTTY));
+                }
+                AMDKFD_IOC_ALLOC_MEMORY_OF_GPU => {
+                    let args = &mut *arg.cast::<AllocMemoryOfGpuArgs>();
+                    if let Some(errno) = self.fail_alloc.and_then(|fail| fail(args)) {
+                        return Err(io::Error::from_raw_os_error(errno));
+                    }
+                    self.next_handle += 1;
+                    args.handle = self.next_handle;
+                    // Userptr buffers pass the host address in `mmap_offset`.
+                    if args.flags & KFD_IOC_ALLOC_MEM_FLAGS_USERPTR == 0 {
+                        args.mmap_offset = self.take_offset(args.size);
+                    }
+                    self.buffers.insert(args.handle, *args);
+                }
+                AMDKFD_IOC_FREE_MEMORY_OF_GPU => {
+                    let handle = (*arg.cast::<FreeMemoryOfGpuArgs>()).handle;
+                    if self.buffers.remove(&handle).is_none() {
+                        return einval();
+                    }
+                    self.freed.push(handle);
+                }
+                AMDKFD_IOC_MAP_MEMORY_TO_GPU | AMDKFD_IOC_UNMAP_MEMORY_FROM_GPU => {
+                    // Both argument structs share one layout.
+                    let args = &mut *arg.cast::<MapMemoryToGpuArgs>();
+                    if !self.buffers.contains_key(&args.handle) {
+                        return einval();
+                    }
+                    args.n_success = args.n_devices;
+                }
+                AMDKFD_IOC_AVAILABLE_MEMORY => {
+                    (*arg.cast::<GetAvailableMemoryArgs>()).available = self.available_memory;
+                }
+                AMDKFD_IOC_CREATE_EVENT => {
+                    let args = &mut *arg.cast::<CreateEventArgs>();
+                    if args.event_page_offset != 0 {
+                        self.events_pages.push(args.event_page_offset);
+                    }
+                    self.next_event += 1;
+                    args.event_id = self.next_event;
+                    args.event_slot_index = self.event_slot.unwrap_or(args.event_id);
+                    args.event_trigger_data = args.event_id;
+                    self.events.push(args.event_id);
+                }
+                AMDKFD_IOC_DESTROY_EVENT => {
+                    let id = (*arg.cast::<DestroyEventArgs>()).event_id;
+                    let Some(pos) = self.events.iter().position(|&e| e == id) else {
+                        return einval();
+                    };
+                    self.events.remove(pos);
+                }
+                AMDKFD_IOC_SET_EVENT
+                    if !self
+                        .events
+                        .contains(&(*arg.cast::<SetEventArgs>()).event_id) =>
+                {
+                    return einval();
+                }
+                AMDKFD_IOC_WAIT_EVENTS => {
+                    // Nothing ever fires: waits up to 1 ms time out, longer ones wake up
+                    // spuriously after 1 ms so the caller rechecks its condition.
+                    let args = &mut *arg.cast::<WaitEventsArgs>();
+                    thread::sleep(Duration::from_millis(u64::from(args.timeout.min(1))));
+                    args.wait_result = if args.timeout > 1 {
+                        KFD_IOC_WAIT_RESULT_COMPLETE
+                    } else {
+                        KFD_IOC_WAIT_RESULT_TIMEOUT
+                    };
+                }
+                AMDKFD_IOC_CREATE_QUEUE => {
+                    let args = &mut *arg.cast::<CreateQueueArgs>();
+                    self.next_queue += 1;
+                    args.queue_id = self.next_queue;
+                    args.doorbell_offset = self.take_offset(PAGE_SIZE);
+                    self.queues.insert(args.queue_id, *args);
+                }
+                AMDKFD_IOC_DESTROY_QUEUE => {
+                    let id = (*arg.cast::<DestroyQueueArgs>()).queue_id;
+                    if self.queues.remove(&id).is_none() {
+                        return einval();
+                    }
+                }
+                AMDKFD_IOC_DBG_TRAP => self.dbg_trap(&mut *arg.cast::<DbgTrapArgs>()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn dbg_trap(&mut self, args: &mut DbgTrapArgs) {
+        let mut call = DbgTrapCall {
+            pid: args.pid,
+            op: args.op,
+            exception_mask: 0,
+            dbg_fd: 0,
+            rinfo_size: 0,
+            grace_period: 0,
+            queues: Vec::new(),
+        };
+        let (queue_ptr, num_queues) = unsafe {
+            match args.op {
+                KFD_IOC_DBG_TRAP_ENABLE => {
+                    let enable = args.data.enable;
+                    call.exception_mask = enable.exception_mask;
+                    call.dbg_fd = enable.dbg_fd;
+                    call.rinfo_size = enable.rinfo_size;
+                    if enable.rinfo_size as usize >= size_of::<RuntimeInfo>() {
+                        *(enable.rinfo_ptr as *mut RuntimeInfo) = RuntimeInfo {
+                            r_debug: 0xdeb6,
+                            runtime_state: DEBUG_RUNTIME_STATE_ENABLED,
+                            ttmp_setup: 1,
+                        };
+                    }
+                    (0, 0)
+                }
+                KFD_IOC_DBG_TRAP_SUSPEND_QUEUES => {
+                    let suspend = args.data.suspend_queues;
+                    call.exception_mask = suspend.exception_mask;
+                    call.grace_period = suspend.grace_period;
+                    (suspend.queue_array_ptr, suspend.num_queues)
+                }
+                KFD_IOC_DBG_TRAP_RESUME_QUEUES => {
+                    let resume = args.data.resume_queues;
+                    (resume.queue_array_ptr, resume.num_queues)
+                }
+                _ => (0, 0),
+            }
+        };
+        if num_queues > 0 {
+            let queues =
+                unsafe { slice::from_raw_parts_mut(queue_ptr as *mut u32, num_queues as usize) };
+            call.queues = queues.to_vec();
+            for id in queues.iter_mut() {
+                if self.invalid_queues.contains(id) {
+                    *id |= KFD_DBG_QUEUE_INVALID_MASK;
+                }
+            }
+        }
+        self.dbg_traps.push(call);
+    }
+}
+
+/// A mock `/dev/kfd`. Dropping it detaches the mock; devices cloned from it then reach the
+/// real (memfd) file and fail.
+pub(crate) struct Mock {
+    pub device: KfdDevice,
+    state: Arc<Mutex<MockState>>,
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl Mock {
+    pub(crate) fn new() -> Self {
+        let serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let fd = unsafe { libc::memfd_create(c"mock-kfd".as_ptr(), libc::MFD_CLOEXEC) };
+        assert!(fd >= 0, "memfd_create: {}", io::Error::last_os_error());
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.set_len(FILE_SIZE).unwrap();
+
+        let state = Arc::new(Mutex::new(MockState::default()));
+        MOCKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((file.as_raw_fd(), Arc::clone(&state)));
+
+        Self {
+            device: KfdDevice {
+                file: Arc::new(file),
+            },
+            state,
+            _serial: serial,
+        }
+    }
+
+    /// A mock reporting one GPU, `gpu_id`, with its apertures.
+    pub(crate) fn with_gpu(gpu_id: u32) -> Self {
+        let mock = Self::new();
+        mock.state().apertures.push(ProcessDeviceApertures {
+            lds_base: 0x1_0000_0000_0000,
+            lds_limit: 0x1_0000_ffff_ffff,
+            scratch_base: 0x2_0000_0000_0000,
+            scratch_limit: 0x2_0000_ffff_ffff,
+            gpuvm_base: 0x1000_0000,
+            gpuvm_limit: 0x7fff_ffff_ffff,
+            gpu_id,
+            pad: 0,
+        });
+        mock
+    }
+
+    pub(crate) fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stands in for the DRM render node: host-accessible buffers map the same file.
+    pub(crate) fn drm_fd(&self) -> RawFd {
+        self.device.file.as_raw_fd()
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        let fd = self.device.file.as_raw_fd();
+        MOCKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|(mock_fd, _)| *mock_fd != fd);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod device;
 pub mod ioctl;
+#[cfg(test)]
+pub(crate) mod mock;
 pub mod sysfs;
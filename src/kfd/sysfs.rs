@@ -4,13 +4,19 @@
     clippy::similar_names
 )]
 
-use std::collections::HashMap;
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::GetClockCountersArgs;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 const KFD_SYSFS_PATH: &str = "/sys/devices/virtual/kfd/kfd/topology";
+/// Environment variable that replaces [`KFD_SYSFS_PATH`], e.g. to parse a captured topology
+/// tree offline or inside a container.
+pub const KFD_SYSFS_ROOT_ENV: &str = "KFD_SYSFS_ROOT";
 const AMDGPU_IDS_PATHS: &[&str] = &[
     "/usr/share/libdrm/amdgpu.ids",
     "/usr/local/share/libdrm/amdgpu.ids",
@@ -26,8 +32,41 @@ pub const HSA_IOLINKTYPE_XGMI: u32 = 3;
 pub const HSA_IOLINKTYPE_NUMA: u32 = 4;
 pub const HSA_IOLINKTYPE_QPI_1_1: u32 = 5;
 
+// Node `capability` bits, from `kfd_topology.h`.
+pub const HSA_CAP_HOT_PLUGGABLE: u32 = 0x0000_0001;
+pub const HSA_CAP_ATS_PRESENT: u32 = 0x0000_0002;
+pub const HSA_CAP_SHARED_WITH_GRAPHICS: u32 = 0x0000_0004;
+pub const HSA_CAP_QUEUE_SIZE_POW2: u32 = 0x0000_0008;
+pub const HSA_CAP_QUEUE_SIZE_32BIT: u32 = 0x0000_0010;
+pub const HSA_CAP_QUEUE_IDLE_EVENT: u32 = 0x0000_0020;
+pub const HSA_CAP_VA_LIMIT: u32 = 0x0000_0040;
+pub const HSA_CAP_WATCH_POINTS_SUPPORTED: u32 = 0x0000_0080;
+pub const HSA_CAP_WATCH_POINTS_TOTALBITS_MASK: u32 = 0x0000_0f00;
+pub const HSA_CAP_WATCH_POINTS_TOTALBITS_SHIFT: u32 = 8;
+pub const HSA_CAP_DOORBELL_TYPE_TOTALBITS_MASK: u32 = 0x0000_3000;
+pub const HSA_CAP_DOORBELL_TYPE_TOTALBITS_SHIFT: u32 = 12;
+pub const HSA_CAP_AQL_QUEUE_DOUBLE_MAP: u32 = 0x0000_4000;
+pub const HSA_CAP_TRAP_DEBUG_SUPPORT: u32 = 0x0000_8000;
+pub const HSA_CAP_TRAP_DEBUG_WAVE_LAUNCH_TRAP_OVERRIDE_SUPPORTED: u32 = 0x0001_0000;
+pub const HSA_CAP_TRAP_DEBUG_WAVE_LAUNCH_MODE_SUPPORTED: u32 = 0x0002_0000;
+pub const HSA_CAP_TRAP_DEBUG_PRECISE_MEMORY_OPERATIONS_SUPPORTED: u32 = 0x0004_0000;
+pub const HSA_CAP_MEM_EDCSUPPORTED: u32 = 0x0010_0000;
+pub const HSA_CAP_RASEVENTNOTIFY: u32 = 0x0020_0000;
+pub const HSA_CAP_ASIC_REVISION_MASK: u32 = 0x03c0_0000;
+pub const HSA_CAP_ASIC_REVISION_SHIFT: u32 = 22;
+pub const HSA_CAP_SRAM_EDCSUPPORTED: u32 = 0x0400_0000;
+/// `HSA_CAP_SVMAPI_SUPPORTED`: the node can take part in SVM, i.e. recoverable page faults.
+pub const HSA_CAP_SVMAPI_SUPPORTED: u32 = 0x0800_0000;
+pub const HSA_CAP_FLAGS_COHERENTHOSTACCESS: u32 = 0x1000_0000;
+pub const HSA_CAP_TRAP_DEBUG_FIRMWARE_SUPPORTED: u32 = 0x2000_0000;
+pub const HSA_CAP_TRAP_DEBUG_PRECISE_ALU_OPERATIONS_SUPPORTED: u32 = 0x4000_0000;
+
+// Node `capability2` bits.
+pub const HSA_CAP2_PER_SDMA_QUEUE_RESET_SUPPORTED: u32 = 0x0000_0001;
+
 const SGPR_SIZE_PER_CU: u32 = 32 * 1024; // 32KB
 
+#[derive(Debug, Clone, Copy)]
 struct GfxIpLookup {
     device_id: u16,
     major: u8,
@@ -1325,13 +1364,43 @@ const GFXIP_LOOKUP_TABLE: &[GfxIpLookup] = &[
     },
 ];
 
-fn find_gfx_ip(device_id: u16, major_version: u8) -> Option<&'static GfxIpLookup> {
+static GFXIP_OVERRIDES: OnceLock<Mutex<HashMap<u16, GfxIpLookup>>> = OnceLock::new();
+
+fn gfx_ip_overrides() -> &'static Mutex<HashMap<u16, GfxIpLookup>> {
+    GFXIP_OVERRIDES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Registers the GFX IP version of a device id, for ASICs newer than the built-in table.
+///
+/// Registered entries take precedence over the built-in table, so they can also correct
+/// it. Only topology snapshots taken after the call pick up the entry.
+///
+/// # Panics
+/// Panics if the override table mutex is poisoned.
+pub fn register_gfx_ip(device_id: u16, major: u8, minor: u8, stepping: u8, name: &'static str) {
+    gfx_ip_overrides().lock().unwrap().insert(
+        device_id,
+        GfxIpLookup {
+            device_id,
+            major,
+            minor,
+            stepping,
+            name,
+        },
+    );
+}
+
+fn find_gfx_ip(device_id: u16, major_version: u8) -> Option<GfxIpLookup> {
+    if let Some(entry) = gfx_ip_overrides().lock().unwrap().get(&device_id) {
+        return Some(*entry);
+    }
     if major_version > 14 {
         return None;
     }
     GFXIP_LOOKUP_TABLE
         .iter()
         .find(|entry| entry.device_id == device_id)
+        .copied()
 }
 
 /// Helper to parse the amdgpu.ids file from libdrm
@@ -1399,30 +1468,18 @@ fn get_pci_revision_id(domain: u32, location_id: u32) -> Option<u32> {
 }
 
 /// Logic to emulate `hsakmt_get_vgpr_size_per_cu` based on GFX version
-const fn get_vgpr_size_per_cu(major: u32, minor: u32, stepping: u32) -> u32 {
-    // Combine into GFX version integer (e.g., 90010 for 9.0.10)
-    // Note: The shifting logic here (major << 16) is different from how
-    // ROCm usually represents it (decimal: 90010).
-
-    // Check for "Large VGPR" GFX9 devices (Aldebaran, Arcturus, MI300)
-    #[rustfmt::skip]
-    let is_large_vgpr_gfx9 = major == 9
-        && (
-            (minor == 0 && stepping == 8) ||    // Arcturus
-            (minor == 4) ||                     // Aldebaran (9.4.2) & Aqua Vanjaram family
-            (minor == 5 && stepping == 0)       // GFX950
-        );
-
-    if is_large_vgpr_gfx9 {
-        return 524_288; // 512 KB
-    }
-
-    if major >= 11 {
-        return 393_216; // 384 KB (RDNA3+)
+#[must_use]
+pub const fn get_vgpr_size_per_cu(major: u32, minor: u32, stepping: u32) -> u32 {
+    match (major, minor, stepping) {
+        // Arcturus (9.0.8), Aldebaran (9.0.10), the Aqua Vanjaram family (9.4.x) and GFX950
+        // (9.5.0) have the unified 512-entry VGPR file.
+        (9, 0, 8 | 10) | (9, 4, _) | (9, 5, 0) => 0x80000, // 512 KB
+        // Navi31/32 and GFX12 dGPUs have the 1.5x VGPR file; Navi33 and the RDNA3 APUs
+        // do not.
+        (11, 0, 0 | 1) | (12, 0, 0 | 1) => 0x60000, // 384 KB
+        // GFX8, GFX9 (Vega), GFX10 (RDNA1/2) and the rest of GFX11
+        _ => 0x40000, // 256 KB
     }
-
-    // Default for GFX8, GFX9 (Vega), GFX10 (RDNA1/2)
-    262_144 // 256 KB
 }
 
 // ===============================================================================================
@@ -1435,6 +1492,8 @@ pub struct HsaSystemProperties {
     pub platform_id: u32,
     pub platform_rev: u32,
     pub num_nodes: u32,
+    /// Frequency in Hz of the GPU system clock domain, the one `timeout_hint_clocks` and
+    /// the KFD clock counters are expressed in.
     pub timestamp_frequency: u64,
 }
 
@@ -1504,6 +1563,343 @@ pub struct HsaNodeProperties {
     pub vgpr_size_per_cu: u32,
 }
 
+/// Whether `gfx_target_version` names an ASIC that only ships as an APU
+/// (Carrizo, Raven, Renoir, Van Gogh, Rembrandt, Raphael, Mendocino, Phoenix, Strix).
+const fn is_apu_gfx_version(gfx_target_version: u32) -> bool {
+    matches!(
+        gfx_target_version,
+        80001 | 90002 | 90012 | 100303 | 100305 | 100306 | 100307 | 110003 | 110500 | 110501
+    )
+}
+
+impl HsaNodeProperties {
+    /// Whether the node has GPU compute units.
+    #[must_use]
+    pub const fn is_gpu(&self) -> bool {
+        self.simd_count > 0
+    }
+
+    /// Number of shader engines (SEs).
+    ///
+    /// KFD reports `array_count = shader_engines * shader_arrays_per_engine`, summed over
+    /// all XCCs, so the SE count is the quotient (Navi21: 8 / 2 = 4, MI200 GCD: 8 / 1 = 8).
+    /// Returns 0 for CPU nodes.
+    #[must_use]
+    pub const fn shader_engines(&self) -> u32 {
+        match self.array_count.checked_div(self.simd_arrays_per_engine) {
+            Some(n) => n,
+            None => 0,
+        }
+    }
+
+    /// Number of shader arrays (SAs) per shader engine, as reported by KFD in
+    /// `simd_arrays_per_engine` (2 on GFX10/11 parts, 1 on GFX9 data-center parts).
+    #[must_use]
+    pub const fn shader_arrays_per_engine(&self) -> u32 {
+        self.simd_arrays_per_engine
+    }
+
+    /// Theoretical number of SDMA queues on this node: the PCIe and XGMI engines times
+    /// `num_sdma_queues_per_engine`. Returns 0 for CPU nodes and kernels that do not
+    /// report the per-engine count.
+    #[must_use]
+    pub const fn sdma_queue_budget(&self) -> u32 {
+        (self.num_sdma_engines + self.num_sdma_xgmi_engines)
+            .saturating_mul(self.num_sdma_queues_per_engine)
+    }
+
+    /// Decodes the `capability` and `capability2` words.
+    #[must_use]
+    pub const fn capabilities(&self) -> NodeCapabilities {
+        NodeCapabilities::from_raw(self.capability, self.capability2)
+    }
+
+    /// Number of compute units, summed over all XCCs. Returns 0 for CPU nodes.
+    #[must_use]
+    pub const fn cu_count(&self) -> u32 {
+        match self.simd_count.checked_div(self.simd_per_cu) {
+            Some(n) => n,
+            None => 0,
+        }
+    }
+
+    /// Theoretical peak throughput in FLOP/s at the peak shader clock.
+    ///
+    /// `cu_count * max_sclk_mhz * ops`, where `ops` is the per-CU, per-clock rate of the
+    /// architecture (an FMA counts as 2). `None` for CPU nodes, unknown architectures,
+    /// precisions the architecture has no unit for, or an unknown clock.
+    #[must_use]
+    pub fn peak_flops(&self, precision: Precision) -> Option<u64> {
+        let ops = flops_per_cu_per_clock(self.engine_id, precision)?;
+        let cus = u64::from(self.cu_count());
+        let mhz = u64::from(self.max_sclk_mhz());
+        (cus > 0 && mhz > 0).then(|| cus * mhz * 1_000_000 * u64::from(ops))
+    }
+
+    /// Estimates how many waves of a kernel fit on each SIMD, and which resource runs out
+    /// first.
+    ///
+    /// `lds_bytes` is the LDS used per wave (a workgroup's allocation divided by its waves).
+    /// A `wavefront_size` of 0 uses the node's native size. Register allocation granularity
+    /// is not modeled. SGPRs only limit GFX9 and older, later parts give every wave a fixed
+    /// SGPR file. Returns `None` for CPU nodes.
+    #[must_use]
+    pub fn occupancy(
+        &self,
+        vgprs_per_thread: u32,
+        sgprs_per_thread: u32,
+        lds_bytes: u32,
+        wavefront_size: u32,
+    ) -> Option<OccupancyEstimate> {
+        if self.simd_count == 0 || self.simd_per_cu == 0 {
+            return None;
+        }
+        let wave_size = if wavefront_size == 0 {
+            self.wave_front_size
+        } else {
+            wavefront_size
+        };
+        let limit = |per_cu: u32, per_wave: u32| {
+            (per_cu / self.simd_per_cu)
+                .checked_div(per_wave)
+                .unwrap_or(u32::MAX)
+        };
+
+        let sgpr_limit = if self.engine_id.major <= 9 {
+            limit(self.sgpr_size_per_cu, sgprs_per_thread.saturating_mul(4))
+        } else {
+            u32::MAX
+        };
+        let candidates = [
+            (OccupancyLimiter::WaveSlots, self.max_waves_per_simd),
+            (
+                OccupancyLimiter::Vgpr,
+                limit(
+                    self.vgpr_size_per_cu,
+                    vgprs_per_thread.saturating_mul(wave_size).saturating_mul(4),
+                ),
+            ),
+            (OccupancyLimiter::Sgpr, sgpr_limit),
+            (
+                OccupancyLimiter::Lds,
+                limit(self.lds_size_in_kb.saturating_mul(1024), lds_bytes),
+            ),
+        ];
+        let (limiter, waves_per_simd) = candidates.into_iter().min_by_key(|&(_, n)| n)?;
+
+        Some(OccupancyEstimate {
+            limiter,
+            waves_per_simd,
+            active_waves: waves_per_simd.saturating_mul(self.simd_count),
+        })
+    }
+
+    /// Peak shader clock in MHz.
+    ///
+    /// Prefers KFD's `max_engine_clk_fcompute`, falling back to the highest `pp_dpm_sclk`
+    /// level when KFD reports 0. Warns when both are known and differ by more than
+    /// [`SCLK_MISMATCH_PERCENT`]. Returns 0 if neither source knows the clock.
+    #[must_use]
+    pub fn max_sclk_mhz(&self) -> u32 {
+        let dpm = get_pci_max_sclk_mhz(self.domain, self.location_id);
+        let (mhz, disagreeing) = reconcile_sclk_mhz(self.max_engine_clk_fcompute, dpm);
+        if let Some(dpm) = disagreeing {
+            eprintln!(
+                "[WARN] Node {}: KFD max sclk {mhz} MHz disagrees with pp_dpm_sclk {dpm} MHz",
+                self.node_id
+            );
+        }
+        mhz
+    }
+
+    /// Per-component firmware versions from amdgpu's `fw_version/` sysfs directory, keyed by
+    /// component name (`mec`, `smc`, `sdma`, ...).
+    ///
+    /// # Errors
+    /// Fails if the directory cannot be read, e.g. for CPU nodes.
+    pub fn firmware_versions(&self) -> io::Result<HashMap<String, u32>> {
+        read_fw_versions(&pci_device_path(self.domain, self.location_id).join("fw_version"))
+    }
+
+    /// The VBIOS version string, e.g. `113-D4120100-100`.
+    ///
+    /// # Errors
+    /// Fails if amdgpu does not expose `vbios_version` for this device.
+    pub fn vbios_version(&self) -> io::Result<String> {
+        let path = pci_device_path(self.domain, self.location_id).join("vbios_version");
+        Ok(fs::read_to_string(path)?.trim().to_string())
+    }
+
+    /// Collects the firmware and VBIOS versions of this GPU for support reports.
+    ///
+    /// Unlike the individual getters this never fails: what amdgpu does not expose is left
+    /// empty.
+    #[must_use]
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            vbios_version: self.vbios_version().ok(),
+            firmware_versions: self
+                .firmware_versions()
+                .map(|v| v.into_iter().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Firmware details of a GPU node for support diagnostics, see
+/// [`HsaNodeProperties::diagnostics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// The VBIOS version string, `None` if amdgpu does not expose it.
+    pub vbios_version: Option<String>,
+    /// Per-component firmware versions, ordered by component name.
+    pub firmware_versions: BTreeMap<String, u32>,
+}
+
+/// Node capability bits KFD reports in `capability`/`capability2`, see
+/// [`HsaNodeProperties::capabilities`].
+///
+/// Each field mirrors the `HSA_CAP_*` bit of the same name. CWSR support and memory
+/// policies are not reported here: KFD enables CWSR per GPU family without a topology bit.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeCapabilities {
+    pub hot_pluggable: bool,
+    /// Address Translation Services (IOMMUv2) present.
+    pub ats_present: bool,
+    pub shared_with_graphics: bool,
+    /// Queue sizes must be a power of two.
+    pub queue_size_pow2: bool,
+    /// Queue sizes are limited to 32 bits.
+    pub queue_size_32bit: bool,
+    pub queue_idle_event: bool,
+    pub va_limit: bool,
+    pub watch_points_supported: bool,
+    /// log2 of the number of address watch points.
+    pub watch_points_total_bits: u32,
+    /// 0 = pre-SOC15 doorbells, 1 = SOC15 (64-bit) doorbells.
+    pub doorbell_type: u32,
+    pub aql_queue_double_map: bool,
+    pub trap_debug_supported: bool,
+    pub trap_debug_wave_launch_trap_override: bool,
+    pub trap_debug_wave_launch_mode: bool,
+    pub trap_debug_precise_memory_operations: bool,
+    pub mem_edc_supported: bool,
+    pub ras_event_notify: bool,
+    pub asic_revision: u32,
+    pub sram_edc_supported: bool,
+    pub svm_api_supported: bool,
+    /// The CPU can access VRAM coherently (e.g. MI300A, or XGMI-connected hosts).
+    pub coherent_host_access: bool,
+    pub trap_debug_firmware_supported: bool,
+    pub trap_debug_precise_alu_operations: bool,
+    /// SDMA queues can be reset individually (`capability2`).
+    pub per_sdma_queue_reset: bool,
+}
+
+impl NodeCapabilities {
+    /// Decodes the raw `capability` and `capability2` words.
+    #[must_use]
+    pub const fn from_raw(cap: u32, cap2: u32) -> Self {
+        Self {
+            hot_pluggable: cap & HSA_CAP_HOT_PLUGGABLE != 0,
+            ats_present: cap & HSA_CAP_ATS_PRESENT != 0,
+            shared_with_graphics: cap & HSA_CAP_SHARED_WITH_GRAPHICS != 0,
+            queue_size_pow2: cap & HSA_CAP_QUEUE_SIZE_POW2 != 0,
+            queue_size_32bit: cap & HSA_CAP_QUEUE_SIZE_32BIT != 0,
+            queue_idle_event: cap & HSA_CAP_QUEUE_IDLE_EVENT != 0,
+            va_limit: cap & HSA_CAP_VA_LIMIT != 0,
+            watch_points_supported: cap & HSA_CAP_WATCH_POINTS_SUPPORTED != 0,
+            watch_points_total_bits: (cap & HSA_CAP_WATCH_POINTS_TOTALBITS_MASK)
+                >> HSA_CAP_WATCH_POINTS_TOTALBITS_SHIFT,
+            doorbell_type: (cap & HSA_CAP_DOORBELL_TYPE_TOTALBITS_MASK)
+                >> HSA_CAP_DOORBELL_TYPE_TOTALBITS_SHIFT,
+            aql_queue_double_map: cap & HSA_CAP_AQL_QUEUE_DOUBLE_MAP != 0,
+            trap_debug_supported: cap & HSA_CAP_TRAP_DEBUG_SUPPORT != 0,
+            trap_debug_wave_launch_trap_override: cap
+                & HSA_CAP_TRAP_DEBUG_WAVE_LAUNCH_TRAP_OVERRIDE_SUPPORTED
+                != 0,
+            trap_debug_wave_launch_mode: cap & HSA_CAP_TRAP_DEBUG_WAVE_LAUNCH_MODE_SUPPORTED != 0,
+            trap_debug_precise_memory_operations: cap
+                & HSA_CAP_TRAP_DEBUG_PRECISE_MEMORY_OPERATIONS_SUPPORTED
+                != 0,
+            mem_edc_supported: cap & HSA_CAP_MEM_EDCSUPPORTED != 0,
+            ras_event_notify: cap & HSA_CAP_RASEVENTNOTIFY != 0,
+            asic_revision: (cap & HSA_CAP_ASIC_REVISION_MASK) >> HSA_CAP_ASIC_REVISION_SHIFT,
+            sram_edc_supported: cap & HSA_CAP_SRAM_EDCSUPPORTED != 0,
+            svm_api_supported: cap & HSA_CAP_SVMAPI_SUPPORTED != 0,
+            coherent_host_access: cap & HSA_CAP_FLAGS_COHERENTHOSTACCESS != 0,
+            trap_debug_firmware_supported: cap & HSA_CAP_TRAP_DEBUG_FIRMWARE_SUPPORTED != 0,
+            trap_debug_precise_alu_operations: cap
+                & HSA_CAP_TRAP_DEBUG_PRECISE_ALU_OPERATIONS_SUPPORTED
+                != 0,
+            per_sdma_queue_reset: cap2 & HSA_CAP2_PER_SDMA_QUEUE_RESET_SUPPORTED != 0,
+        }
+    }
+}
+
+/// Arithmetic precision for [`HsaNodeProperties::peak_flops`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precision {
+    Fp64,
+    Fp32,
+    Fp16,
+    /// Dense matrix FP64 (MFMA on CDNA).
+    MatrixFp64,
+    /// Dense matrix FP16 (MFMA on CDNA, WMMA on RDNA3+).
+    MatrixFp16,
+}
+
+/// FLOPs per CU per clock, keyed by GFX IP (an FMA counts as 2). `None` for unknown
+/// architectures and precisions the architecture has no unit for.
+#[must_use]
+pub fn flops_per_cu_per_clock(gfx: EngineId, precision: Precision) -> Option<u32> {
+    // (fp64, fp32, fp16, matrix fp64, matrix fp16); 0 = no such unit.
+    let (fp64, fp32, fp16, mfp64, mfp16) = match (gfx.major, gfx.minor, gfx.stepping) {
+        (8, _, _) => (8, 128, 128, 0, 0),
+        (9, 0, 6) => (64, 128, 256, 0, 0),        // MI50/MI60
+        (9, 0, 8) => (64, 128, 256, 0, 1024),     // MI100
+        (9, 0, 10) => (128, 128, 256, 256, 1024), // MI200
+        (9, 4, _) => (128, 256, 512, 256, 2048),  // MI300
+        (9, 0, _) => (8, 128, 256, 0, 0),         // Vega, Raven
+        (10, _, _) => (8, 128, 256, 0, 0),        // RDNA1/2
+        (11, _, _) => (8, 256, 512, 0, 512),      // RDNA3 (dual issue, WMMA)
+        (12, _, _) => (8, 256, 512, 0, 1024),     // RDNA4
+        _ => return None,
+    };
+    let ops = match precision {
+        Precision::Fp64 => fp64,
+        Precision::Fp32 => fp32,
+        Precision::Fp16 => fp16,
+        Precision::MatrixFp64 => mfp64,
+        Precision::MatrixFp16 => mfp16,
+    };
+    (ops > 0).then_some(ops)
+}
+
+/// Resource that caps the number of resident waves in [`OccupancyEstimate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OccupancyLimiter {
+    /// The hardware wave slots per SIMD (`max_waves_per_simd`).
+    WaveSlots,
+    Vgpr,
+    Sgpr,
+    Lds,
+}
+
+/// Theoretical occupancy of a kernel on one node, see [`HsaNodeProperties::occupancy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccupancyEstimate {
+    pub limiter: OccupancyLimiter,
+    pub waves_per_simd: u32,
+    /// `waves_per_simd` times the SIMDs of the whole node.
+    pub active_waves: u32,
+}
+
+/// Difference, in percent of the KFD value, above which `max_sclk_mhz` warns that KFD and
+/// `pp_dpm_sclk` disagree.
+pub const SCLK_MISMATCH_PERCENT: u32 = 5;
+
 #[derive(Debug, Clone, Default, Copy)]
 pub struct EngineId {
     pub major: u32,
@@ -1511,6 +1907,15 @@ pub struct EngineId {
     pub stepping: u32,
 }
 
+impl EngineId {
+    /// Whether the ISA target has an `xnack` feature: every GFX9 target, and gfx1010
+    /// through gfx1013 on GFX10.
+    #[must_use]
+    pub const fn has_xnack_target(&self) -> bool {
+        self.major == 9 || (self.major == 10 && self.minor == 1 && self.stepping <= 3)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct HsaMemoryProperties {
     pub heap_type: u32,
@@ -1518,12 +1923,60 @@ pub struct HsaMemoryProperties {
     pub flags: u32,
     pub width: u32,
     pub mem_clk_max: u32,
+    /// Base VA of the bank for heaps that live at a fixed address (`MMIO_REMAP`), else 0.
+    pub virtual_base_address: u64,
+}
+
+impl HsaMemoryProperties {
+    /// Decoded `heap_type`, `None` for values this crate does not know.
+    #[must_use]
+    pub const fn heap(&self) -> Option<HeapType> {
+        HeapType::from_raw(self.heap_type)
+    }
+}
+
+/// Memory bank heap type (`HSA_HEAPTYPE_*`).
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeapType {
+    System = 0,
+    FrameBufferPublic = 1,
+    FrameBufferPrivate = 2,
+    GpuGds = 3,
+    GpuLds = 4,
+    GpuScratch = 5,
+    DeviceSvm = 6,
+    MmioRemap = 7,
+}
+
+impl HeapType {
+    #[must_use]
+    pub const fn from_raw(heap_type: u32) -> Option<Self> {
+        Some(match heap_type {
+            0 => Self::System,
+            1 => Self::FrameBufferPublic,
+            2 => Self::FrameBufferPrivate,
+            3 => Self::GpuGds,
+            4 => Self::GpuLds,
+            5 => Self::GpuScratch,
+            6 => Self::DeviceSvm,
+            7 => Self::MmioRemap,
+            _ => return None,
+        })
+    }
+
+    /// Public (host-visible) or private frame buffer.
+    #[must_use]
+    pub const fn is_vram(self) -> bool {
+        matches!(self, Self::FrameBufferPublic | Self::FrameBufferPrivate)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct HsaCacheProperties {
     pub processor_id_low: u32,
     pub cache_level: u32,
+    /// Size in bytes. KFD reports KiB; the parser converts.
     pub cache_size: u32,
     pub cache_line_size: u32,
     pub cache_lines_per_tag: u32,
@@ -1533,6 +1986,38 @@ pub struct HsaCacheProperties {
     pub sibling_map: Vec<u32>,
 }
 
+impl HsaCacheProperties {
+    /// Decodes `sibling_map` into the list of processor ids that share this cache.
+    ///
+    /// KFD prints the cache's sibling bitmap one bit at a time (`sibling_map 1,1,0,0,...`),
+    /// so entry `i` is the bit for processor `processor_id_low + i`: CU ids for GPU caches,
+    /// APIC ids for CPU caches. Every non-zero entry marks a processor sharing the cache.
+    #[must_use]
+    pub fn sharing_processors(&self) -> Vec<u32> {
+        self.sibling_map
+            .iter()
+            .enumerate()
+            .filter(|&(_, &bit)| bit != 0)
+            .map(|(i, _)| self.processor_id_low + i as u32)
+            .collect()
+    }
+
+    /// Whether processor `cpu` shares this cache, see [`Self::sharing_processors`].
+    #[must_use]
+    pub fn shares_cache_with(&self, cpu: u32) -> bool {
+        cpu.checked_sub(self.processor_id_low)
+            .and_then(|i| self.sibling_map.get(i as usize))
+            .is_some_and(|&bit| bit != 0)
+    }
+}
+
+/// L2 capacity of a GPU node. Multi-XCC GPUs have one L2 per XCC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L2CacheSize {
+    pub per_xcc_bytes: u64,
+    pub total_bytes: u64,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct HsaIoLinkProperties {
     pub type_: u32,
@@ -1550,6 +2035,70 @@ pub struct HsaIoLinkProperties {
     pub flags: u32,
 }
 
+impl HsaIoLinkProperties {
+    #[must_use]
+    pub const fn link_flags(&self) -> IoLinkFlags {
+        IoLinkFlags(self.flags)
+    }
+}
+
+/// Decoded IO link `flags` (`CRAT_IOLINK_FLAGS_*`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IoLinkFlags(pub u32);
+
+impl IoLinkFlags {
+    pub const ENABLED: u32 = 1 << 0;
+    pub const NON_COHERENT: u32 = 1 << 1;
+    pub const NO_ATOMICS_32_BIT: u32 = 1 << 2;
+    pub const NO_ATOMICS_64_BIT: u32 = 1 << 3;
+    pub const NO_PEER_TO_PEER_DMA: u32 = 1 << 4;
+
+    /// Set by KFD on every link it reports. Links synthesized for multi-hop routes lack it.
+    #[must_use]
+    pub const fn enabled(self) -> bool {
+        self.0 & Self::ENABLED != 0
+    }
+
+    #[must_use]
+    pub const fn coherent(self) -> bool {
+        self.0 & Self::NON_COHERENT == 0
+    }
+
+    #[must_use]
+    pub const fn atomics_32(self) -> bool {
+        self.0 & Self::NO_ATOMICS_32_BIT == 0
+    }
+
+    #[must_use]
+    pub const fn atomics_64(self) -> bool {
+        self.0 & Self::NO_ATOMICS_64_BIT == 0
+    }
+
+    #[must_use]
+    pub const fn peer_to_peer_dma(self) -> bool {
+        self.0 & Self::NO_PEER_TO_PEER_DMA == 0
+    }
+}
+
+/// Bandwidth and latency of the connection between two nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkMetrics {
+    pub min_bandwidth_mbps: u32,
+    pub max_bandwidth_mbps: u32,
+    pub min_latency_ns: u32,
+    pub max_latency_ns: u32,
+    pub weight: u32,
+    /// `false` when the figures come from a route synthesized through CPU nodes.
+    pub direct: bool,
+}
+
+/// Operand width of a device atomic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicWidth {
+    Bits32,
+    Bits64,
+}
+
 #[derive(Debug, Clone)]
 pub struct Topology {
     pub system_props: HsaSystemProperties,
@@ -1564,21 +2113,123 @@ pub struct Node {
     pub io_links: Vec<HsaIoLinkProperties>,
 }
 
+impl Node {
+    /// Memory banks of the given heap type.
+    pub fn mem_banks_of(&self, heap: HeapType) -> impl Iterator<Item = &HsaMemoryProperties> {
+        self.mem_banks
+            .iter()
+            .filter(move |b| b.heap() == Some(heap))
+    }
+
+    /// Total size of the node's public and private frame buffer banks.
+    #[must_use]
+    pub fn total_vram_bytes(&self) -> u64 {
+        self.mem_banks_of(HeapType::FrameBufferPublic)
+            .chain(self.mem_banks_of(HeapType::FrameBufferPrivate))
+            .map(|b| b.size_in_bytes)
+            .sum()
+    }
+
+    /// Whether the node has at least one public or private frame buffer bank.
+    #[must_use]
+    pub fn has_local_vram(&self) -> bool {
+        self.mem_banks
+            .iter()
+            .any(|b| b.heap().is_some_and(HeapType::is_vram))
+    }
+
+    /// Whether the node is the GPU half of an APU, sharing system memory with the CPU.
+    ///
+    /// True when KFD reports CPU cores and SIMDs on the same node, the gfx version is a
+    /// known APU, or the GPU has no frame buffer bank. `local_mem_size` is not consulted:
+    /// current kernels report 0 there for every node.
+    #[must_use]
+    pub fn is_apu(&self) -> bool {
+        let p = &self.properties;
+        p.is_gpu()
+            && (p.cpu_cores_count > 0
+                || is_apu_gfx_version(p.gfx_target_version)
+                || !self.has_local_vram())
+    }
+
+    /// Whether the node is a discrete GPU with its own VRAM.
+    #[must_use]
+    pub fn is_dgpu(&self) -> bool {
+        self.properties.is_gpu() && !self.is_apu()
+    }
+
+    /// Whether the node is a dGPU whose whole VRAM is CPU-visible (large or resizable BAR).
+    ///
+    /// KFD reports the frame buffer as a public bank only when the BAR covers all of it,
+    /// and as a private bank otherwise.
+    #[must_use]
+    pub fn is_large_bar(&self) -> bool {
+        self.is_dgpu()
+            && self
+                .mem_banks_of(HeapType::FrameBufferPublic)
+                .any(|b| b.size_in_bytes > 0)
+    }
+}
+
 // ===============================================================================================
 // Topology Implementation
 // ===============================================================================================
 
+/// The KFD topology directory: `$KFD_SYSFS_ROOT` if set, the live sysfs tree otherwise.
+fn kfd_sysfs_root() -> PathBuf {
+    env::var_os(KFD_SYSFS_ROOT_ENV).map_or_else(|| PathBuf::from(KFD_SYSFS_PATH), PathBuf::from)
+}
+
 impl Topology {
     pub fn get_generation_id() -> io::Result<u32> {
-        let path = Path::new(KFD_SYSFS_PATH).join("generation_id");
+        let path = kfd_sysfs_root().join("generation_id");
         let content = fs::read_to_string(path)?;
         content.trim().parse::<u32>().map_err(|_| {
             io::Error::new(io::ErrorKind::InvalidData, "Failed to parse generation_id")
         })
     }
 
+    /// Reads the live KFD topology and fills in what only this host knows: CPU model names
+    /// from `/proc/cpuinfo`, `HSA_OVERRIDE_GFX_VERSION[_<node>]`, PCI marketing names and
+    /// the GPU timestamp frequency from `/dev/kfd`.
+    ///
+    /// # Errors
+    /// Fails if the KFD topology is missing or its `system_properties` cannot be read.
     pub fn get_snapshot() -> io::Result<Self> {
-        let root = Path::new(KFD_SYSFS_PATH);
+        let mut topology = Self::from_root(&kfd_sysfs_root())?;
+        let cpu_info = Self::parse_cpu_info();
+
+        for node in &mut topology.nodes {
+            let props = &mut node.properties;
+            if props.cpu_cores_count > 0 {
+                if let Some(info) = cpu_info.get(&props.cpu_core_id_base) {
+                    props.marketing_name.clone_from(info);
+                    props.amd_name.clone_from(info);
+                } else {
+                    props.marketing_name = "AMD CPU".to_string();
+                }
+            }
+
+            Self::enrich_gpu_properties_from_host(props);
+        }
+
+        if let Some(freq) = Self::gpu_system_clock_frequency(&topology.nodes) {
+            topology.system_props.timestamp_frequency = freq;
+        }
+
+        Ok(topology)
+    }
+
+    /// Parses a topology tree laid out like KFD's sysfs directory (`system_properties` and
+    /// `nodes/<n>/...`), e.g. a checked-in fixture or a capture from another machine.
+    ///
+    /// Only files under `root` are read. Host-specific details (CPU model names, GFX
+    /// version overrides, PCI marketing names, the GPU timestamp frequency) are left to
+    /// [`Topology::get_snapshot`].
+    ///
+    /// # Errors
+    /// Fails if `root` does not exist or its `system_properties` cannot be read.
+    pub fn from_root(root: &Path) -> io::Result<Self> {
         if !root.exists() {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -1587,7 +2238,6 @@ impl Topology {
         }
 
         let mut system_props = Self::parse_system_properties(&root.join("system_properties"))?;
-        let cpu_info = Self::parse_cpu_info();
 
         let mut nodes = Vec::new();
         let nodes_dir = root.join("nodes");
@@ -1606,24 +2256,32 @@ impl Topology {
             });
 
             for (idx, path) in paths.iter().enumerate() {
-                if path.is_dir()
-                    && let Ok(mut node) = Node::from_sysfs(path)
-                {
-                    node.properties.node_id = idx as u32;
-
-                    if node.properties.cpu_cores_count > 0 {
-                        if let Some(info) = cpu_info.get(&node.properties.cpu_core_id_base) {
-                            node.properties.marketing_name.clone_from(info);
-                            node.properties.amd_name.clone_from(info);
-                        } else {
-                            node.properties.marketing_name = "AMD CPU".to_string();
-                        }
+                if !path.is_dir() {
+                    continue;
+                }
+                // A node can be half-populated while it is being hot-plugged; don't let it
+                // hide the others.
+                let mut node = match Node::from_sysfs(path) {
+                    Ok(node) => node,
+                    Err(e) => {
+                        eprintln!("Warning: skipping topology node {}: {e}", path.display());
+                        continue;
                     }
+                };
 
-                    Self::enrich_gpu_properties(&mut node.properties);
+                node.properties.node_id = idx as u32;
 
-                    nodes.push(node);
+                if node.properties.simd_count > 0 {
+                    let version = node.properties.gfx_target_version;
+                    Self::derive_gpu_properties(
+                        &mut node.properties,
+                        (version / 10000) % 100,
+                        (version / 100) % 100,
+                        version % 100,
+                    );
                 }
+
+                nodes.push(node);
             }
         }
 
@@ -1654,15 +2312,30 @@ impl Topology {
         })
     }
 
-    fn enrich_gpu_properties(props: &mut HsaNodeProperties) {
+    /// Reads the GPU system clock frequency (the domain of `system_clock_counter`) from the
+    /// first GPU node, or `None` on CPU-only systems or if the ioctl fails.
+    fn gpu_system_clock_frequency(nodes: &[Node]) -> Option<u64> {
+        let gpu_id = nodes
+            .iter()
+            .map(|n| n.properties.kfd_gpu_id)
+            .find(|&id| id != 0)?;
+
+        let kfd = KfdDevice::open().ok()?;
+        let mut args = GetClockCountersArgs {
+            gpu_id,
+            ..Default::default()
+        };
+        kfd.get_clock_counters(&mut args).ok()?;
+
+        (args.system_clock_freq > 0).then_some(args.system_clock_freq)
+    }
+
+    /// Applies `HSA_OVERRIDE_GFX_VERSION[_<node>]` and the PCI marketing name to a GPU node.
+    fn enrich_gpu_properties_from_host(props: &mut HsaNodeProperties) {
         if props.simd_count == 0 {
             return;
         }
 
-        let mut major = (props.gfx_target_version / 10000) % 100;
-        let mut minor = (props.gfx_target_version / 100) % 100;
-        let mut step = props.gfx_target_version % 100;
-
         let override_var_node = format!("HSA_OVERRIDE_GFX_VERSION_{}", props.node_id);
         let override_val =
             env::var(&override_var_node).or_else(|_| env::var("HSA_OVERRIDE_GFX_VERSION"));
@@ -1676,12 +2349,24 @@ impl Topology {
                     parts[2].parse::<u32>(),
                 )
             {
-                major = maj;
-                minor = min;
-                step = stp;
+                let derived_name = props.amd_name.clone();
+                Self::derive_gpu_properties(props, maj, min, stp);
+                if props.marketing_name == derived_name {
+                    props.marketing_name = props.amd_name.clone();
+                }
             }
         }
 
+        if let Some(rev_id) = get_pci_revision_id(props.domain, props.location_id)
+            && let Some(name) = lookup_marketing_name_from_file(props.device_id, rev_id)
+        {
+            props.marketing_name = name;
+        }
+    }
+
+    /// Fills in the GPU properties that follow from the GFX version and the node's own
+    /// counts: engine id, IP name, shader banks and register file sizes.
+    fn derive_gpu_properties(props: &mut HsaNodeProperties, major: u32, minor: u32, step: u32) {
         props.engine_id = EngineId {
             major,
             minor,
@@ -1698,16 +2383,7 @@ impl Topology {
             props.amd_name = format!("GFX{:02x}", props.gfx_target_version);
         }
 
-        let marketing_name =
-            if let Some(rev_id) = get_pci_revision_id(props.domain, props.location_id) {
-                lookup_marketing_name_from_file(props.device_id, rev_id)
-            } else {
-                None
-            };
-
-        if let Some(name) = marketing_name {
-            props.marketing_name = name;
-        } else if props.marketing_name.is_empty() {
+        if props.marketing_name.is_empty() {
             props.marketing_name = props.amd_name.clone();
         }
 
@@ -1767,9 +2443,8 @@ impl Topology {
         let cpu_src = get_direct_cpu(src, src_idx)?;
         let cpu_dst = get_direct_cpu(dst, dst_idx)?;
 
-        let mut weight1 = 0;
-        let mut weight2 = 0;
-        let mut weight3 = 0;
+        // Links making up the route, used to derive its weight, bandwidth and latency.
+        let mut hops: Vec<&HsaIoLinkProperties> = Vec::with_capacity(3);
         let mut link_type = HSA_IOLINKTYPE_UNDEFINED;
 
         if cpu_src == cpu_dst {
@@ -1778,7 +2453,7 @@ impl Topology {
                     .io_links
                     .iter()
                     .find(|l| l.node_to as usize == cpu_src)?;
-                weight1 = l.weight;
+                hops.push(l);
             }
 
             if dst_is_gpu {
@@ -1786,7 +2461,7 @@ impl Topology {
                     .io_links
                     .iter()
                     .find(|l| l.node_to as usize == dst_idx)?;
-                weight2 = l.weight;
+                hops.push(l);
                 link_type = if src_is_gpu {
                     HSA_IOLINKTYPE_PCIEXPRESS
                 } else {
@@ -1799,16 +2474,16 @@ impl Topology {
                     .io_links
                     .iter()
                     .find(|l| l.node_to as usize == cpu_src)?;
-                weight1 = l.weight;
+                hops.push(l);
             }
 
             let l_cpu = nodes[cpu_src]
                 .io_links
                 .iter()
                 .find(|l| l.node_to as usize == cpu_dst)?;
-            weight2 = l_cpu.weight;
+            hops.push(l_cpu);
 
-            if l_cpu.type_ == HSA_IOLINKTYPE_QPI_1_1 && weight2 > 20 {
+            if l_cpu.type_ == HSA_IOLINKTYPE_QPI_1_1 && l_cpu.weight > 20 {
                 return None;
             }
 
@@ -1817,15 +2492,21 @@ impl Topology {
                     .io_links
                     .iter()
                     .find(|l| l.node_to as usize == dst_idx)?;
-                weight3 = l.weight;
+                hops.push(l);
             }
         }
 
-        let total_weight = weight1 + weight2 + weight3;
+        let total_weight: u32 = hops.iter().map(|l| l.weight).sum();
         if total_weight == 0 {
             return None;
         }
 
+        // A route is as fast as its slowest hop and as slow as all of its hops together.
+        let min_bandwidth = hops.iter().map(|l| l.min_bandwidth).min().unwrap_or(0);
+        let max_bandwidth = hops.iter().map(|l| l.max_bandwidth).min().unwrap_or(0);
+        let min_latency = hops.iter().map(|l| l.min_latency).sum();
+        let max_latency = hops.iter().map(|l| l.max_latency).sum();
+
         Some(HsaIoLinkProperties {
             type_: link_type,
             version_major: 0,
@@ -1833,10 +2514,10 @@ impl Topology {
             node_from: src_idx as u32,
             node_to: dst_idx as u32,
             weight: total_weight,
-            min_latency: 0,
-            max_latency: 0,
-            min_bandwidth: 0,
-            max_bandwidth: 0,
+            min_latency,
+            max_latency,
+            min_bandwidth,
+            max_bandwidth,
             rec_transfer_size: 0,
             rec_sdma_eng_id_mask: 0,
             flags: 0,
@@ -1889,10 +2570,299 @@ impl Topology {
             }
         }
 
+        // Last resort for CPU-only systems, `get_snapshot` replaces it with the GPU clock.
         p.timestamp_frequency = get_system_clock_frequency();
 
         Ok(p)
     }
+
+    /// Returns the NUMA node the GPU at `node_id` is attached to.
+    ///
+    /// Read from the PCI device's `numa_node` file. `None` for CPU-only nodes, unknown
+    /// node ids, and platforms that don't report locality (`-1`).
+    #[must_use]
+    pub fn numa_node_for_gpu(&self, node_id: u32) -> Option<u32> {
+        let props = &self.nodes.get(node_id as usize)?.properties;
+        if props.kfd_gpu_id == 0 {
+            return None;
+        }
+        get_pci_numa_node(props.domain, props.location_id)
+    }
+
+    /// Pins the calling thread to the CPUs of the NUMA node nearest the GPU at `node_id`.
+    ///
+    /// Does nothing (with a warning) when the GPU's NUMA node is unknown.
+    pub fn set_thread_affinity_near_gpu(&self, node_id: u32) -> io::Result<()> {
+        if node_id as usize >= self.nodes.len() {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        let Some(numa_node) = self.numa_node_for_gpu(node_id) else {
+            eprintln!("Warning: NUMA node of GPU node {node_id} is unknown, affinity unchanged");
+            return Ok(());
+        };
+
+        let cpulist =
+            fs::read_to_string(format!("/sys/devices/system/node/node{numa_node}/cpulist"))?;
+        let cpus = parse_cpu_list(&cpulist);
+        if cpus.is_empty() {
+            eprintln!("Warning: NUMA node {numa_node} has no CPUs, affinity unchanged");
+            return Ok(());
+        }
+
+        // A fixed-size `cpu_set_t` only holds CPUs below `CPU_SETSIZE`.
+        let (cpus, too_high): (Vec<usize>, Vec<usize>) = cpus
+            .into_iter()
+            .partition(|&cpu| cpu < libc::CPU_SETSIZE as usize);
+        if !too_high.is_empty() {
+            eprintln!(
+                "Warning: ignoring CPUs {too_high:?} of NUMA node {numa_node} (>= CPU_SETSIZE)"
+            );
+        }
+        if cpus.is_empty() {
+            return Ok(());
+        }
+
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for cpu in cpus {
+                libc::CPU_SET(cpu, &mut set);
+            }
+            if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &raw const set)
+                != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether atomics of `width` issued by `from_node` work on memory of `to_node`.
+    ///
+    /// Only direct links reported by KFD are considered: routes synthesized through
+    /// CPU nodes carry no flags, so atomics across them are reported as unsupported.
+    #[must_use]
+    pub fn supports_device_atomics(
+        &self,
+        from_node: u32,
+        to_node: u32,
+        width: AtomicWidth,
+    ) -> bool {
+        let Some(node) = self.nodes.get(from_node as usize) else {
+            return false;
+        };
+        node.io_links
+            .iter()
+            .filter(|l| l.node_to == to_node)
+            .map(HsaIoLinkProperties::link_flags)
+            .any(|f| {
+                f.enabled()
+                    && match width {
+                        AtomicWidth::Bits32 => f.atomics_32(),
+                        AtomicWidth::Bits64 => f.atomics_64(),
+                    }
+            })
+    }
+
+    /// Returns the bandwidth and latency between two nodes.
+    ///
+    /// Prefers the direct KFD link with the highest bandwidth. Without one, falls back to
+    /// the route synthesized through the CPU nodes, whose figures are the minimum
+    /// bandwidth and summed latency of its hops. `None` if the nodes are not connected.
+    #[must_use]
+    pub fn link_metrics(&self, from_node: u32, to_node: u32) -> Option<LinkMetrics> {
+        let node = self.nodes.get(from_node as usize)?;
+        let links = || node.io_links.iter().filter(|l| l.node_to == to_node);
+
+        let (link, direct) = match links()
+            .filter(|l| l.link_flags().enabled())
+            .max_by_key(|l| (l.max_bandwidth, std::cmp::Reverse(l.weight)))
+        {
+            Some(l) => (l, true),
+            None => (links().min_by_key(|l| l.weight)?, false),
+        };
+
+        Some(LinkMetrics {
+            min_bandwidth_mbps: link.min_bandwidth,
+            max_bandwidth_mbps: link.max_bandwidth,
+            min_latency_ns: link.min_latency,
+            max_latency_ns: link.max_latency,
+            weight: link.weight,
+            direct,
+        })
+    }
+
+    /// Whether the GPU at `node_id` can run with XNACK (page fault retry) enabled.
+    ///
+    /// KFD has no dedicated capability bit; XNACK needs the SVM API (retry faults are
+    /// routed through it) and an ISA target with an `xnack` feature, see
+    /// [`EngineId::has_xnack_target`]. Whether it is currently on is
+    /// [`KfdDevice::get_xnack_mode`].
+    ///
+    /// [`KfdDevice::get_xnack_mode`]: crate::kfd::device::KfdDevice::get_xnack_mode
+    #[must_use]
+    pub fn xnack_supported(&self, node_id: u32) -> bool {
+        self.nodes.get(node_id as usize).is_some_and(|n| {
+            n.properties.kfd_gpu_id != 0
+                && n.properties.capability & HSA_CAP_SVMAPI_SUPPORTED != 0
+                && n.properties.engine_id.has_xnack_target()
+        })
+    }
+
+    /// SDMA queue budget of `node_id`, see [`HsaNodeProperties::sdma_queue_budget`].
+    /// Returns 0 for unknown node ids.
+    #[must_use]
+    pub fn sdma_queue_budget(&self, node_id: u32) -> u32 {
+        self.nodes
+            .get(node_id as usize)
+            .map_or(0, |n| n.properties.sdma_queue_budget())
+    }
+
+    /// Occupancy of a kernel on `node_id`, see [`HsaNodeProperties::occupancy`]. `None`
+    /// for CPU nodes and unknown node ids.
+    #[must_use]
+    pub fn occupancy_estimate(
+        &self,
+        node_id: u32,
+        vgprs_per_thread: u32,
+        sgprs_per_thread: u32,
+        lds_bytes: u32,
+        wavefront_size: u32,
+    ) -> Option<OccupancyEstimate> {
+        self.nodes.get(node_id as usize)?.properties.occupancy(
+            vgprs_per_thread,
+            sgprs_per_thread,
+            lds_bytes,
+            wavefront_size,
+        )
+    }
+
+    /// Returns the L2 cache size of the GPU at `node_id`.
+    ///
+    /// Uses the largest level-2 cache reported for the node. `None` for CPU nodes, unknown
+    /// node ids, and GPUs that report no L2.
+    #[must_use]
+    pub fn l2_cache_bytes(&self, node_id: u32) -> Option<L2CacheSize> {
+        let node = self.nodes.get(node_id as usize)?;
+        if node.properties.kfd_gpu_id == 0 {
+            return None;
+        }
+        let per_xcc_bytes = node
+            .caches
+            .iter()
+            .filter(|c| c.cache_level == 2)
+            .map(|c| u64::from(c.cache_size))
+            .max()?;
+        Some(L2CacheSize {
+            per_xcc_bytes,
+            total_bytes: per_xcc_bytes * u64::from(node.properties.num_xcc.max(1)),
+        })
+    }
+}
+
+/// Reads the NUMA node of a PCI device, `None` if the platform reports `-1`.
+fn get_pci_numa_node(domain: u32, location_id: u32) -> Option<u32> {
+    let bus = (location_id >> 8) & 0xFF;
+    let dev = (location_id >> 3) & 0x1F;
+    let func = location_id & 0x07;
+
+    let pci_path =
+        format!("/sys/bus/pci/devices/{domain:04x}:{bus:02x}:{dev:02x}.{func:01x}/numa_node");
+
+    let node = fs::read_to_string(pci_path)
+        .ok()?
+        .trim()
+        .parse::<i32>()
+        .ok()?;
+    u32::try_from(node).ok()
+}
+
+/// Reads the highest shader clock level (MHz) from the PCI device's `pp_dpm_sclk`.
+///
+/// Each line looks like `1: 2500Mhz *`; the file is absent when power-play is disabled.
+fn get_pci_max_sclk_mhz(domain: u32, location_id: u32) -> Option<u32> {
+    let path = pci_device_path(domain, location_id).join("pp_dpm_sclk");
+    parse_max_dpm_sclk(&fs::read_to_string(path).ok()?)
+}
+
+/// The highest level of a `pp_dpm_sclk` listing, `None` if no line parses.
+fn parse_max_dpm_sclk(content: &str) -> Option<u32> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (_, level) = line.split_once(':')?;
+            let mhz = level.split_whitespace().next()?;
+            let mhz = mhz
+                .strip_suffix("Mhz")
+                .or_else(|| mhz.strip_suffix("MHz"))?;
+            mhz.parse::<u32>().ok()
+        })
+        .max()
+}
+
+/// Picks the peak shader clock from KFD's value and the `pp_dpm_sclk` maximum.
+///
+/// Returns the clock and, when both are known and differ by more than
+/// [`SCLK_MISMATCH_PERCENT`], the disagreeing `pp_dpm_sclk` value to warn about.
+fn reconcile_sclk_mhz(kfd: u32, dpm: Option<u32>) -> (u32, Option<u32>) {
+    if kfd == 0 {
+        return (dpm.unwrap_or(0), None);
+    }
+    let disagreeing = dpm.filter(|&dpm| {
+        u64::from(kfd.abs_diff(dpm)) * 100 > u64::from(kfd) * u64::from(SCLK_MISMATCH_PERCENT)
+    });
+    (kfd, disagreeing)
+}
+
+/// Sysfs directory of the PCI device with the given KFD domain and location ID (BDF).
+fn pci_device_path(domain: u32, location_id: u32) -> PathBuf {
+    let bus = (location_id >> 8) & 0xFF;
+    let dev = (location_id >> 3) & 0x1F;
+    let func = location_id & 0x07;
+
+    PathBuf::from(format!(
+        "/sys/bus/pci/devices/{domain:04x}:{bus:02x}:{dev:02x}.{func:01x}"
+    ))
+}
+
+/// Reads the `<component>_fw_version` files of an amdgpu `fw_version/` directory.
+///
+/// Files without the suffix or with unparseable content are skipped.
+fn read_fw_versions(dir: &Path) -> io::Result<HashMap<String, u32>> {
+    let mut versions = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(component) = name.to_str().and_then(|n| n.strip_suffix("_fw_version")) else {
+            continue;
+        };
+        if let Ok(content) = fs::read_to_string(entry.path())
+            && let Some(version) = parse_fw_version(&content)
+        {
+            versions.insert(component.to_string(), version);
+        }
+    }
+    Ok(versions)
+}
+
+/// Parses one amdgpu `fw_version/*` file, which holds a hex value such as `0x00000082`.
+fn parse_fw_version(content: &str) -> Option<u32> {
+    let content = content.trim();
+    let hex = content
+        .strip_prefix("0x")
+        .or_else(|| content.strip_prefix("0X"))?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Parses a sysfs CPU list such as `0-7,16-23`.
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+            cpus.extend(start..=end);
+        }
+    }
+    cpus
 }
 
 fn get_system_clock_frequency() -> u64 {
@@ -2028,9 +2998,9 @@ impl Node {
             let mut parts = line.split_whitespace();
             let key = parts.next();
             if key == Some("sibling_map") {
-                for num_str in parts {
-                    let clean = num_str.trim_matches(',');
-                    if let Ok(val) = clean.parse::<u32>() {
+                // One entry per bit, comma separated: "1,1,0,0,..."
+                for num_str in parts.flat_map(|s| s.split(',')) {
+                    if let Ok(val) = num_str.parse::<u32>() {
                         p.sibling_map.push(val);
                     }
                 }
@@ -2118,3 +3088,100 @@ impl Node {
         results
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fw_version_is_hex() {
+        assert_eq!(parse_fw_version("0x00000082\n"), Some(0x82));
+        assert_eq!(parse_fw_version("0X1F"), Some(0x1f));
+        assert_eq!(parse_fw_version("130"), None);
+        assert_eq!(parse_fw_version("N/A"), None);
+        assert_eq!(parse_fw_version(""), None);
+    }
+
+    #[test]
+    fn fw_version_dir_keeps_parseable_components() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fw_version");
+        let versions: BTreeMap<_, _> = read_fw_versions(&dir).unwrap().into_iter().collect();
+        assert_eq!(
+            versions,
+            BTreeMap::from([
+                ("mec".to_string(), 0x1d6),
+                ("sdma".to_string(), 0xa8),
+                ("smc".to_string(), 0x0028_3900),
+            ])
+        );
+    }
+
+    #[test]
+    fn dpm_sclk_takes_the_highest_level() {
+        let levels = "0: 500Mhz\n1: 1800Mhz *\n2: 2500Mhz\n";
+        assert_eq!(parse_max_dpm_sclk(levels), Some(2500));
+        assert_eq!(parse_max_dpm_sclk("0: 300MHz\n1: 1200MHz *\n"), Some(1200));
+        assert_eq!(parse_max_dpm_sclk(""), None);
+    }
+
+    #[test]
+    fn sclk_falls_back_to_dpm_when_kfd_reports_zero() {
+        assert_eq!(reconcile_sclk_mhz(0, Some(2500)), (2500, None));
+        assert_eq!(reconcile_sclk_mhz(0, None), (0, None));
+    }
+
+    #[test]
+    fn sclk_prefers_kfd_and_flags_disagreement() {
+        assert_eq!(reconcile_sclk_mhz(2000, None), (2000, None));
+        // 5% is tolerated, anything beyond is reported.
+        assert_eq!(reconcile_sclk_mhz(2000, Some(2100)), (2000, None));
+        assert_eq!(reconcile_sclk_mhz(2000, Some(2101)), (2000, Some(2101)));
+        assert_eq!(reconcile_sclk_mhz(2000, Some(1500)), (2000, Some(1500)));
+    }
+
+    #[test]
+    fn cpu_list_expands_ranges() {
+        assert_eq!(parse_cpu_list("0-3"), [0, 1, 2, 3]);
+        assert_eq!(parse_cpu_list("5-5\n"), [5]);
+    }
+
+    #[test]
+    fn cpu_list_joins_ranges_and_single_cpus() {
+        assert_eq!(parse_cpu_list("0-1,4,6-7\n"), [0, 1, 4, 6, 7]);
+    }
+
+    #[test]
+    fn empty_cpu_list_has_no_cpus() {
+        assert!(parse_cpu_list("").is_empty());
+        assert!(parse_cpu_list("\n").is_empty());
+    }
+
+    /// Needs an AMD GPU whose PCI device reports a NUMA node.
+    #[cfg(feature = "numa-tests")]
+    #[test]
+    fn affinity_matches_the_gpu_numa_node() {
+        let topo = Topology::get_snapshot().expect("KFD topology");
+        let (node_id, numa_node) = (0..topo.nodes.len() as u32)
+            .find_map(|n| Some((n, topo.numa_node_for_gpu(n)?)))
+            .expect("a GPU with a known NUMA node");
+        let cpulist =
+            fs::read_to_string(format!("/sys/devices/system/node/node{numa_node}/cpulist"))
+                .unwrap();
+        let expected: Vec<usize> = parse_cpu_list(&cpulist)
+            .into_iter()
+            .filter(|&cpu| cpu < libc::CPU_SETSIZE as usize)
+            .collect();
+
+        topo.set_thread_affinity_near_gpu(node_id).unwrap();
+
+        let actual: Vec<usize> = unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            let len = std::mem::size_of::<libc::cpu_set_t>();
+            assert_eq!(libc::sched_getaffinity(0, len, &raw mut set), 0);
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect()
+        };
+        assert_eq!(actual, expected);
+    }
+}
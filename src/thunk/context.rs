@@ -1,5 +1,8 @@
+use crate::error::{HsaError, HsaResult};
 use crate::kfd::device::KfdDevice;
 use crate::kfd::sysfs::EngineId;
+use crate::thunk::memory::MemoryManager;
+use crate::thunk::queues::builder::{HsaQueue, Placement, QueueBuilder, QueueType};
 use crate::thunk::topology::{
     HsaCacheProperties, HsaIoLinkProperties, HsaMemoryProperties, HsaNodeProperties,
     HsaSystemProperties, acquire_system_properties, get_node_cache_properties,
@@ -7,8 +10,12 @@ use crate::thunk::topology::{
     release_system_properties,
 };
 use std::io;
+use std::os::fd::RawFd;
 use std::sync::{Arc, Mutex};
 
+/// Ring size used for queues created through `Context::create_sdma_queue`.
+const SDMA_RING_SIZE: u64 = 64 * 1024;
+
 // ===============================================================================================
 // Context Structures
 // ===============================================================================================
@@ -46,6 +53,38 @@ pub struct Context {
     pub supports_event_age: bool,
 }
 
+impl Context {
+    /// Creates an SDMA queue on `node_id` with a ring allocated and owned by the queue.
+    ///
+    /// The ring lives in GTT, as SDMA engines fetch from system memory without going
+    /// through the BAR. Submit copies with [`HsaQueue::sdma_copy`].
+    pub fn create_sdma_queue(
+        &self,
+        mem_mgr: &mut MemoryManager,
+        drm_fd: RawFd,
+        node_id: u32,
+    ) -> HsaResult<HsaQueue> {
+        let node = self
+            .nodes
+            .get(node_id as usize)
+            .filter(|n| n.properties.kfd_gpu_id != 0)
+            .ok_or(HsaError::InvalidNodeId(node_id))?;
+
+        QueueBuilder::new(
+            &self.device,
+            mem_mgr,
+            &node.properties,
+            node_id,
+            drm_fd,
+            0,
+            SDMA_RING_SIZE,
+        )
+        .with_type(QueueType::Sdma)
+        .with_ring_placement(Placement::Gtt)
+        .create()
+    }
+}
+
 // ===============================================================================================
 // Global Singleton Management
 // ===============================================================================================
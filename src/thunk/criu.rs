@@ -0,0 +1,248 @@
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::{
+    CriuArgs, CriuBoBucket, CriuDeviceBucket, KFD_CRIU_OP_CHECKPOINT, KFD_CRIU_OP_PROCESS_INFO,
+    KFD_CRIU_OP_RESTORE, KFD_CRIU_OP_RESUME, KFD_CRIU_OP_UNPAUSE,
+};
+use std::io;
+
+const IMAGE_MAGIC: &[u8; 8] = b"KFDCRIU1";
+
+/// The KFD state of a process captured by [`CriuSession::checkpoint`].
+///
+/// `priv_data` is an opaque blob only KFD interprets; it must be handed back unchanged on
+/// restore. Buffer contents are not part of the image, CRIU copies them separately.
+#[derive(Debug, Clone, Default)]
+pub struct CriuImage {
+    pub devices: Vec<CriuDeviceBucket>,
+    pub bos: Vec<CriuBoBucket>,
+    /// Number of queues, events and SVM ranges described by `priv_data`.
+    pub num_objects: u32,
+    pub priv_data: Vec<u8>,
+}
+
+/// Drives the `AMDKFD_IOC_CRIU_OP` sequence for checkpointing and restoring a process.
+///
+/// Checkpoint runs `PROCESS_INFO` (which pauses the target's queues), `CHECKPOINT` and
+/// `UNPAUSE`. Restore runs `RESTORE` in the restoring process, followed by `RESUME` once
+/// the buffer contents are back in place.
+#[derive(Debug, Clone)]
+pub struct CriuSession {
+    device: KfdDevice,
+}
+
+impl CriuSession {
+    #[must_use]
+    pub fn new(device: &KfdDevice) -> Self {
+        Self {
+            device: device.clone(),
+        }
+    }
+
+    /// Captures the KFD state of `pid`.
+    ///
+    /// The target's queues are unpaused again before returning, also when the checkpoint
+    /// itself fails.
+    ///
+    /// # Errors
+    /// Fails if any of the ioctls fail, e.g. without ptrace rights on `pid`.
+    pub fn checkpoint(&self, pid: u32) -> io::Result<CriuImage> {
+        let mut info = CriuArgs {
+            pid,
+            op: KFD_CRIU_OP_PROCESS_INFO,
+            ..Default::default()
+        };
+        self.device.criu_op(&mut info)?;
+
+        let image = self.checkpoint_paused(&info);
+
+        let mut unpause = CriuArgs {
+            pid,
+            op: KFD_CRIU_OP_UNPAUSE,
+            ..Default::default()
+        };
+        let unpaused = self.device.criu_op(&mut unpause);
+
+        let image = image?;
+        unpaused?;
+        Ok(image)
+    }
+
+    fn checkpoint_paused(&self, info: &CriuArgs) -> io::Result<CriuImage> {
+        let priv_data_size = usize::try_from(info.priv_data_size)
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        let mut image = CriuImage {
+            devices: vec![CriuDeviceBucket::default(); info.num_devices as usize],
+            bos: vec![CriuBoBucket::default(); info.num_bos as usize],
+            num_objects: info.num_objects,
+            priv_data: vec![0; priv_data_size],
+        };
+
+        let mut args = CriuArgs {
+            devices: image.devices.as_mut_ptr() as u64,
+            bos: image.bos.as_mut_ptr() as u64,
+            priv_data: image.priv_data.as_mut_ptr() as u64,
+            priv_data_size: info.priv_data_size,
+            num_devices: info.num_devices,
+            num_bos: info.num_bos,
+            num_objects: info.num_objects,
+            pid: info.pid,
+            op: KFD_CRIU_OP_CHECKPOINT,
+        };
+        self.device.criu_op(&mut args)?;
+        Ok(image)
+    }
+
+    /// Recreates the checkpointed state in the calling process.
+    ///
+    /// The device buckets must already carry the `actual_gpu_id` and `drm_fd` of the GPUs
+    /// on this machine. Returns the BO buckets as KFD rewrote them, with the
+    /// `restored_offset` to mmap each buffer at and its `dmabuf_fd`.
+    ///
+    /// # Errors
+    /// Fails if the image is inconsistent or KFD rejects it.
+    pub fn restore(&self, image: &CriuImage) -> io::Result<Vec<CriuBoBucket>> {
+        let mut devices = image.devices.clone();
+        let mut bos = image.bos.clone();
+        let mut priv_data = image.priv_data.clone();
+
+        let mut args = CriuArgs {
+            devices: devices.as_mut_ptr() as u64,
+            bos: bos.as_mut_ptr() as u64,
+            priv_data: priv_data.as_mut_ptr() as u64,
+            priv_data_size: priv_data.len() as u64,
+            num_devices: u32::try_from(devices.len())
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+            num_bos: u32::try_from(bos.len())
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+            num_objects: image.num_objects,
+            pid: 0,
+            op: KFD_CRIU_OP_RESTORE,
+        };
+        self.device.criu_op(&mut args)?;
+        Ok(bos)
+    }
+
+    /// Restarts the queues of the restored process `pid`.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn resume(&self, pid: u32) -> io::Result<()> {
+        let mut args = CriuArgs {
+            pid,
+            op: KFD_CRIU_OP_RESUME,
+            ..Default::default()
+        };
+        self.device.criu_op(&mut args)
+    }
+}
+
+impl CriuImage {
+    /// Serializes the image: a magic, the counts, then every bucket and the private data,
+    /// all little-endian.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(28 + self.devices.len() * 16 + self.bos.len() * 48);
+        out.extend_from_slice(IMAGE_MAGIC);
+        out.extend_from_slice(&self.num_objects.to_le_bytes());
+        out.extend_from_slice(&(self.devices.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.bos.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.priv_data.len() as u64).to_le_bytes());
+
+        for d in &self.devices {
+            for v in [d.user_gpu_id, d.actual_gpu_id, d.drm_fd, d.pad] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        for b in &self.bos {
+            for v in [b.addr, b.size, b.offset, b.restored_offset] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in [b.gpu_id, b.alloc_flags, b.dmabuf_fd, b.pad] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&self.priv_data);
+        out
+    }
+
+    /// Parses an image written by [`Self::to_bytes`].
+    ///
+    /// # Errors
+    /// Fails with `InvalidData` on a bad magic or a truncated image.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut r = Reader(bytes);
+        if r.take(IMAGE_MAGIC.len())? != IMAGE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a KFD CRIU image",
+            ));
+        }
+        let num_objects = r.u32()?;
+        let num_devices = r.u32()?;
+        let num_bos = r.u32()?;
+        let priv_data_size =
+            usize::try_from(r.u64()?).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+
+        let devices = (0..num_devices)
+            .map(|_| {
+                Ok(CriuDeviceBucket {
+                    user_gpu_id: r.u32()?,
+                    actual_gpu_id: r.u32()?,
+                    drm_fd: r.u32()?,
+                    pad: r.u32()?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let bos = (0..num_bos)
+            .map(|_| {
+                Ok(CriuBoBucket {
+                    addr: r.u64()?,
+                    size: r.u64()?,
+                    offset: r.u64()?,
+                    restored_offset: r.u64()?,
+                    gpu_id: r.u32()?,
+                    alloc_flags: r.u32()?,
+                    dmabuf_fd: r.u32()?,
+                    pad: r.u32()?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let priv_data = r.take(priv_data_size)?.to_vec();
+
+        Ok(Self {
+            devices,
+            bos,
+            num_objects,
+            priv_data,
+        })
+    }
+}
+
+/// Little-endian cursor over a serialized image.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated KFD CRIU image",
+            ));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+}
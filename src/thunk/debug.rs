@@ -0,0 +1,203 @@
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::{
+    DbgTrapArgs, DbgTrapArgsUnion, DbgTrapEnableArgs, DbgTrapResumeQueuesArgs,
+    DbgTrapSuspendQueuesArgs, KFD_IOC_DBG_TRAP_DISABLE, KFD_IOC_DBG_TRAP_ENABLE,
+    KFD_IOC_DBG_TRAP_RESUME_QUEUES, KFD_IOC_DBG_TRAP_SUSPEND_QUEUES, RuntimeInfo,
+};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// A debugger attachment to a process through `AMDKFD_IOC_DBG_TRAP`.
+///
+/// `attach` enables the debug trap for `pid`; dropping the session disables it again, so
+/// an early return cannot leave the target with debugging enabled.
+#[derive(Debug)]
+pub struct DebugSession {
+    device: KfdDevice,
+    pid: u32,
+    runtime_info: RuntimeInfo,
+    /// KFD writes to this eventfd whenever a debug event is pending on the target.
+    event_fd: OwnedFd,
+}
+
+impl DebugSession {
+    /// Enables the debug trap on `pid`, reporting the exceptions in `exception_mask`.
+    ///
+    /// # Errors
+    /// Fails if the eventfd cannot be created or KFD refuses to enable debugging, e.g.
+    /// because another debugger is attached or the caller lacks ptrace rights on `pid`.
+    pub fn attach(device: &KfdDevice, pid: u32, exception_mask: u64) -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let event_fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut runtime_info = RuntimeInfo::default();
+        #[allow(clippy::cast_sign_loss)]
+        let enable = DbgTrapEnableArgs {
+            exception_mask,
+            rinfo_ptr: (&raw mut runtime_info) as u64,
+            rinfo_size: mem::size_of::<RuntimeInfo>() as u32,
+            dbg_fd: event_fd.as_raw_fd() as u32,
+        };
+        dbg_trap(
+            device,
+            pid,
+            KFD_IOC_DBG_TRAP_ENABLE,
+            DbgTrapArgsUnion { enable },
+        )?;
+
+        Ok(Self {
+            device: device.clone(),
+            pid,
+            runtime_info,
+            event_fd,
+        })
+    }
+
+    #[must_use]
+    pub const fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Runtime state KFD reported for the target when the session was attached.
+    #[must_use]
+    pub const fn runtime_info(&self) -> &RuntimeInfo {
+        &self.runtime_info
+    }
+
+    /// Eventfd that becomes readable when a debug event is pending.
+    #[must_use]
+    pub fn event_fd(&self) -> RawFd {
+        self.event_fd.as_raw_fd()
+    }
+
+    /// Suspends the given queues, waiting up to `grace_period` (in 100 µs units) for waves
+    /// to reach a trap handler boundary.
+    ///
+    /// Returns the queue ids as KFD rewrote them: entries that could not be suspended carry
+    /// `KFD_DBG_QUEUE_ERROR_MASK` or `KFD_DBG_QUEUE_INVALID_MASK`.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn suspend_queues(&self, queue_ids: &[u32], grace_period: u32) -> io::Result<Vec<u32>> {
+        let mut queues = queue_ids.to_vec();
+        #[allow(clippy::cast_possible_truncation)]
+        let suspend_queues = DbgTrapSuspendQueuesArgs {
+            // Pending exceptions are left for the debugger to query.
+            exception_mask: 0,
+            queue_array_ptr: queues.as_mut_ptr() as u64,
+            num_queues: queues.len() as u32,
+            grace_period,
+        };
+        dbg_trap(
+            &self.device,
+            self.pid,
+            KFD_IOC_DBG_TRAP_SUSPEND_QUEUES,
+            DbgTrapArgsUnion { suspend_queues },
+        )?;
+        Ok(queues)
+    }
+
+    /// Resumes the given queues. Returns the queue ids as KFD rewrote them, see
+    /// [`suspend_queues`](Self::suspend_queues).
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn resume_queues(&self, queue_ids: &[u32]) -> io::Result<Vec<u32>> {
+        let mut queues = queue_ids.to_vec();
+        #[allow(clippy::cast_possible_truncation)]
+        let resume_queues = DbgTrapResumeQueuesArgs {
+            queue_array_ptr: queues.as_mut_ptr() as u64,
+            num_queues: queues.len() as u32,
+            pad: 0,
+        };
+        dbg_trap(
+            &self.device,
+            self.pid,
+            KFD_IOC_DBG_TRAP_RESUME_QUEUES,
+            DbgTrapArgsUnion { resume_queues },
+        )?;
+        Ok(queues)
+    }
+}
+
+impl Drop for DebugSession {
+    fn drop(&mut self) {
+        let data = unsafe { mem::zeroed::<DbgTrapArgsUnion>() };
+        if let Err(e) = dbg_trap(&self.device, self.pid, KFD_IOC_DBG_TRAP_DISABLE, data) {
+            eprintln!("AMDKFD_IOC_DBG_TRAP disable failed: {e:?}");
+        }
+    }
+}
+
+fn dbg_trap(device: &KfdDevice, pid: u32, op: u32, data: DbgTrapArgsUnion) -> io::Result<()> {
+    let mut args = DbgTrapArgs { pid, op, data };
+    device.dbg_trap(&mut args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kfd::ioctl::{DEBUG_RUNTIME_STATE_ENABLED, KFD_DBG_QUEUE_INVALID_MASK};
+    use crate::kfd::mock::Mock;
+
+    #[test]
+    fn attach_passes_the_runtime_info_buffer_and_eventfd() {
+        let mock = Mock::new();
+        let session = DebugSession::attach(&mock.device, 4242, 0xf0).unwrap();
+
+        let call = mock.state().dbg_traps[0].clone();
+        assert_eq!(call.op, KFD_IOC_DBG_TRAP_ENABLE);
+        assert_eq!(call.pid, 4242);
+        assert_eq!(call.exception_mask, 0xf0);
+        assert_eq!(call.rinfo_size as usize, mem::size_of::<RuntimeInfo>());
+        assert_eq!(call.dbg_fd as RawFd, session.event_fd());
+
+        assert_eq!(
+            session.runtime_info().runtime_state,
+            DEBUG_RUNTIME_STATE_ENABLED
+        );
+        assert_eq!(session.runtime_info().r_debug, 0xdeb6);
+    }
+
+    #[test]
+    fn suspend_and_resume_return_the_ids_as_rewritten() {
+        let mock = Mock::new();
+        mock.state().invalid_queues.push(2);
+        let session = DebugSession::attach(&mock.device, 4242, 0).unwrap();
+
+        let suspended = session.suspend_queues(&[1, 2, 3], 10).unwrap();
+        assert_eq!(suspended, [1, 2 | KFD_DBG_QUEUE_INVALID_MASK, 3]);
+        let resumed = session.resume_queues(&[3, 2]).unwrap();
+        assert_eq!(resumed, [3, 2 | KFD_DBG_QUEUE_INVALID_MASK]);
+
+        let state = mock.state();
+        let suspend = &state.dbg_traps[1];
+        assert_eq!(suspend.op, KFD_IOC_DBG_TRAP_SUSPEND_QUEUES);
+        assert_eq!(suspend.queues, [1, 2, 3]);
+        assert_eq!(suspend.exception_mask, 0);
+        assert_eq!(suspend.grace_period, 10);
+        let resume = &state.dbg_traps[2];
+        assert_eq!(resume.op, KFD_IOC_DBG_TRAP_RESUME_QUEUES);
+        assert_eq!(resume.queues, [3, 2]);
+    }
+
+    #[test]
+    fn drop_disables_the_trap() {
+        let mock = Mock::new();
+        drop(DebugSession::attach(&mock.device, 4242, 0).unwrap());
+
+        let state = mock.state();
+        let ops: Vec<_> = state.dbg_traps.iter().map(|c| (c.op, c.pid)).collect();
+        assert_eq!(
+            ops,
+            [
+                (KFD_IOC_DBG_TRAP_ENABLE, 4242),
+                (KFD_IOC_DBG_TRAP_DISABLE, 4242)
+            ]
+        );
+    }
+}
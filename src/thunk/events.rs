@@ -5,20 +5,31 @@ use crate::kfd::device::KfdDevice;
 use crate::kfd::ioctl::{
     AMDKFD_IOC_CREATE_EVENT, AMDKFD_IOC_DESTROY_EVENT, AMDKFD_IOC_RESET_EVENT,
     AMDKFD_IOC_SET_EVENT, AMDKFD_IOC_WAIT_EVENTS, CreateEventArgs, DestroyEventArgs,
-    EventData as IoctlEventData, KFD_IOC_WAIT_RESULT_TIMEOUT, ResetEventArgs, SetEventArgs,
-    WaitEventsArgs,
+    EventData as IoctlEventData, HsaHwExceptionData, HsaMemoryExceptionData, HsaSignalEventData,
+    KFD_IOC_WAIT_RESULT_TIMEOUT, ResetEventArgs, SetEventArgs, WaitEventsArgs,
 };
 use crate::kfd::sysfs::HsaNodeProperties;
 use crate::thunk::memory::{Allocation, MemoryManager};
 use std::collections::HashMap;
 use std::os::fd::RawFd;
-use std::sync::Mutex;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::{mem, ptr};
 
 /// The hardware limit for signal events per process.
 pub const KFD_SIGNAL_EVENT_LIMIT: usize = 4096;
 
+/// The events page registered with KFD for this process.
+///
+/// KFD takes exactly one events page per process, on the first `CREATE_EVENT` that passes
+/// `event_page_offset`, and rejects any later one with `EINVAL`. Every `EventManager` in
+/// the process shares this page; it stays pinned by KFD until the process exits.
+///
+/// Once registered the page is untracked, so `MemoryManager::shutdown` and `free_memory`
+/// never release it. The `Allocation` still holds the manager that allocated it, which
+/// therefore also lives until the process exits.
+static EVENTS_PAGE: Mutex<Option<Arc<Allocation>>> = Mutex::new(None);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum HsaEventType {
@@ -39,6 +50,13 @@ impl HsaEventType {
     pub const fn is_system_event(&self) -> bool {
         !matches!(self, Self::Signal | Self::DebugEvent)
     }
+
+    /// KFD backs signal and debug events with an events page slot and reports their
+    /// firing through `last_event_age`; every other type is a driver-side notification.
+    #[must_use]
+    pub const fn has_signal_slot(&self) -> bool {
+        matches!(self, Self::Signal | Self::DebugEvent)
+    }
 }
 
 /// Represents the user-space sync variable associated with an event.
@@ -75,11 +93,46 @@ pub enum HsaEventDataPayload {
 unsafe impl Send for HsaEventDataPayload {}
 unsafe impl Sync for HsaEventDataPayload {}
 
+/// The raw KFD payload of an event, decoded from the `EventData` union.
+#[derive(Debug, Clone, Copy)]
+pub enum EventPayload {
+    MemoryException(HsaMemoryExceptionData),
+    HwException(HsaHwExceptionData),
+    Signal(HsaSignalEventData),
+}
+
+impl EventPayload {
+    /// Reads the union arm KFD fills in for `event_type`.
+    ///
+    /// Returns `None` for event types that carry no payload.
+    #[must_use]
+    pub fn from_raw(event_type: HsaEventType, data: &IoctlEventData) -> Option<Self> {
+        // SAFETY: KFD writes the arm matching the event type, and every arm is plain
+        // integer data, so reading the one selected by `event_type` is always valid.
+        unsafe {
+            match event_type {
+                HsaEventType::Signal | HsaEventType::DebugEvent => {
+                    Some(Self::Signal(data.payload.signal_event_data))
+                }
+                HsaEventType::Memory => {
+                    Some(Self::MemoryException(data.payload.memory_exception_data))
+                }
+                HsaEventType::HwException => {
+                    Some(Self::HwException(data.payload.hw_exception_data))
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
 /// Details of a memory access fault reported by the GPU.
 #[derive(Debug, Clone)]
 pub struct HsaMemoryAccessFault {
     /// The logical Node ID where the fault occurred.
     pub node_id: u32,
+    /// The KFD GPU ID of the faulting device.
+    pub gpu_id: u32,
     /// The virtual address that caused the fault.
     pub virtual_address: u64,
     /// Specific failure flags.
@@ -143,6 +196,18 @@ impl Drop for HsaEvent {
 }
 
 impl HsaEvent {
+    /// File descriptor that becomes readable when the event fires, for use with
+    /// `epoll`/`poll`.
+    ///
+    /// Always `None`: KFD events are not backed by an fd. `hw_data1` is only the KFD
+    /// event id and the sole way to sleep on an event is `AMDKFD_IOC_WAIT_EVENTS`, so many
+    /// signals are waited on with [`EventManager::wait_on_multiple_events`] (or
+    /// `signal::wait_any`) from a dedicated thread.
+    #[must_use]
+    pub const fn poll_fd(&self) -> Option<RawFd> {
+        None
+    }
+
     /// Signals the event (sets it to signaled state).
     pub fn set(&self) -> HsaResult<()> {
         if self.event_type.is_system_event() {
@@ -169,8 +234,8 @@ impl HsaEvent {
 /// Manages the global context for events, specifically the Events Page.
 pub struct EventManager {
     /// The shared memory page used by the GPU to write signal events.
-    /// This is allocated once per process/device context.
-    events_page: Option<Allocation>,
+    /// There is one per process, shared by all managers (see `EVENTS_PAGE`).
+    events_page: Option<Arc<Allocation>>,
 
     /// Mapping from Kernel GPU IDs to Logical Node IDs.
     /// Required to translate `gpu_id` in exception reports back to the user-facing `node_id`.
@@ -199,8 +264,9 @@ impl EventManager {
 
     /// Creates a new HSA Event.
     ///
-    /// If this is the first signal event created, it automatically allocates the
-    /// hardware Events Page on the GPU associated with the event's `node_id`.
+    /// If this is the first event created in the process, it automatically allocates the
+    /// hardware Events Page on the GPU associated with the event's `node_id`. Later
+    /// managers reuse that page, as KFD accepts only one per process.
     pub fn create_event(
         &mut self,
         device: &KfdDevice,
@@ -218,34 +284,63 @@ impl EventManager {
             ..Default::default()
         };
 
+        // Held across the ioctl while this call registers the process's events page.
+        let mut registering = None;
         if self.events_page.is_none() {
-            let alloc_size = KFD_SIGNAL_EVENT_LIMIT * 8;
-
-            let events_alloc = memory_manager
-                .allocate_gtt(device, alloc_size, desc.node_id, drm_fd)
-                .map_err(|e| -> HsaError {
-                    eprintln!("Failed to allocate events page: {e:?}");
-                    e
-                })?;
+            let global = EVENTS_PAGE.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(page) = global.as_ref() {
+                self.events_page = Some(Arc::clone(page));
+            } else {
+                let alloc_size = KFD_SIGNAL_EVENT_LIMIT * 8;
+
+                let events_alloc = memory_manager
+                    .allocate_gtt(device, alloc_size, desc.node_id, drm_fd)
+                    .map_err(|e| -> HsaError {
+                        eprintln!("Failed to allocate events page: {e:?}");
+                        e.into()
+                    })?;
+
+                if !events_alloc.ptr.is_null() {
+                    unsafe { ptr::write_bytes(events_alloc.ptr, 0, alloc_size) };
+                }
 
-            if !events_alloc.ptr.is_null() {
-                unsafe { ptr::write_bytes(events_alloc.ptr, 0, alloc_size) };
+                args.event_page_offset = events_alloc.handle;
+                self.events_page = Some(Arc::new(events_alloc));
+                registering = Some(global);
             }
-
-            self.events_page = Some(events_alloc);
         }
 
-        if let Some(alloc) = &self.events_page {
-            args.event_page_offset = alloc.handle;
+        let created = unsafe { device.ioctl(AMDKFD_IOC_CREATE_EVENT, &mut args) };
+        if let Some(mut global) = registering {
+            if created.is_ok() {
+                // KFD owns the page from now on; the manager must not free it.
+                if let Some(page) = &self.events_page {
+                    memory_manager.untrack(page.handle);
+                }
+                global.clone_from(&self.events_page);
+            } else {
+                self.events_page = None;
+            }
         }
-
-        if let Err(e) = unsafe { device.ioctl(AMDKFD_IOC_CREATE_EVENT, &mut args) } {
+        if let Err(e) = created {
             eprintln!("AMDKFD_IOC_CREATE_EVENT failed: {e:?}");
-            return Err(HsaError::from(e));
+            return Err(HsaError::from_ioctl(e));
+        }
+
+        // The slot index comes straight from the kernel and is used to compute the mailbox
+        // address signals write to, so never trust it beyond the page we allocated.
+        let slot_count = self.events_page.as_ref().map_or(0, |alloc| alloc.size / 8);
+        let slot_in_bounds = (args.event_slot_index as usize) < slot_count;
+        if desc.event_type.has_signal_slot() && !slot_in_bounds {
+            device.destroy_event(args.event_id).ok();
+            return Err(HsaError::General(format!(
+                "KFD returned event slot {} outside the {slot_count}-slot events page",
+                args.event_slot_index
+            )));
         }
 
         let hw_data2 = if let Some(alloc) = &self.events_page
-            && args.event_slot_index < KFD_SIGNAL_EVENT_LIMIT as u32
+            && slot_in_bounds
         {
             let base = alloc.ptr as u64;
             base + (u64::from(args.event_slot_index) * 8)
@@ -271,13 +366,105 @@ impl EventManager {
             };
             if let Err(e) = unsafe { device.ioctl(AMDKFD_IOC_SET_EVENT, &mut set_args) } {
                 self.destroy_event(device, &event).ok();
-                return Err(HsaError::from(e));
+                return Err(HsaError::from_ioctl(e));
             }
         }
 
         Ok(event)
     }
 
+    /// Creates an auto-reset, unsignaled event of any type, with no sync variable.
+    ///
+    /// Signal and debug events get an events page slot; the other types are notifications
+    /// KFD raises itself (memory faults, hardware exceptions, profiling, queue events, ...).
+    pub fn create_typed_event(
+        &mut self,
+        device: &KfdDevice,
+        memory_manager: &mut MemoryManager,
+        drm_fd: RawFd,
+        event_type: HsaEventType,
+        node_id: u32,
+    ) -> HsaResult<HsaEvent> {
+        let desc = HsaEventDescriptor {
+            event_type,
+            node_id,
+            sync_var: HsaSyncVar {
+                user_data: ptr::null_mut(),
+                sync_var_size: 0,
+            },
+        };
+        self.create_event(device, memory_manager, drm_fd, &desc, false, false)
+    }
+
+    /// Creates an event that KFD signals on GPU memory access faults of this process.
+    pub fn create_memory_event(
+        &mut self,
+        device: &KfdDevice,
+        memory_manager: &mut MemoryManager,
+        drm_fd: RawFd,
+        node_id: u32,
+    ) -> HsaResult<HsaEvent> {
+        self.create_typed_event(
+            device,
+            memory_manager,
+            drm_fd,
+            HsaEventType::Memory,
+            node_id,
+        )
+    }
+
+    /// Waits up to `timeout_ms` on a single event and returns the payload KFD reported,
+    /// decoded according to the event's type.
+    ///
+    /// Signal and debug events yield `EventPayload::Signal` and advance `last_event_age`.
+    /// Types without a payload (node change, profile, queue events, ...) yield `None`.
+    ///
+    /// # Errors
+    /// Returns `HsaError::WaitTimeout` if the timeout expires, or the ioctl error.
+    pub fn wait_event(
+        &self,
+        device: &KfdDevice,
+        event: &HsaEvent,
+        timeout_ms: u32,
+    ) -> HsaResult<Option<EventPayload>> {
+        let (raw, _) = Self::wait_raw(device, &[event], false, timeout_ms)?;
+        let payload = EventPayload::from_raw(event.event_type, &raw[0]);
+        if let Some(EventPayload::Signal(data)) = payload {
+            event
+                .last_event_age
+                .fetch_max(data.last_event_age, Ordering::Relaxed);
+        }
+        Ok(payload)
+    }
+
+    /// Waits up to `timeout_ms` on a memory event and returns the decoded fault.
+    ///
+    /// Returns `Ok(None)` on timeout, or if the wake did not carry a memory exception.
+    pub fn wait_memory_exception(
+        &self,
+        device: &KfdDevice,
+        event: &HsaEvent,
+        timeout_ms: u32,
+    ) -> HsaResult<Option<HsaMemoryAccessFault>> {
+        if event.event_type != HsaEventType::Memory {
+            return Err(HsaError::General(
+                "wait_memory_exception requires a memory event".into(),
+            ));
+        }
+
+        *event.payload.lock().unwrap() = HsaEventDataPayload::None;
+        match self.wait_on_multiple_events(device, &[event], false, timeout_ms) {
+            Ok(_) => {}
+            Err(HsaError::WaitTimeout) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        match &*event.payload.lock().unwrap() {
+            HsaEventDataPayload::MemoryAccessFault(fault) => Ok(Some(fault.clone())),
+            _ => Ok(None),
+        }
+    }
+
     pub fn destroy_event(&self, device: &KfdDevice, event: &HsaEvent) -> HsaResult<()> {
         let mut args = DestroyEventArgs {
             event_id: event.event_id,
@@ -286,7 +473,7 @@ impl EventManager {
 
         if let Err(e) = unsafe { device.ioctl(AMDKFD_IOC_DESTROY_EVENT, &mut args) } {
             eprintln!("AMDKFD_IOC_DESTROY_EVENT failed: {e:?}");
-            return Err(HsaError::from(e));
+            return Err(HsaError::from_ioctl(e));
         }
         Ok(())
     }
@@ -307,6 +494,17 @@ impl EventManager {
         Ok(())
     }
 
+    /// Sets several events through one entry point. Events are currently set one ioctl at a
+    /// time; every event is attempted and the first error is returned.
+    pub fn set_events(&self, device: &KfdDevice, events: &[&HsaEvent]) -> HsaResult<()> {
+        let mut result = Ok(());
+        for event in events {
+            let set = self.set_event(device, event);
+            result = result.and(set);
+        }
+        result
+    }
+
     pub fn reset_event(&self, device: &KfdDevice, event: &HsaEvent) -> HsaResult<()> {
         if event.event_type.is_system_event() {
             return Err(HsaError::General(
@@ -345,65 +543,33 @@ impl EventManager {
         wait_all: bool,
         timeout_ms: u32,
     ) -> HsaResult<Vec<usize>> {
-        if events.is_empty() {
-            return Err(HsaError::General("No events to wait on.".into()));
-        }
-
-        let mut ioctl_events: Vec<IoctlEventData> = events
-            .iter()
-            .map(|e| {
-                let mut data: IoctlEventData = unsafe { mem::zeroed() };
-                data.event_id = e.event_id;
-                data.kfd_event_data_ext = 0;
-
-                if e.event_type == HsaEventType::Signal {
-                    data.payload.signal_event_data.last_event_age =
-                        e.last_event_age.load(Ordering::Relaxed);
-                }
-                data
-            })
-            .collect();
-
-        let mut args = WaitEventsArgs {
-            events_ptr: ioctl_events.as_mut_ptr() as u64,
-            num_events: ioctl_events.len() as u32,
-            wait_for_all: u32::from(wait_all),
-            timeout: timeout_ms,
-            wait_result: 0,
-        };
-
-        unsafe { device.ioctl(AMDKFD_IOC_WAIT_EVENTS, &mut args)? };
-
-        if args.wait_result == KFD_IOC_WAIT_RESULT_TIMEOUT {
-            return Err(HsaError::WaitTimeout);
-        }
+        let (ioctl_events, wait_result) = Self::wait_raw(device, events, wait_all, timeout_ms)?;
 
         let mut signaled_indices = Vec::new();
 
         for (i, ioctl_evt) in ioctl_events.iter().enumerate() {
             let event = events[i];
 
-            match event.event_type {
-                HsaEventType::Signal => unsafe {
-                    let new_age = ioctl_evt.payload.signal_event_data.last_event_age;
+            match EventPayload::from_raw(event.event_type, ioctl_evt) {
+                Some(EventPayload::Signal(data)) => {
+                    let new_age = data.last_event_age;
                     let old_age = event.last_event_age.load(Ordering::Relaxed);
 
                     if new_age > old_age {
                         event.last_event_age.store(new_age, Ordering::Relaxed);
                         signaled_indices.push(i);
                     }
-                },
-
-                HsaEventType::Memory => unsafe {
-                    if ioctl_evt.payload.memory_exception_data.gpu_id != 0 {
-                        let data = &ioctl_evt.payload.memory_exception_data;
+                }
 
+                Some(EventPayload::MemoryException(data)) => {
+                    if data.gpu_id != 0 {
                         let node_id = *self.gpu_to_node_map.get(&data.gpu_id).unwrap_or(&0);
                         {
                             let mut payload_guard = event.payload.lock().unwrap();
                             *payload_guard =
                                 HsaEventDataPayload::MemoryAccessFault(HsaMemoryAccessFault {
                                     node_id,
+                                    gpu_id: data.gpu_id,
                                     virtual_address: data.va,
                                     failure: HsaAccessAttributeFailure {
                                         not_present: data.failure.not_present != 0,
@@ -418,11 +584,9 @@ impl EventManager {
                         }
                         signaled_indices.push(i);
                     }
-                },
-                HsaEventType::HwException => unsafe {
-                    if ioctl_evt.payload.hw_exception_data.gpu_id != 0 {
-                        let data = &ioctl_evt.payload.hw_exception_data;
-
+                }
+                Some(EventPayload::HwException(data)) => {
+                    if data.gpu_id != 0 {
                         let node_id = *self.gpu_to_node_map.get(&data.gpu_id).unwrap_or(&0);
                         {
                             let mut payload_guard = event.payload.lock().unwrap();
@@ -435,16 +599,16 @@ impl EventManager {
                         }
                         signaled_indices.push(i);
                     }
-                },
-                _ => {
+                }
+                None => {
                     signaled_indices.push(i);
                 }
             }
         }
 
-        if signaled_indices.is_empty() && args.wait_result == 0 {
+        if signaled_indices.is_empty() && wait_result == 0 {
             for (i, event) in events.iter().enumerate() {
-                if event.event_type == HsaEventType::Signal {
+                if event.event_type.has_signal_slot() {
                     signaled_indices.push(i);
                 }
             }
@@ -452,4 +616,230 @@ impl EventManager {
 
         Ok(signaled_indices)
     }
+
+    /// Issues `WAIT_EVENTS` and returns the per-event data KFD filled in and the wait result.
+    fn wait_raw(
+        device: &KfdDevice,
+        events: &[&HsaEvent],
+        wait_all: bool,
+        timeout_ms: u32,
+    ) -> HsaResult<(Vec<IoctlEventData>, u32)> {
+        if events.is_empty() {
+            return Err(HsaError::General("No events to wait on.".into()));
+        }
+
+        let mut ioctl_events: Vec<IoctlEventData> = events
+            .iter()
+            .map(|e| {
+                let mut data: IoctlEventData = unsafe { mem::zeroed() };
+                data.event_id = e.event_id;
+                data.kfd_event_data_ext = 0;
+
+                if e.event_type.has_signal_slot() {
+                    data.payload.signal_event_data.last_event_age =
+                        e.last_event_age.load(Ordering::Relaxed);
+                }
+                data
+            })
+            .collect();
+
+        let mut args = WaitEventsArgs {
+            events_ptr: ioctl_events.as_mut_ptr() as u64,
+            num_events: ioctl_events.len() as u32,
+            wait_for_all: u32::from(wait_all),
+            timeout: timeout_ms,
+            wait_result: 0,
+        };
+
+        unsafe { device.ioctl(AMDKFD_IOC_WAIT_EVENTS, &mut args)? };
+
+        if args.wait_result == KFD_IOC_WAIT_RESULT_TIMEOUT {
+            return Err(HsaError::WaitTimeout);
+        }
+
+        Ok((ioctl_events, args.wait_result))
+    }
+}
+
+// ===============================================================================================
+// Memory Fault Handling
+// ===============================================================================================
+
+/// A GPU VM fault, as delivered by a memory exception event.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryFault {
+    pub va: u64,
+    pub gpu_id: u32,
+    /// The page was not mapped.
+    pub not_present: bool,
+    /// Write to a read-only page.
+    pub read_only: bool,
+    /// Instruction fetch from a non-executable page.
+    pub no_execute: bool,
+}
+
+impl From<&HsaMemoryAccessFault> for MemoryFault {
+    fn from(fault: &HsaMemoryAccessFault) -> Self {
+        Self {
+            va: fault.virtual_address,
+            gpu_id: fault.gpu_id,
+            not_present: fault.failure.not_present,
+            read_only: fault.failure.read_only,
+            no_execute: fault.failure.no_execute,
+        }
+    }
+}
+
+/// Owns a memory exception event and turns its deliveries into [`MemoryFault`]s.
+///
+/// KFD signals memory exception events for every VM fault of the process, regardless of
+/// the node the event was created on.
+#[derive(Debug)]
+pub struct FaultHandler {
+    event: HsaEvent,
+}
+
+impl FaultHandler {
+    /// Creates the memory exception event for `node_id`.
+    pub fn new(
+        event_mgr: &mut EventManager,
+        device: &KfdDevice,
+        memory_manager: &mut MemoryManager,
+        drm_fd: RawFd,
+        node_id: u32,
+    ) -> HsaResult<Self> {
+        let event = event_mgr.create_memory_event(device, memory_manager, drm_fd, node_id)?;
+        Ok(Self { event })
+    }
+
+    #[must_use]
+    pub const fn event(&self) -> &HsaEvent {
+        &self.event
+    }
+
+    /// Waits up to `timeout_ms` for the next fault.
+    ///
+    /// Returns `Err(HsaError::WaitTimeout)` if no fault was delivered in time.
+    pub fn wait(
+        &self,
+        event_mgr: &EventManager,
+        device: &KfdDevice,
+        timeout_ms: u32,
+    ) -> HsaResult<MemoryFault> {
+        event_mgr
+            .wait_memory_exception(device, &self.event, timeout_ms)?
+            .map(|fault| MemoryFault::from(&fault))
+            .ok_or(HsaError::WaitTimeout)
+    }
+}
+
+/// Forgets the process events page, as if the process had restarted.
+#[cfg(test)]
+pub(crate) fn release_events_page() {
+    EVENTS_PAGE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kfd::mock::Mock;
+    use crate::thunk::memory::MemoryError;
+
+    const GPU_ID: u32 = 0x5347;
+
+    #[test]
+    fn events_page_is_shared_and_outside_manager_tracking() {
+        let mock = Mock::with_gpu(GPU_ID);
+        let gpu = HsaNodeProperties {
+            kfd_gpu_id: GPU_ID,
+            ..HsaNodeProperties::default()
+        };
+        let nodes = [HsaNodeProperties::default(), gpu];
+        let first = MemoryManager::new(&mock.device, &nodes).unwrap();
+        let second = MemoryManager::new(&mock.device, &nodes).unwrap();
+        let tracked = first.lock().unwrap().allocation_count();
+
+        let mut events = EventManager::new(&nodes);
+        let a = events
+            .create_typed_event(
+                &mock.device,
+                &mut first.lock().unwrap(),
+                mock.drm_fd(),
+                HsaEventType::Signal,
+                1,
+            )
+            .unwrap();
+        let b = EventManager::new(&nodes)
+            .create_typed_event(
+                &mock.device,
+                &mut second.lock().unwrap(),
+                mock.drm_fd(),
+                HsaEventType::Signal,
+                1,
+            )
+            .unwrap();
+
+        let page = Arc::clone(EVENTS_PAGE.lock().unwrap().as_ref().unwrap());
+        assert_eq!(mock.state().events_pages, [page.handle]);
+        for event in [&a, &b] {
+            let offset = event.hw_data2 - page.ptr as u64;
+            assert!(offset < page.size as u64);
+        }
+        assert_ne!(a.hw_data2, b.hw_data2);
+
+        // Neither manager frees the page, not even the one that allocated it.
+        let mut mgr = first.lock().unwrap();
+        assert_eq!(mgr.allocation_count(), tracked);
+        mgr.shutdown().unwrap();
+        assert!(matches!(
+            mgr.free_memory(&mock.device, page.handle),
+            Err(MemoryError::UnknownHandle(_))
+        ));
+        drop(mgr);
+        drop(second);
+        assert!(mock.state().buffers.contains_key(&page.handle));
+
+        // The page pins the manager it came from.
+        let weak = Arc::downgrade(&first);
+        drop((first, events));
+        assert!(weak.upgrade().is_some());
+
+        drop((a, b, page));
+        release_events_page();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn out_of_range_event_slot_is_rejected_and_destroyed() {
+        let mock = Mock::with_gpu(GPU_ID);
+        let gpu = HsaNodeProperties {
+            kfd_gpu_id: GPU_ID,
+            ..HsaNodeProperties::default()
+        };
+        let nodes = [HsaNodeProperties::default(), gpu];
+        let manager = MemoryManager::new(&mock.device, &nodes).unwrap();
+        mock.state().event_slot = Some(KFD_SIGNAL_EVENT_LIMIT as u32);
+
+        let mut events = EventManager::new(&nodes);
+        let result = events.create_typed_event(
+            &mock.device,
+            &mut manager.lock().unwrap(),
+            mock.drm_fd(),
+            HsaEventType::Signal,
+            1,
+        );
+        assert!(matches!(result, Err(HsaError::General(_))));
+
+        let state = mock.state();
+        assert_eq!(state.count(AMDKFD_IOC_DESTROY_EVENT), 1);
+        assert!(state.events.is_empty());
+        drop(state);
+
+        drop(events);
+        release_events_page();
+    }
 }
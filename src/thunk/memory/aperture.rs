@@ -1,32 +1,215 @@
-use super::ApertureAllocator;
-use std::collections::BTreeMap;
+use super::{ApertureAllocator, MemoryError};
+use std::collections::{BTreeMap, BTreeSet};
+
+const PAGE_SIZE: u64 = 4096;
 
 /// Represents a managed range of Virtual Address space.
 /// Closely mirrors `manageable_aperture_t` in `fmm.c`.
+///
+/// Free space is kept as a list of coalesced ranges: `free_va` merges a released range with
+/// its free neighbours, and `allocate_va` picks the smallest free range that fits.
+///
+/// Every reservation is padded with `guard_pages` pages of never-mapped VA on both sides,
+/// so a GPU overrun past the end of one buffer faults instead of hitting its neighbour.
 #[derive(Debug)]
 pub struct Aperture {
     base: u64,
     limit: u64,
     align: u64,
     guard_pages: u64,
+    /// Reserved ranges (guard pages included), start -> size.
     allocations: BTreeMap<u64, u64>,
+    /// Starts of the reservations made without guard pages.
+    unguarded: BTreeSet<u64>,
+    /// Free ranges, start -> size. Never holds two adjacent ranges.
+    free: BTreeMap<u64, u64>,
 }
 
 impl Aperture {
+    /// An aperture covering `[base, limit]`.
+    ///
+    /// Sizes are kept in a `u64`, so an aperture reaching `u64::MAX` loses its last byte
+    /// rather than overflowing.
     #[must_use]
-    pub const fn new(base: u64, limit: u64, align: u64, guard_pages: u64) -> Self {
+    pub fn new(base: u64, limit: u64, align: u64, guard_pages: u64) -> Self {
+        let mut free = BTreeMap::new();
+        if limit > base {
+            free.insert(base, (limit - base).saturating_add(1));
+        }
         Self {
             base,
             limit,
             align,
             guard_pages,
             allocations: BTreeMap::new(),
+            unguarded: BTreeSet::new(),
+            free,
         }
     }
 
+    /// First address of the aperture.
+    #[must_use]
+    pub const fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Size of the aperture in bytes.
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        if self.limit > self.base {
+            (self.limit - self.base).saturating_add(1)
+        } else {
+            0
+        }
+    }
+
+    /// Number of unmapped guard pages placed on each side of a reservation.
+    #[must_use]
+    pub const fn guard_pages(&self) -> u64 {
+        self.guard_pages
+    }
+
+    const fn guard_size(&self) -> u64 {
+        self.guard_pages * PAGE_SIZE
+    }
+
     const fn align_up(val: u64, align: u64) -> u64 {
         (val + align - 1) & !(align - 1)
     }
+
+    /// Ratio of the largest free block to the total free space.
+    ///
+    /// 1.0 means all free space is contiguous (or there is none); values approaching 0
+    /// mean the free space is split into many small holes.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fragmentation_ratio(&self) -> f32 {
+        let total: u64 = self.free.values().sum();
+        if total == 0 {
+            return 1.0;
+        }
+        let largest = self.free.values().copied().max().unwrap_or(0);
+        (largest as f64 / total as f64) as f32
+    }
+
+    /// Reserved ranges as `(start_va, size)`, in address order. Guard pages are not included.
+    pub fn iter_reserved(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        let guard_size = self.guard_size();
+        self.allocations.iter().map(move |(&start, &size)| {
+            let guard = if self.unguarded.contains(&start) {
+                0
+            } else {
+                guard_size
+            };
+            (start + guard, (size - guard * 2) as usize)
+        })
+    }
+
+    /// Raw free span in bytes, guard page overhead included. See [`Self::usable_bytes`].
+    #[must_use]
+    pub fn free_bytes(&self) -> u64 {
+        self.free.values().sum()
+    }
+
+    /// Reserves exactly `[va, va + size)`, e.g. to recreate an exporter's VA on import.
+    ///
+    /// # Errors
+    /// Fails with `UnalignedRequest` if `va` or `size` is not page-aligned, or
+    /// `RangeUnavailable` if the range (with its guard pages) is outside the aperture or
+    /// overlaps a reservation.
+    pub fn reserve_va_at(&mut self, va: u64, size: usize) -> Result<(), MemoryError> {
+        if size == 0 || !va.is_multiple_of(PAGE_SIZE) || !(size as u64).is_multiple_of(PAGE_SIZE) {
+            return Err(MemoryError::UnalignedRequest);
+        }
+        if self.reserve_va(va, size) {
+            Ok(())
+        } else {
+            Err(MemoryError::RangeUnavailable { addr: va, size })
+        }
+    }
+
+    /// Like [`Self::reserve_va_at`], but without guard pages: the range may sit right
+    /// against its neighbours. For VA that mirrors memory the aperture does not own, such
+    /// as userptr host buffers, where adjacent host allocations must stay registrable.
+    ///
+    /// # Errors
+    /// Fails with `UnalignedRequest` if `va` or `size` is not page-aligned, or
+    /// `RangeUnavailable` if the range is outside the aperture or overlaps a reservation.
+    pub fn reserve_va_unguarded(&mut self, va: u64, size: usize) -> Result<(), MemoryError> {
+        if size == 0 || !va.is_multiple_of(PAGE_SIZE) || !(size as u64).is_multiple_of(PAGE_SIZE) {
+            return Err(MemoryError::UnalignedRequest);
+        }
+        if self.reserve_range(va, size as u64) {
+            self.unguarded.insert(va);
+            Ok(())
+        } else {
+            Err(MemoryError::RangeUnavailable { addr: va, size })
+        }
+    }
+
+    /// Bytes that can still be handed out, summed over the free blocks.
+    ///
+    /// Each block loses the leading and trailing guard pages a reservation carves out of it,
+    /// so this is what the allocator can actually satisfy rather than the raw free span.
+    #[must_use]
+    pub fn usable_bytes(&self) -> u64 {
+        let guards = self.guard_size() * 2;
+        self.free
+            .iter()
+            .map(|(&start, &size)| {
+                let skew = Self::align_up(start, self.align) - start;
+                size.saturating_sub(skew + guards)
+            })
+            .sum()
+    }
+
+    /// Carves `[start, start + size)` out of the free block beginning at `block_start`.
+    fn take_from_block(&mut self, block_start: u64, start: u64, size: u64) {
+        let Some(block_size) = self.free.remove(&block_start) else {
+            return;
+        };
+        if start > block_start {
+            self.free.insert(block_start, start - block_start);
+        }
+        let block_end = block_start + block_size;
+        let end = start + size;
+        if block_end > end {
+            self.free.insert(end, block_end - end);
+        }
+        self.allocations.insert(start, size);
+    }
+
+    /// Carves exactly `[start, start + size)` out of the free block containing it.
+    fn reserve_range(&mut self, start: u64, size: u64) -> bool {
+        // Only the free block starting at or below `start` can contain the range.
+        let Some((&block_start, &block_size)) = self.free.range(..=start).next_back() else {
+            return false;
+        };
+        if start
+            .checked_add(size)
+            .is_none_or(|end| end > block_start + block_size)
+        {
+            return false;
+        }
+
+        self.take_from_block(block_start, start, size);
+        true
+    }
+
+    /// Returns `[start, start + size)` to the free list, merging with adjacent free ranges.
+    fn release(&mut self, mut start: u64, mut size: u64) {
+        if let Some((&prev_start, &prev_size)) = self.free.range(..start).next_back()
+            && prev_start + prev_size == start
+        {
+            self.free.remove(&prev_start);
+            start = prev_start;
+            size += prev_size;
+        }
+        if let Some(next_size) = self.free.remove(&(start + size)) {
+            size += next_size;
+        }
+        self.free.insert(start, size);
+    }
 }
 
 impl ApertureAllocator for Aperture {
@@ -34,44 +217,48 @@ impl ApertureAllocator for Aperture {
         (self.base, self.limit)
     }
 
-    /// Port of `reserved_aperture_allocate_aligned` from `fmm.c`
+    /// Best-fit variant of `reserved_aperture_allocate_aligned` from `fmm.c`
     fn allocate_va(&mut self, size: usize, align: usize) -> Option<u64> {
         let size = size as u64;
         let align = std::cmp::max(align as u64, self.align);
-        let guard_size = self.guard_pages * 4096;
+        let guard_size = self.guard_size();
 
+        // Leading guard + buffer + trailing guard. The next block can start right after the
+        // trailing guard, so neighbouring buffers are always `size + guard` or more apart.
         let request_size = size + (guard_size * 2);
 
-        let mut candidate_start = Self::align_up(self.base, align);
-
-        for (&alloc_start, &alloc_size) in &self.allocations {
-            let alloc_end = alloc_start + alloc_size;
-
-            if alloc_start > candidate_start {
-                let gap = alloc_start - candidate_start;
-                if gap >= request_size {
-                    self.allocations.insert(candidate_start, request_size);
-                    return Some(candidate_start + guard_size);
-                }
+        let mut best: Option<(u64, u64, u64)> = None; // (block_start, block_size, start)
+        for (&block_start, &block_size) in &self.free {
+            let start = Self::align_up(block_start, align);
+            let fits = start - block_start + request_size <= block_size;
+            if fits && best.is_none_or(|(_, best_size, _)| block_size < best_size) {
+                best = Some((block_start, block_size, start));
             }
-
-            candidate_start = Self::align_up(alloc_end, align);
         }
 
-        if candidate_start + request_size <= self.limit {
-            self.allocations.insert(candidate_start, request_size);
-            return Some(candidate_start + guard_size);
-        }
+        let (block_start, _, start) = best?;
+        self.take_from_block(block_start, start, request_size);
+        Some(start + guard_size)
+    }
 
-        None
+    fn reserve_va(&mut self, addr: u64, size: usize) -> bool {
+        let guard_size = self.guard_size();
+        let Some(start) = addr.checked_sub(guard_size) else {
+            return false;
+        };
+        self.reserve_range(start, size as u64 + (guard_size * 2))
     }
 
     fn free_va(&mut self, addr: u64, _size: usize) {
-        let guard_size = self.guard_pages * 4096;
-        let tracked_start = addr - guard_size;
+        let tracked_start = if self.unguarded.remove(&addr) {
+            addr
+        } else {
+            addr - self.guard_size()
+        };
 
-        if self.allocations.remove(&tracked_start).is_none() {
-            eprintln!("FMM Error: Tried to free VA 0x{addr:x} which was not tracked");
+        match self.allocations.remove(&tracked_start) {
+            Some(size) => self.release(tracked_start, size),
+            None => eprintln!("FMM Error: Tried to free VA 0x{addr:x} which was not tracked"),
         }
     }
 }
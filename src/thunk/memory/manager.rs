@@ -4,32 +4,50 @@
     clippy::cast_possible_wrap
 )]
 
-use crate::error::{HsaError, HsaResult};
-use crate::kfd::device::KfdDevice;
+use crate::error::HsaResult;
+use crate::kfd::device::{KfdDevice, KfdFeature};
 use crate::kfd::ioctl::{
-    AllocMemoryOfGpuArgs, GetProcessAperturesNewArgs, KFD_IOC_ALLOC_MEM_FLAGS_AQL_QUEUE_MEM,
-    KFD_IOC_ALLOC_MEM_FLAGS_COHERENT, KFD_IOC_ALLOC_MEM_FLAGS_CONTIGUOUS_BEST_EFFORT,
-    KFD_IOC_ALLOC_MEM_FLAGS_DOORBELL, KFD_IOC_ALLOC_MEM_FLAGS_EXECUTABLE,
-    KFD_IOC_ALLOC_MEM_FLAGS_EXT_COHERENT, KFD_IOC_ALLOC_MEM_FLAGS_GTT,
+    AllocMemoryOfGpuArgs, ExportDmabufArgs, GetDmabufInfoArgs, GetProcessAperturesArgs,
+    GetProcessAperturesNewArgs, ImportDmabufArgs, IpcExportHandleArgs, IpcImportHandleArgs,
+    KFD_IOC_ALLOC_MEM_FLAGS_AQL_QUEUE_MEM, KFD_IOC_ALLOC_MEM_FLAGS_COHERENT,
+    KFD_IOC_ALLOC_MEM_FLAGS_CONTIGUOUS_BEST_EFFORT, KFD_IOC_ALLOC_MEM_FLAGS_DOORBELL,
+    KFD_IOC_ALLOC_MEM_FLAGS_EXECUTABLE, KFD_IOC_ALLOC_MEM_FLAGS_EXT_COHERENT,
+    KFD_IOC_ALLOC_MEM_FLAGS_GTT, KFD_IOC_ALLOC_MEM_FLAGS_MMIO_REMAP,
     KFD_IOC_ALLOC_MEM_FLAGS_NO_SUBSTITUTE, KFD_IOC_ALLOC_MEM_FLAGS_PUBLIC,
-    KFD_IOC_ALLOC_MEM_FLAGS_UNCACHED, KFD_IOC_ALLOC_MEM_FLAGS_VRAM,
-    KFD_IOC_ALLOC_MEM_FLAGS_WRITABLE, MapMemoryToGpuArgs, ProcessDeviceApertures,
-    UnmapMemoryFromGpuArgs,
+    KFD_IOC_ALLOC_MEM_FLAGS_UNCACHED, KFD_IOC_ALLOC_MEM_FLAGS_USERPTR,
+    KFD_IOC_ALLOC_MEM_FLAGS_VRAM, KFD_IOC_ALLOC_MEM_FLAGS_WRITABLE, KFD_IOC_CACHE_POLICY_COHERENT,
+    KFD_IOC_CACHE_POLICY_NONCOHERENT, KFD_IOCTL_SVM_ATTR_ACCESS, KFD_IOCTL_SVM_ATTR_PREFERRED_LOC,
+    KFD_IOCTL_SVM_ATTR_PREFETCH_LOC, KFD_IOCTL_SVM_LOCATION_SYSMEM, KFD_IOCTL_SVM_OP_SET_ATTR,
+    MapMemoryToGpuArgs, NUM_OF_SUPPORTED_GPUS, ProcessDeviceApertures, SetMemoryPolicyArgs,
+    SetScratchBackingVaArgs, SvmArgs, SvmAttribute, UnmapMemoryFromGpuArgs,
 };
 use crate::kfd::sysfs::HsaNodeProperties;
 use crate::thunk::memory::aperture::Aperture;
-use crate::thunk::memory::{Allocation, ApertureAllocator, ArcManager};
+use crate::thunk::memory::{Allocation, ApertureAllocator, ArcManager, MemoryError};
 use crate::thunk::queues::builder::MemoryManager as BuilderMemoryManager;
+use crate::thunk::topology::{self, VaRange};
 use std::collections::HashMap;
 use std::os::fd::RawFd;
 use std::os::unix::io::AsRawFd;
 use std::ptr;
 use std::sync::{Arc, Mutex, Weak};
 
-const SVM_RESERVATION_LIMIT: u64 = (1 << 47) - 1; // 47-bit VA limit
-const SVM_MIN_BASE: u64 = 0x1000_0000; // Start at 256MB
+pub(crate) const SVM_RESERVATION_LIMIT: u64 = (1 << 47) - 1; // 47-bit VA limit
+pub(crate) const SVM_MIN_BASE: u64 = 0x1000_0000; // Start at 256MB
 const SVM_DEFAULT_ALIGN: usize = 4096;
 const SVM_GUARD_PAGES: usize = 1;
+const PAGE_SIZE: usize = 4096;
+
+/// Size [`MemoryManager::allocate`] reserves and allocates for a `size`-byte request
+/// aligned to `align`: `size` rounded up to a multiple of `align` (at least a page).
+///
+/// Returns `None` if the rounded size does not fit in `usize`.
+#[must_use]
+pub const fn aligned_allocation_size(size: usize, align: usize) -> Option<usize> {
+    let align = if align > PAGE_SIZE { align } else { PAGE_SIZE };
+    let size = if size > 0 { size } else { 1 };
+    size.checked_next_multiple_of(align)
+}
 
 /// Flags controlling memory allocation behavior (Maps to `HsaMemFlags`)
 #[allow(clippy::struct_excessive_bools)]
@@ -49,6 +67,8 @@ pub struct AllocFlags {
     pub extended_coherent: bool,
     pub scratch: bool,
     pub lds: bool,
+    pub userptr: bool,
+    pub mmio_remap: bool,
 }
 
 impl AllocFlags {
@@ -125,8 +145,72 @@ impl AllocFlags {
         self
     }
 
+    #[must_use]
+    pub const fn userptr(mut self) -> Self {
+        self.userptr = true;
+        self
+    }
+
+    #[must_use]
+    pub const fn mmio_remap(mut self) -> Self {
+        self.mmio_remap = true;
+        self
+    }
+
+    /// Flags for a queue buffer (ring, EOP, CWSR area, pointers) placed in VRAM or GTT.
+    ///
+    /// Queue buffers are executable and coherent; VRAM that is not host visible must not
+    /// be substituted with system memory.
+    #[must_use]
+    pub fn queue_buffer(vram: bool, host_access: bool) -> Self {
+        let mut flags = Self::new();
+        if vram {
+            flags = flags.vram();
+            if !host_access {
+                flags = flags.no_substitute();
+            }
+        } else {
+            flags = flags.gtt();
+        }
+        if host_access {
+            flags = flags.host_access();
+        }
+        flags.executable().coherent()
+    }
+
+    /// Bytes of VA to reserve for a `size`-byte allocation with these flags.
+    ///
+    /// KFD maps AQL queue memory a second time right after the first mapping so the CP can
+    /// read a wrapping packet contiguously, so such allocations need twice the VA, as in
+    /// libhsakmt. Returns `None` on overflow.
+    #[must_use]
+    pub const fn va_reservation_size(&self, size: usize) -> Option<usize> {
+        if self.aql_queue_mem {
+            size.checked_mul(2)
+        } else {
+            Some(size)
+        }
+    }
+
+    /// Returns the `mmap` protection bits used when mapping the allocation on the host.
+    ///
+    /// Read access is always granted; `PROT_WRITE` is dropped for read-only
+    /// allocations and `PROT_EXEC` is added for executable ones.
+    #[must_use]
+    pub const fn mmap_prot(&self) -> i32 {
+        let mut prot = libc::PROT_READ;
+        if !self.read_only {
+            prot |= libc::PROT_WRITE;
+        }
+        if self.execute_access {
+            prot |= libc::PROT_EXEC;
+        }
+        prot
+    }
+
     /// Converts high-level flags into the raw bitmask required by the KFD IOCTL.
-    const fn to_kfd_ioctl_flags(self) -> u32 {
+    #[must_use]
+    pub const fn to_kfd_ioctl_flags(self) -> u32 {
         let mut ioc_flags = 0;
 
         if self.vram {
@@ -141,6 +225,12 @@ impl AllocFlags {
         if self.doorbell {
             ioc_flags |= KFD_IOC_ALLOC_MEM_FLAGS_DOORBELL;
         }
+        if self.mmio_remap {
+            ioc_flags |= KFD_IOC_ALLOC_MEM_FLAGS_MMIO_REMAP;
+        }
+        if self.userptr {
+            ioc_flags |= KFD_IOC_ALLOC_MEM_FLAGS_USERPTR;
+        }
         if self.host_access {
             ioc_flags |= KFD_IOC_ALLOC_MEM_FLAGS_PUBLIC;
         }
@@ -170,12 +260,145 @@ impl AllocFlags {
     }
 }
 
+/// A KFD IPC share handle plus the size of the shared buffer, which KFD does not report
+/// on import.
+///
+/// Encodes to 32 bytes for sending over a socket: the four handle words, then the size,
+/// all little-endian, then 8 zero bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpcShareHandle {
+    pub share_handle: [u32; 4],
+    pub size: u64,
+}
+
+impl IpcShareHandle {
+    pub const ENCODED_LEN: usize = 32;
+
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(self.share_handle) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes[16..24].copy_from_slice(&self.size.to_le_bytes());
+        bytes
+    }
+
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let word =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&bytes[16..24]);
+        Self {
+            share_handle: [word(0), word(4), word(8), word(12)],
+            size: u64::from_le_bytes(size),
+        }
+    }
+}
+
+/// Which placement [`MemoryManager::allocate_contiguous_or_fallback`] ended up with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryTier {
+    /// VRAM allocated with `CONTIGUOUS_BEST_EFFORT`.
+    ContiguousVram,
+    /// Ordinary, possibly scattered VRAM.
+    Vram,
+    /// System memory.
+    Gtt,
+}
+
+/// GPU cache coherence for an aperture, see [`MemoryManager::set_default_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// GPU accesses snoop the CPU caches.
+    Coherent,
+    /// GPU accesses bypass coherence with the CPU caches.
+    NonCoherent,
+}
+
+impl CachePolicy {
+    const fn to_kfd(self) -> u32 {
+        match self {
+            Self::Coherent => KFD_IOC_CACHE_POLICY_COHERENT,
+            Self::NonCoherent => KFD_IOC_CACHE_POLICY_NONCOHERENT,
+        }
+    }
+
+    /// Arguments of the `SET_MEMORY_POLICY` ioctl applying `default` to `gpu_id` and
+    /// `alternate` to the `alternate_aperture` range.
+    #[must_use]
+    pub const fn memory_policy_args(
+        gpu_id: u32,
+        default: Self,
+        alternate: Self,
+        alternate_aperture: &Aperture,
+    ) -> SetMemoryPolicyArgs {
+        SetMemoryPolicyArgs {
+            alternate_aperture_base: alternate_aperture.base(),
+            alternate_aperture_size: alternate_aperture.size(),
+            gpu_id,
+            default_policy: default.to_kfd(),
+            alternate_policy: alternate.to_kfd(),
+            misc_process_flag: 0,
+        }
+    }
+}
+
+/// Scratch backing buffer registered for a node by [`MemoryManager::setup_scratch`].
+///
+/// A plain record rather than an `Allocation`: an `Allocation` holds a handle to the manager
+/// that would store it, and that cycle would keep the manager alive forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScratchBacking {
+    pub va: u64,
+    pub size: usize,
+    /// KFD handle of the GTT buffer.
+    pub handle: u64,
+    pub gpu_id: u32,
+}
+
+impl ScratchBacking {
+    /// Arguments of the `SET_SCRATCH_BACKING_VA` ioctl registering this buffer.
+    #[must_use]
+    pub const fn ioctl_args(&self) -> SetScratchBackingVaArgs {
+        SetScratchBackingVaArgs {
+            va_addr: self.va,
+            gpu_id: self.gpu_id,
+            pad: 0,
+        }
+    }
+}
+
+/// Bookkeeping kept by the `MemoryManager` for every live `Allocation`.
+#[derive(Debug, Clone)]
+pub struct AllocationRecord {
+    pub gpu_va: u64,
+    /// CPU address of the mapping, 0 if not CPU-visible.
+    pub cpu_addr: u64,
+    pub size: usize,
+    pub handle: u64,
+    pub node_id: u32,
+    pub flags: AllocFlags,
+    pub is_userptr: bool,
+    /// GPU ids the allocation is currently mapped to.
+    pub mapped_gpus: Vec<u32>,
+}
+
+impl AllocationRecord {
+    /// Whether `va` falls within `[gpu_va, gpu_va + size)`.
+    #[must_use]
+    pub const fn contains_va(&self, va: u64) -> bool {
+        va >= self.gpu_va && va - self.gpu_va < self.size as u64
+    }
+}
+
 /// Per-GPU Apertures derived from KFD Process Info
 #[derive(Debug)]
 struct GpuApertures {
     lds: Aperture,
     scratch: Aperture,
-    _gpuvm: Aperture,
+    gpuvm: Aperture,
 }
 
 #[derive(Debug)]
@@ -185,7 +408,21 @@ pub struct MemoryManager {
 
     gpu_apertures: HashMap<u32, GpuApertures>,
     node_to_gpu_id: HashMap<u32, u32>,
+    /// Number of topology nodes, CPU nodes included.
+    num_nodes: u32,
+    /// Live allocations keyed by KFD handle.
+    allocations: HashMap<u64, AllocationRecord>,
+    /// Doorbell pages shared between queues, keyed by `(gpu_id, mmap offset)`.
+    doorbell_pages: HashMap<(u32, u64), Weak<Allocation>>,
+    /// SVM ranges set aside by `reserve_svm_range`, base -> size.
+    svm_reservations: HashMap<u64, usize>,
+    /// Scratch backing buffers registered by `setup_scratch`, keyed by node id.
+    scratch_backing: HashMap<u32, ScratchBacking>,
+    /// Handles of the MMIO remap pages mapped at startup, keyed by GPU id.
+    mmio_remap_pages: HashMap<u32, u64>,
     self_weak: Option<Weak<Mutex<Self>>>,
+    /// Handle used to release whatever is still tracked at shutdown.
+    device: KfdDevice,
 }
 
 impl MemoryManager {
@@ -208,7 +445,16 @@ impl MemoryManager {
             pad: 0,
         };
 
-        device.get_process_apertures_new(&mut args)?;
+        if let Err(e) = device.get_process_apertures_new(&mut args) {
+            // Kernels without the new ioctl only report a fixed array of up to 7 GPUs.
+            let mut args_old = GetProcessAperturesArgs {
+                process_apertures: [ProcessDeviceApertures::default(); NUM_OF_SUPPORTED_GPUS],
+                num_of_nodes: NUM_OF_SUPPORTED_GPUS as u32,
+                pad: 0,
+            };
+            device.get_process_apertures(&mut args_old).map_err(|_| e)?;
+            apertures_vec = args_old.filled().to_vec();
+        }
 
         let mut gpu_apertures = HashMap::new();
         let mut max_gpuvm_limit = 0;
@@ -250,7 +496,7 @@ impl MemoryManager {
                 GpuApertures {
                     lds,
                     scratch,
-                    _gpuvm: gpuvm,
+                    gpuvm,
                 },
             );
         }
@@ -286,7 +532,14 @@ impl MemoryManager {
             svm_alt_aperture,
             gpu_apertures,
             node_to_gpu_id,
+            num_nodes: num_sysfs_nodes,
+            allocations: HashMap::new(),
+            doorbell_pages: HashMap::new(),
+            svm_reservations: HashMap::new(),
+            scratch_backing: HashMap::new(),
+            mmio_remap_pages: HashMap::new(),
             self_weak: None,
+            device: device.clone(),
         };
 
         let arc_mgr = Arc::new(Mutex::new(mgr));
@@ -294,11 +547,60 @@ impl MemoryManager {
         {
             let mut guard = arc_mgr.lock().unwrap();
             guard.self_weak = Some(Arc::downgrade(&arc_mgr));
+            guard.map_mmio_remap_pages(device);
         }
 
         Ok(arc_mgr)
     }
 
+    /// Sets the cache policy of `node_id` for the default SVM aperture and for the
+    /// alternate aperture (the fine-grain `svm_alt_aperture`, where coherent, uncached and
+    /// doorbell allocations live).
+    ///
+    /// # Errors
+    /// Fails with `UnknownNode` for nodes without a GPU, or if the ioctl fails.
+    pub fn set_default_policy(
+        &self,
+        device: &KfdDevice,
+        node_id: u32,
+        default: CachePolicy,
+        alternate: CachePolicy,
+    ) -> HsaResult<()> {
+        let gpu_id = self
+            .get_gpu_id(node_id)
+            .ok_or(MemoryError::UnknownNode(node_id))?;
+        let mut args =
+            CachePolicy::memory_policy_args(gpu_id, default, alternate, &self.svm_alt_aperture);
+        device.set_memory_policy(&mut args)?;
+        Ok(())
+    }
+
+    /// Describes every aperture (bounds, free bytes, reservation count) for crash reports
+    /// and VA exhaustion debugging.
+    #[must_use]
+    pub fn dump_apertures(&self) -> String {
+        let line = |name: &str, ap: &Aperture| {
+            format!(
+                "{name:<14} 0x{:016x}-0x{:016x} free {:>14} reserved {}\n",
+                ap.base(),
+                ap.base() + ap.size().saturating_sub(1),
+                ap.free_bytes(),
+                ap.iter_reserved().count()
+            )
+        };
+
+        let mut out = line("svm", &self.svm_aperture);
+        out.push_str(&line("svm-alt", &self.svm_alt_aperture));
+        let mut nodes: Vec<_> = self.gpu_apertures.iter().collect();
+        nodes.sort_by_key(|&(node_id, _)| *node_id);
+        for (node_id, ap) in nodes {
+            out.push_str(&line(&format!("node{node_id} lds"), &ap.lds));
+            out.push_str(&line(&format!("node{node_id} scratch"), &ap.scratch));
+            out.push_str(&line(&format!("node{node_id} gpuvm"), &ap.gpuvm));
+        }
+        out
+    }
+
     #[must_use]
     pub fn get_gpu_id(&self, node_id: u32) -> Option<u32> {
         self.node_to_gpu_id.get(&node_id).copied()
@@ -309,6 +611,14 @@ impl MemoryManager {
     /// This is the primary entry point for memory allocation.
     /// It handles selecting the correct aperture (SVM, Scratch, LDS, etc.) based on flags,
     /// calls the KFD IOCTL, and maps the memory.
+    ///
+    /// `size` is rounded up to a multiple of `align` (at least a page), so the aperture, KFD
+    /// and the returned `Allocation::size` all describe the same range. AQL queue memory
+    /// reserves twice that VA, see [`AllocFlags::va_reservation_size`].
+    ///
+    /// # Errors
+    /// Fails with `SizeOverflow` if the rounded size does not fit in `usize`, otherwise with
+    /// the aperture, allocation or mapping error.
     pub fn allocate(
         &mut self,
         device: &KfdDevice,
@@ -317,37 +627,82 @@ impl MemoryManager {
         flags: AllocFlags,
         node_id: Option<u32>,
         drm_fd: RawFd,
-    ) -> HsaResult<Allocation> {
-        let size = if size == 0 { 4096 } else { size };
+    ) -> Result<Allocation, MemoryError> {
+        let size = aligned_allocation_size(size, align)
+            .ok_or(MemoryError::SizeOverflow { size, align })?;
+        let va_size = flags
+            .va_reservation_size(size)
+            .ok_or(MemoryError::SizeOverflow { size, align })?;
 
         let node_id = node_id.unwrap_or_else(|| *self.node_to_gpu_id.keys().next().unwrap_or(&0));
 
-        let aperture = if flags.scratch {
+        let va_addr = self
+            .aperture_for(&flags, node_id)?
+            .allocate_va(va_size, align)
+            .ok_or(MemoryError::ApertureExhausted)?;
+
+        self.back_reserved_va(device, va_addr, size, flags, node_id, drm_fd)
+    }
+
+    /// Like [`Self::allocate`], but at the caller-chosen `va` instead of one picked from the
+    /// aperture, e.g. to reproduce an exporter's address on IPC import or CRIU restore.
+    ///
+    /// # Errors
+    /// Fails with `UnalignedRequest` or `RangeUnavailable` if `[va, va + size)` cannot be
+    /// reserved in the aperture `flags` select, or with the allocation error.
+    pub fn allocate_at(
+        &mut self,
+        device: &KfdDevice,
+        va: u64,
+        size: usize,
+        flags: AllocFlags,
+        node_id: u32,
+        drm_fd: RawFd,
+    ) -> Result<Allocation, MemoryError> {
+        let va_size = flags
+            .va_reservation_size(size)
+            .ok_or(MemoryError::RangeUnavailable { addr: va, size })?;
+        self.aperture_for(&flags, node_id)?
+            .reserve_va_at(va, va_size)?;
+        self.back_reserved_va(device, va, size, flags, node_id, drm_fd)
+    }
+
+    /// The aperture an allocation with `flags` on `node_id` takes its VA from.
+    fn aperture_for(
+        &mut self,
+        flags: &AllocFlags,
+        node_id: u32,
+    ) -> Result<&mut Aperture, MemoryError> {
+        Ok(if flags.scratch {
             &mut self
                 .gpu_apertures
                 .get_mut(&node_id)
-                .ok_or_else(|| {
-                    HsaError::General(format!("No scratch aperture found for node {node_id}"))
-                })?
+                .ok_or(MemoryError::UnknownNode(node_id))?
                 .scratch
         } else if flags.lds {
             &mut self
                 .gpu_apertures
                 .get_mut(&node_id)
-                .ok_or_else(|| {
-                    HsaError::General(format!("No LDS aperture found for node {node_id}"))
-                })?
+                .ok_or(MemoryError::UnknownNode(node_id))?
                 .lds
         } else if flags.coherent || flags.uncached || flags.doorbell {
             &mut self.svm_alt_aperture
         } else {
             &mut self.svm_aperture
-        };
-
-        let va_addr = aperture
-            .allocate_va(size, align)
-            .ok_or(HsaError::OutOfMemory)?;
+        })
+    }
 
+    /// Backs the already reserved `[va_addr, va_addr + size)` with a KFD allocation, maps it
+    /// to the node's GPU and, if requested, to the CPU. Releases the VA on failure.
+    fn back_reserved_va(
+        &mut self,
+        device: &KfdDevice,
+        va_addr: u64,
+        size: usize,
+        flags: AllocFlags,
+        node_id: u32,
+        drm_fd: RawFd,
+    ) -> Result<Allocation, MemoryError> {
         let ioc_flags = flags.to_kfd_ioctl_flags();
 
         let gpu_id = *self.node_to_gpu_id.get(&node_id).unwrap_or(&0);
@@ -365,7 +720,13 @@ impl MemoryManager {
             Err(e) => {
                 eprintln!("KFD Alloc Failed: {e:?}");
                 self.free_va_from_flags(va_addr, size, &flags, node_id);
-                return Err(HsaError::Io(e));
+                if flags.vram
+                    && e.raw_os_error() == Some(libc::ENOMEM)
+                    && let Ok(available) = device.get_available_memory(gpu_id)
+                {
+                    return Err(diagnose_vram_failure(size as u64, available));
+                }
+                return Err(MemoryError::KfdAllocFailed(e));
             }
         }
 
@@ -380,17 +741,13 @@ impl MemoryManager {
             eprintln!("KFD Map Memory to GPU failed: {e:?}");
             device.free_memory_of_gpu(args.handle).ok();
             self.free_va_from_flags(va_addr, size, &flags, node_id);
-            return Err(HsaError::Io(e));
+            return Err(MemoryError::MapFailed(e));
         }
 
         let mut cpu_ptr = ptr::null_mut();
 
         if flags.host_access || flags.doorbell {
-            let prot = if flags.read_only {
-                libc::PROT_READ
-            } else {
-                libc::PROT_READ | libc::PROT_WRITE
-            };
+            let prot = flags.mmap_prot();
 
             let mmap_flags = libc::MAP_SHARED | libc::MAP_FIXED;
 
@@ -420,7 +777,7 @@ impl MemoryManager {
                     device.unmap_memory_from_gpu(&mut unmap_args).ok();
                     device.free_memory_of_gpu(args.handle).ok();
                     self.free_va_from_flags(va_addr, size, &flags, node_id);
-                    return Err(HsaError::Io(std::io::Error::last_os_error()));
+                    return Err(MemoryError::MmapFailed(std::io::Error::last_os_error()));
                 }
                 cpu_ptr = ret.cast::<u8>();
             }
@@ -430,7 +787,7 @@ impl MemoryManager {
             .self_weak
             .as_ref()
             .and_then(std::sync::Weak::upgrade)
-            .ok_or_else(|| HsaError::General("MemoryManager has been dropped".into()))?;
+            .ok_or(MemoryError::ManagerDropped)?;
 
         let allocation = Allocation {
             ptr: cpu_ptr,
@@ -443,6 +800,7 @@ impl MemoryManager {
             device: device.clone(),
             manager_handle,
         };
+        self.track(&allocation, vec![gpu_id]);
 
         Ok(allocation)
     }
@@ -456,7 +814,7 @@ impl MemoryManager {
         align: usize,
         node_id: u32,
         drm_fd: RawFd,
-    ) -> HsaResult<Allocation> {
+    ) -> Result<Allocation, MemoryError> {
         let flags = AllocFlags::new()
             .vram()
             .executable()
@@ -473,7 +831,7 @@ impl MemoryManager {
         size: usize,
         node_id: u32,
         drm_fd: RawFd,
-    ) -> HsaResult<Allocation> {
+    ) -> Result<Allocation, MemoryError> {
         let flags = AllocFlags::new().vram();
         self.allocate(device, size, 0, flags, Some(node_id), drm_fd)
     }
@@ -485,147 +843,1255 @@ impl MemoryManager {
         size: usize,
         node_id: u32,
         drm_fd: RawFd,
-    ) -> HsaResult<Allocation> {
+    ) -> Result<Allocation, MemoryError> {
         let flags = AllocFlags::new().gtt();
         self.allocate(device, size, 0, flags, Some(node_id), drm_fd)
     }
 
-    /// Map a doorbell index to a CPU virtual address.
-    pub fn map_doorbell(
+    /// Allocates VRAM that is physically contiguous if possible, degrading when memory is
+    /// short: contiguous VRAM, then ordinary VRAM, then GTT.
+    ///
+    /// Meant for RDMA and scanout buffers. Only out-of-memory failures move on to the next
+    /// tier; the returned [`MemoryTier`] tells which one succeeded.
+    pub fn allocate_contiguous_or_fallback(
+        &mut self,
+        device: &KfdDevice,
+        size: usize,
+        node_id: u32,
+        drm_fd: RawFd,
+    ) -> Result<(Allocation, MemoryTier), MemoryError> {
+        let tiers = [
+            (
+                MemoryTier::ContiguousVram,
+                AllocFlags::new().vram().contiguous(),
+            ),
+            (MemoryTier::Vram, AllocFlags::new().vram()),
+            (MemoryTier::Gtt, AllocFlags::new().gtt()),
+        ];
+
+        let mut last_err = MemoryError::ApertureExhausted;
+        for (tier, flags) in tiers {
+            match self.allocate(device, size, 0, flags, Some(node_id), drm_fd) {
+                Ok(alloc) => return Ok((alloc, tier)),
+                Err(e) if e.is_out_of_memory() => last_err = e,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Registers existing host memory with the GPU (userptr).
+    ///
+    /// The GPU VA is reserved at the same address as `host_ptr`, so the returned
+    /// allocation is usable through the same pointer on both sides. No CPU mapping is
+    /// created: the memory stays owned by the caller and is not unmapped on drop.
+    /// `host_ptr` must be page-aligned; `size` is rounded up to a whole page. The VA gets
+    /// no guard pages, so adjacent host buffers can be registered separately.
+    pub fn register_userptr(
         &mut self,
         device: &KfdDevice,
+        host_ptr: *mut u8,
+        size: usize,
         node_id: u32,
-        gpu_id: u32,
-        doorbell_offset: u64,
-        size: u64,
-    ) -> HsaResult<Allocation> {
-        let size = size as usize;
-        let flags = AllocFlags::new().doorbell();
+        flags: AllocFlags,
+    ) -> Result<Allocation, MemoryError> {
+        let host_addr = host_ptr as u64;
+        if host_ptr.is_null() || !host_addr.is_multiple_of(PAGE_SIZE as u64) {
+            return Err(MemoryError::UnalignedRequest);
+        }
+        let size = size.max(1).next_multiple_of(PAGE_SIZE);
+        let flags = flags.userptr();
 
-        let va_addr = self
-            .svm_alt_aperture
-            .allocate_va(size, 4096)
-            .ok_or(HsaError::OutOfMemory)?;
+        let gpu_id = self
+            .get_gpu_id(node_id)
+            .ok_or(MemoryError::UnknownNode(node_id))?;
 
-        let ioc_flags = KFD_IOC_ALLOC_MEM_FLAGS_DOORBELL
-            | KFD_IOC_ALLOC_MEM_FLAGS_WRITABLE
-            | KFD_IOC_ALLOC_MEM_FLAGS_COHERENT
-            | KFD_IOC_ALLOC_MEM_FLAGS_NO_SUBSTITUTE;
+        self.svm_aperture.reserve_va_unguarded(host_addr, size)?;
 
+        // For userptr BOs KFD takes the host address through `mmap_offset`.
         let mut args = AllocMemoryOfGpuArgs {
-            va_addr,
+            va_addr: host_addr,
             size: size as u64,
             handle: 0,
-            mmap_offset: 0,
+            mmap_offset: host_addr,
             gpu_id,
-            flags: ioc_flags,
+            flags: flags.to_kfd_ioctl_flags(),
         };
 
         if let Err(e) = device.alloc_memory_of_gpu(&mut args) {
-            eprintln!("[ERROR] map_doorbell: KFD Alloc failed: {e:?}");
-            self.svm_alt_aperture.free_va(va_addr, size);
-            return Err(HsaError::from(e));
+            eprintln!("KFD userptr Alloc Failed: {e:?}");
+            self.free_va_from_flags(host_addr, size, &flags, node_id);
+            return Err(MemoryError::KfdAllocFailed(e));
         }
 
-        let cpu_ptr;
-        unsafe {
-            let ret = libc::mmap(
-                va_addr as *mut libc::c_void,
-                size,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_SHARED | libc::MAP_FIXED,
-                device.file.as_raw_fd(),
-                doorbell_offset as libc::off_t,
-            );
+        let mut map_args = MapMemoryToGpuArgs {
+            handle: args.handle,
+            device_ids_array_ptr: &raw const gpu_id as u64,
+            n_devices: 1,
+            n_success: 0,
+        };
 
-            if ret == libc::MAP_FAILED {
-                device.free_memory_of_gpu(args.handle).ok();
-                self.svm_alt_aperture.free_va(va_addr, size);
-                return Err(HsaError::General("mmap failed for doorbell".into()));
-            }
-            cpu_ptr = ret.cast::<u8>();
+        if let Err(e) = device.map_memory_to_gpu(&mut map_args) {
+            eprintln!("KFD Map userptr to GPU failed: {e:?}");
+            device.free_memory_of_gpu(args.handle).ok();
+            self.free_va_from_flags(host_addr, size, &flags, node_id);
+            return Err(MemoryError::MapFailed(e));
         }
 
         let manager_handle = self
             .self_weak
             .as_ref()
             .and_then(std::sync::Weak::upgrade)
-            .ok_or_else(|| HsaError::General("MemoryManager has been dropped".into()))?;
+            .ok_or(MemoryError::ManagerDropped)?;
 
-        Ok(Allocation {
-            ptr: cpu_ptr,
+        let allocation = Allocation {
+            ptr: host_ptr,
             size,
-            gpu_va: va_addr,
+            gpu_va: host_addr,
             handle: args.handle,
-            is_userptr: false,
+            is_userptr: true,
             node_id,
             flags,
             device: device.clone(),
             manager_handle,
-        })
+        };
+        self.track(&allocation, vec![gpu_id]);
+
+        Ok(allocation)
     }
 
-    /// Internal helper: reclaim VA space.
-    /// Public crate-wide so that `Allocation::drop` can call it.
-    pub fn free_va_from_flags(&mut self, addr: u64, size: usize, flags: &AllocFlags, node_id: u32) {
-        if flags.scratch {
-            if let Some(g) = self.gpu_apertures.get_mut(&node_id) {
-                g.scratch.free_va(addr, size);
-            }
-        } else if flags.lds {
-            if let Some(g) = self.gpu_apertures.get_mut(&node_id) {
-                g.lds.free_va(addr, size);
+    /// Maps the allocation behind `handle` to every GPU in `gpu_ids` (e.g. for peer access).
+    ///
+    /// Returns the number of GPUs the handle is mapped to. If KFD stops part way, the GPUs
+    /// mapped so far stay mapped and are reported alongside the ones that failed.
+    pub fn map_to_gpus(
+        &mut self,
+        device: &KfdDevice,
+        handle: u64,
+        gpu_ids: &[u32],
+    ) -> Result<u32, MemoryError> {
+        let mut args = MapMemoryToGpuArgs {
+            handle,
+            device_ids_array_ptr: gpu_ids.as_ptr() as u64,
+            n_devices: gpu_ids.len() as u32,
+            n_success: 0,
+        };
+        let res = device.map_memory_to_gpu(&mut args);
+
+        let done = &gpu_ids[..(args.n_success as usize).min(gpu_ids.len())];
+        if let Some(record) = self.allocations.get_mut(&handle) {
+            for &id in done {
+                if !record.mapped_gpus.contains(&id) {
+                    record.mapped_gpus.push(id);
+                }
             }
-        } else if flags.coherent || flags.uncached || flags.doorbell {
-            self.svm_alt_aperture.free_va(addr, size);
-        } else {
-            self.svm_aperture.free_va(addr, size);
+        }
+
+        match res {
+            Ok(()) => Ok(args.n_success),
+            Err(e) if done.is_empty() => Err(MemoryError::MapFailed(e)),
+            Err(e) => Err(MemoryError::PartialMapping {
+                succeeded: args.n_success,
+                failed: gpu_ids[done.len()..].to_vec(),
+                source: e,
+            }),
         }
     }
-}
 
-impl BuilderMemoryManager for MemoryManager {
-    fn allocate_gpu_memory(
+    /// Unmaps the allocation behind `handle` from every GPU in `gpu_ids`.
+    ///
+    /// Returns the number of GPUs it was unmapped from, with the same partial-failure
+    /// reporting as [`Self::map_to_gpus`].
+    pub fn unmap_from_gpus(
         &mut self,
         device: &KfdDevice,
-        size: usize,
-        align: usize,
-        vram: bool,
-        public: bool,
-        drm_fd: RawFd,
-        node_id: u32,
-    ) -> HsaResult<Allocation> {
-        let mut flags = AllocFlags::new();
-        if vram {
-            flags = flags.vram();
-            if !public {
-                flags = flags.no_substitute();
-            }
-        } else {
-            flags = flags.gtt();
+        handle: u64,
+        gpu_ids: &[u32],
+    ) -> Result<u32, MemoryError> {
+        let mut args = UnmapMemoryFromGpuArgs {
+            handle,
+            device_ids_array_ptr: gpu_ids.as_ptr() as u64,
+            n_devices: gpu_ids.len() as u32,
+            n_success: 0,
+        };
+        let res = device.unmap_memory_from_gpu(&mut args);
+
+        let done = &gpu_ids[..(args.n_success as usize).min(gpu_ids.len())];
+        if let Some(record) = self.allocations.get_mut(&handle) {
+            record.mapped_gpus.retain(|id| !done.contains(id));
         }
-        if public {
-            flags = flags.host_access();
+
+        match res {
+            Ok(()) => Ok(args.n_success),
+            Err(e) if done.is_empty() => Err(MemoryError::MapFailed(e)),
+            Err(e) => Err(MemoryError::PartialMapping {
+                succeeded: args.n_success,
+                failed: gpu_ids[done.len()..].to_vec(),
+                source: e,
+            }),
         }
+    }
 
-        flags = flags.executable().coherent();
+    /// Returns the GPU ids the handle is currently mapped to.
+    #[must_use]
+    pub fn mapped_gpus(&self, handle: u64) -> &[u32] {
+        self.allocations
+            .get(&handle)
+            .map_or(&[], |r| r.mapped_gpus.as_slice())
+    }
 
-        self.allocate(device, size, align, flags, Some(node_id), drm_fd)
+    /// Iterates over the records of all live allocations, e.g. to audit leaks at shutdown.
+    pub fn iter_allocations(&self) -> impl Iterator<Item = &AllocationRecord> {
+        self.allocations.values()
     }
 
-    fn free_gpu_memory(&mut self, _device: &KfdDevice, _alloc: &Allocation) {
-        // No-op: The Allocation struct handles its own cleanup via Drop.
-        // We explicitly do not want to double-free.
+    /// Number of live allocations.
+    #[must_use]
+    pub fn allocation_count(&self) -> usize {
+        self.allocations.len()
     }
 
-    fn map_doorbell(
-        &mut self,
-        device: &KfdDevice,
-        node_id: u32,
-        gpu_id: u32,
-        doorbell_offset: u64,
-        size: u64,
-    ) -> HsaResult<Allocation> {
-        self.map_doorbell(device, node_id, gpu_id, doorbell_offset, size)
+    /// Returns the live allocation whose GPU VA range `[gpu_va, gpu_va + size)` contains
+    /// `va`, e.g. to find the buffer a kernel faulted on.
+    #[must_use]
+    pub fn find_allocation_by_va(&self, va: u64) -> Option<&AllocationRecord> {
+        self.allocations.values().find(|r| r.contains_va(va))
+    }
+
+    /// Returns the free memory KFD reports for the GPU at `node_id`.
+    pub fn available_memory(&self, device: &KfdDevice, node_id: u32) -> Result<u64, MemoryError> {
+        let gpu_id = self
+            .get_gpu_id(node_id)
+            .ok_or(MemoryError::UnknownNode(node_id))?;
+        device
+            .get_available_memory(gpu_id)
+            .map_err(MemoryError::QueryFailed)
+    }
+
+    /// Lowest node id that has a GPU, used to map memory requested for CPU-only nodes.
+    #[must_use]
+    pub fn first_gpu_node(&self) -> Option<u32> {
+        self.node_to_gpu_id.keys().min().copied()
+    }
+
+    /// Sums the size of all live allocations owned by `node_id`.
+    #[must_use]
+    pub fn total_allocated_bytes(&self, node_id: u32) -> u64 {
+        self.allocations
+            .values()
+            .filter(|r| r.node_id == node_id)
+            .map(|r| r.size as u64)
+            .sum()
+    }
+
+    /// Scratch bytes still allocatable on `node_id`, after guard pages and live reservations.
+    ///
+    /// Unlike the span reported by `get_node_memory_properties`, this accounts for the
+    /// allocator's bookkeeping. Returns 0 for nodes without a GPU.
+    #[must_use]
+    pub fn usable_scratch_bytes(&self, node_id: u32) -> u64 {
+        self.gpu_apertures
+            .get(&node_id)
+            .map_or(0, |g| g.scratch.usable_bytes())
+    }
+
+    fn track(&mut self, alloc: &Allocation, mapped_gpus: Vec<u32>) {
+        self.allocations.insert(
+            alloc.handle,
+            AllocationRecord {
+                gpu_va: alloc.gpu_va,
+                cpu_addr: alloc.ptr as u64,
+                size: alloc.size,
+                handle: alloc.handle,
+                node_id: alloc.node_id,
+                flags: alloc.flags,
+                is_userptr: alloc.is_userptr,
+                mapped_gpus,
+            },
+        );
+    }
+
+    /// Stops tracking `handle`, returning its record.
+    pub(crate) fn untrack(&mut self, handle: u64) -> Option<AllocationRecord> {
+        self.allocations.remove(&handle)
+    }
+
+    /// Exports the allocation behind `handle` as a DMA-buf and returns its fd.
+    ///
+    /// The fd is owned by the caller and must be closed once handed to the importer.
+    /// Fails up front on drivers older than KFD 1.12, which lack the ioctl.
+    pub fn export_allocation_as_dmabuf(&self, device: &KfdDevice, handle: u64) -> HsaResult<RawFd> {
+        let (major, minor) = KfdFeature::ExportDmabuf.min_version();
+        device.require_version(major, minor)?;
+
+        let mut args = ExportDmabufArgs {
+            handle,
+            flags: libc::O_CLOEXEC as u32,
+            dmabuf_fd: 0,
+        };
+        device.export_dmabuf(&mut args)?;
+        Ok(args.dmabuf_fd as RawFd)
+    }
+
+    /// Imports a DMA-buf (e.g. exported by a Vulkan/GL render node) into this process.
+    ///
+    /// A VA range is reserved from the SVM aperture and the buffer is mapped to the GPU of
+    /// `node_id`. The returned allocation has no CPU mapping. The caller keeps ownership
+    /// of `fd`; KFD holds its own reference to the buffer.
+    pub fn import_dmabuf(
+        &mut self,
+        device: &KfdDevice,
+        fd: RawFd,
+        node_id: u32,
+        _drm_fd: RawFd,
+    ) -> Result<Allocation, MemoryError> {
+        let gpu_id = self
+            .get_gpu_id(node_id)
+            .ok_or(MemoryError::UnknownNode(node_id))?;
+
+        let mut info = GetDmabufInfoArgs {
+            dmabuf_fd: fd as u32,
+            ..Default::default()
+        };
+        device
+            .get_dmabuf_info(&mut info)
+            .map_err(MemoryError::KfdAllocFailed)?;
+
+        let size = info.size as usize;
+        let flags = AllocFlags::new();
+
+        let va_addr = self
+            .svm_aperture
+            .allocate_va(size, PAGE_SIZE)
+            .ok_or(MemoryError::ApertureExhausted)?;
+
+        let mut args = ImportDmabufArgs {
+            va_addr,
+            handle: 0,
+            gpu_id,
+            dmabuf_fd: fd as u32,
+        };
+
+        if let Err(e) = device.import_dmabuf(&mut args) {
+            eprintln!("KFD DMA-buf import failed: {e:?}");
+            self.free_va_from_flags(va_addr, size, &flags, node_id);
+            return Err(MemoryError::KfdAllocFailed(e));
+        }
+
+        let mut map_args = MapMemoryToGpuArgs {
+            handle: args.handle,
+            device_ids_array_ptr: &raw const gpu_id as u64,
+            n_devices: 1,
+            n_success: 0,
+        };
+
+        if let Err(e) = device.map_memory_to_gpu(&mut map_args) {
+            eprintln!("KFD Map DMA-buf to GPU failed: {e:?}");
+            device.free_memory_of_gpu(args.handle).ok();
+            self.free_va_from_flags(va_addr, size, &flags, node_id);
+            return Err(MemoryError::MapFailed(e));
+        }
+
+        let manager_handle = self
+            .self_weak
+            .as_ref()
+            .and_then(std::sync::Weak::upgrade)
+            .ok_or(MemoryError::ManagerDropped)?;
+
+        let allocation = Allocation {
+            ptr: ptr::null_mut(),
+            size,
+            gpu_va: va_addr,
+            handle: args.handle,
+            is_userptr: false,
+            node_id,
+            flags,
+            device: device.clone(),
+            manager_handle,
+        };
+        self.track(&allocation, vec![gpu_id]);
+
+        Ok(allocation)
+    }
+
+    /// Exports the allocation behind `handle` for import by another process.
+    ///
+    /// # Errors
+    /// Fails with `UnknownHandle` if the manager does not track `handle`, or `IpcFailed` if
+    /// KFD refuses the export.
+    pub fn export_ipc(
+        &self,
+        device: &KfdDevice,
+        handle: u64,
+    ) -> Result<IpcShareHandle, MemoryError> {
+        let record = self
+            .allocations
+            .get(&handle)
+            .ok_or(MemoryError::UnknownHandle(handle))?;
+        let gpu_id = self
+            .get_gpu_id(record.node_id)
+            .ok_or(MemoryError::UnknownNode(record.node_id))?;
+
+        let mut args = IpcExportHandleArgs {
+            handle,
+            gpu_id,
+            ..Default::default()
+        };
+        device
+            .ipc_export_handle(&mut args)
+            .map_err(MemoryError::IpcFailed)?;
+
+        Ok(IpcShareHandle {
+            share_handle: args.share_handle,
+            size: record.size as u64,
+        })
+    }
+
+    /// Imports a buffer exported by [`Self::export_ipc`] in another process.
+    ///
+    /// A VA range is reserved from the SVM aperture, the buffer is mapped to the GPU of
+    /// `node_id` and to the CPU at the same address.
+    ///
+    /// # Errors
+    /// Fails if the VA cannot be reserved, or if the import, GPU mapping or CPU mapping
+    /// fails. Every step already taken is rolled back.
+    pub fn import_ipc(
+        &mut self,
+        device: &KfdDevice,
+        share: &IpcShareHandle,
+        node_id: u32,
+        drm_fd: RawFd,
+    ) -> Result<Allocation, MemoryError> {
+        let gpu_id = self
+            .get_gpu_id(node_id)
+            .ok_or(MemoryError::UnknownNode(node_id))?;
+
+        let size = share.size as usize;
+        let flags = AllocFlags::new().host_access();
+
+        let va_addr = self
+            .svm_aperture
+            .allocate_va(size, PAGE_SIZE)
+            .ok_or(MemoryError::ApertureExhausted)?;
+
+        let mut args = IpcImportHandleArgs {
+            va_addr,
+            share_handle: share.share_handle,
+            gpu_id,
+            ..Default::default()
+        };
+        if let Err(e) = device.ipc_import_handle(&mut args) {
+            eprintln!("KFD IPC import failed: {e:?}");
+            self.free_va_from_flags(va_addr, size, &flags, node_id);
+            return Err(MemoryError::IpcFailed(e));
+        }
+
+        let mut map_args = MapMemoryToGpuArgs {
+            handle: args.handle,
+            device_ids_array_ptr: &raw const gpu_id as u64,
+            n_devices: 1,
+            n_success: 0,
+        };
+        if let Err(e) = device.map_memory_to_gpu(&mut map_args) {
+            eprintln!("KFD Map IPC buffer to GPU failed: {e:?}");
+            device.free_memory_of_gpu(args.handle).ok();
+            self.free_va_from_flags(va_addr, size, &flags, node_id);
+            return Err(MemoryError::MapFailed(e));
+        }
+
+        let cpu_ptr = unsafe {
+            libc::mmap(
+                va_addr as *mut libc::c_void,
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_FIXED,
+                drm_fd,
+                args.mmap_offset as libc::off_t,
+            )
+        };
+        if cpu_ptr == libc::MAP_FAILED {
+            let err = std::io::Error::last_os_error();
+            let mut unmap_args = UnmapMemoryFromGpuArgs {
+                handle: args.handle,
+                device_ids_array_ptr: &raw const gpu_id as u64,
+                n_devices: 1,
+                n_success: 0,
+            };
+            device.unmap_memory_from_gpu(&mut unmap_args).ok();
+            device.free_memory_of_gpu(args.handle).ok();
+            self.free_va_from_flags(va_addr, size, &flags, node_id);
+            return Err(MemoryError::MmapFailed(err));
+        }
+
+        let manager_handle = self
+            .self_weak
+            .as_ref()
+            .and_then(std::sync::Weak::upgrade)
+            .ok_or(MemoryError::ManagerDropped)?;
+
+        let allocation = Allocation {
+            ptr: cpu_ptr.cast::<u8>(),
+            size,
+            gpu_va: va_addr,
+            handle: args.handle,
+            is_userptr: false,
+            node_id,
+            flags,
+            device: device.clone(),
+            manager_handle,
+        };
+        self.track(&allocation, vec![gpu_id]);
+
+        Ok(allocation)
+    }
+
+    /// Map a doorbell index to a CPU virtual address.
+    pub fn map_doorbell(
+        &mut self,
+        device: &KfdDevice,
+        node_id: u32,
+        gpu_id: u32,
+        doorbell_offset: u64,
+        size: u64,
+    ) -> Result<Allocation, MemoryError> {
+        let size = size as usize;
+        let flags = AllocFlags::new().doorbell();
+
+        let va_addr = self
+            .svm_alt_aperture
+            .allocate_va(size, 4096)
+            .ok_or(MemoryError::ApertureExhausted)?;
+
+        let ioc_flags = KFD_IOC_ALLOC_MEM_FLAGS_DOORBELL
+            | KFD_IOC_ALLOC_MEM_FLAGS_WRITABLE
+            | KFD_IOC_ALLOC_MEM_FLAGS_COHERENT
+            | KFD_IOC_ALLOC_MEM_FLAGS_NO_SUBSTITUTE;
+
+        let mut args = AllocMemoryOfGpuArgs {
+            va_addr,
+            size: size as u64,
+            handle: 0,
+            mmap_offset: 0,
+            gpu_id,
+            flags: ioc_flags,
+        };
+
+        if let Err(e) = device.alloc_memory_of_gpu(&mut args) {
+            eprintln!("[ERROR] map_doorbell: KFD Alloc failed: {e:?}");
+            self.svm_alt_aperture.free_va(va_addr, size);
+            return Err(MemoryError::KfdAllocFailed(e));
+        }
+
+        let cpu_ptr;
+        unsafe {
+            let ret = libc::mmap(
+                va_addr as *mut libc::c_void,
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_FIXED,
+                device.file.as_raw_fd(),
+                doorbell_offset as libc::off_t,
+            );
+
+            if ret == libc::MAP_FAILED {
+                device.free_memory_of_gpu(args.handle).ok();
+                self.svm_alt_aperture.free_va(va_addr, size);
+                return Err(MemoryError::MmapFailed(std::io::Error::last_os_error()));
+            }
+            cpu_ptr = ret.cast::<u8>();
+        }
+
+        let manager_handle = self
+            .self_weak
+            .as_ref()
+            .and_then(std::sync::Weak::upgrade)
+            .ok_or(MemoryError::ManagerDropped)?;
+
+        let allocation = Allocation {
+            ptr: cpu_ptr,
+            size,
+            gpu_va: va_addr,
+            handle: args.handle,
+            is_userptr: false,
+            node_id,
+            flags,
+            device: device.clone(),
+            manager_handle,
+        };
+        self.track(&allocation, Vec::new());
+
+        Ok(allocation)
+    }
+
+    /// Maps a doorbell page, reusing the existing mapping if another queue already holds it.
+    ///
+    /// KFD hands out one doorbell page per process and GPU, so every queue on a GPU lands on
+    /// the same page. The page is unmapped when the last `Arc` to it is dropped.
+    pub fn map_doorbell_shared(
+        &mut self,
+        device: &KfdDevice,
+        node_id: u32,
+        gpu_id: u32,
+        doorbell_offset: u64,
+        size: u64,
+    ) -> Result<Arc<Allocation>, MemoryError> {
+        let key = (gpu_id, doorbell_offset);
+        if let Some(page) = self.doorbell_pages.get(&key).and_then(Weak::upgrade) {
+            return Ok(page);
+        }
+
+        let page = Arc::new(self.map_doorbell(device, node_id, gpu_id, doorbell_offset, size)?);
+        self.doorbell_pages.retain(|_, p| p.strong_count() > 0);
+        self.doorbell_pages.insert(key, Arc::downgrade(&page));
+        Ok(page)
+    }
+
+    /// Maps the MMIO remap (HDP flush) page of every GPU, as libhsakmt does when it opens
+    /// KFD, and publishes its aperture for the topology's `MMIO_REMAP` bank.
+    ///
+    /// GPUs whose kernel refuses the allocation (e.g. pages larger than 4 KiB) get no bank.
+    fn map_mmio_remap_pages(&mut self, device: &KfdDevice) {
+        let mut gpus: Vec<(u32, u32)> = self
+            .node_to_gpu_id
+            .iter()
+            .map(|(&node, &gpu)| (node, gpu))
+            .collect();
+        gpus.sort_unstable();
+
+        for (node_id, gpu_id) in gpus {
+            if let Ok(range) = self.map_mmio_remap(device, node_id, gpu_id) {
+                topology::publish_mmio_aperture(gpu_id, Some(range));
+            }
+        }
+    }
+
+    /// Allocates the MMIO remap page of `gpu_id`, maps it on the CPU and the GPU and tracks
+    /// it like any other allocation.
+    fn map_mmio_remap(
+        &mut self,
+        device: &KfdDevice,
+        node_id: u32,
+        gpu_id: u32,
+    ) -> Result<VaRange, MemoryError> {
+        let size =
+            usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(PAGE_SIZE);
+        let flags = AllocFlags::new().mmio_remap().coherent();
+
+        let va_addr = self
+            .svm_alt_aperture
+            .allocate_va(size, size)
+            .ok_or(MemoryError::ApertureExhausted)?;
+
+        let mut args = AllocMemoryOfGpuArgs {
+            va_addr,
+            size: size as u64,
+            handle: 0,
+            mmap_offset: 0,
+            gpu_id,
+            flags: flags.to_kfd_ioctl_flags(),
+        };
+
+        if let Err(e) = device.alloc_memory_of_gpu(&mut args) {
+            self.svm_alt_aperture.free_va(va_addr, size);
+            return Err(MemoryError::KfdAllocFailed(e));
+        }
+
+        let ret = unsafe {
+            libc::mmap(
+                va_addr as *mut libc::c_void,
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_FIXED,
+                device.file.as_raw_fd(),
+                args.mmap_offset as libc::off_t,
+            )
+        };
+        if ret == libc::MAP_FAILED {
+            let err = std::io::Error::last_os_error();
+            device.free_memory_of_gpu(args.handle).ok();
+            self.svm_alt_aperture.free_va(va_addr, size);
+            return Err(MemoryError::MmapFailed(err));
+        }
+
+        self.allocations.insert(
+            args.handle,
+            AllocationRecord {
+                gpu_va: va_addr,
+                cpu_addr: ret as u64,
+                size,
+                handle: args.handle,
+                node_id,
+                flags,
+                is_userptr: false,
+                mapped_gpus: Vec::new(),
+            },
+        );
+
+        if let Err(e) = self.map_to_gpus(device, args.handle, &[gpu_id]) {
+            self.free_memory(device, args.handle).ok();
+            return Err(e);
+        }
+        self.mmio_remap_pages.insert(gpu_id, args.handle);
+
+        Ok(VaRange::new(va_addr, va_addr + size as u64 - 1))
+    }
+
+    /// Number of doorbell pages currently mapped through [`Self::map_doorbell_shared`].
+    #[must_use]
+    pub fn mapped_doorbell_pages(&self) -> usize {
+        self.doorbell_pages
+            .values()
+            .filter(|p| p.strong_count() > 0)
+            .count()
+    }
+
+    /// Sets the preferred location of an SVM range to `preferred_node` and migrates it there.
+    ///
+    /// Issues `KFD_IOCTL_SVM_ATTR_PREFERRED_LOC` and `KFD_IOCTL_SVM_ATTR_PREFETCH_LOC`, both
+    /// with the node's gpu id, or `KFD_IOCTL_SVM_LOCATION_SYSMEM` for CPU nodes.
+    ///
+    /// # Errors
+    /// Fails with `UnknownNode` if `preferred_node` is not in the topology, if the range is
+    /// not page-aligned, or if KFD rejects the attributes.
+    pub fn svm_prefetch(
+        &self,
+        device: &KfdDevice,
+        start: u64,
+        size: u64,
+        preferred_node: u32,
+    ) -> Result<(), MemoryError> {
+        let location = match self.get_gpu_id(preferred_node) {
+            Some(gpu_id) => gpu_id,
+            None if preferred_node < self.num_nodes => KFD_IOCTL_SVM_LOCATION_SYSMEM,
+            None => return Err(MemoryError::UnknownNode(preferred_node)),
+        };
+        let attrs = [
+            SvmAttribute {
+                type_: KFD_IOCTL_SVM_ATTR_PREFERRED_LOC,
+                value: location,
+            },
+            SvmAttribute {
+                type_: KFD_IOCTL_SVM_ATTR_PREFETCH_LOC,
+                value: location,
+            },
+        ];
+        Self::svm_set_attrs(device, start, size, &attrs)
+    }
+
+    /// Maps an SVM range for access by the GPUs at `nodes`.
+    ///
+    /// Issues one `KFD_IOCTL_SVM_ATTR_ACCESS` attribute per GPU, valued with its gpu id.
+    ///
+    /// # Errors
+    /// Fails with `UnknownNode` for nodes without a GPU, if the range is not page-aligned,
+    /// or if KFD rejects the attributes.
+    pub fn svm_set_accessed_by(
+        &self,
+        device: &KfdDevice,
+        start: u64,
+        size: u64,
+        nodes: &[u32],
+    ) -> Result<(), MemoryError> {
+        let attrs = nodes
+            .iter()
+            .map(|&node_id| {
+                let gpu_id = self
+                    .get_gpu_id(node_id)
+                    .ok_or(MemoryError::UnknownNode(node_id))?;
+                Ok(SvmAttribute {
+                    type_: KFD_IOCTL_SVM_ATTR_ACCESS,
+                    value: gpu_id,
+                })
+            })
+            .collect::<Result<Vec<_>, MemoryError>>()?;
+        Self::svm_set_attrs(device, start, size, &attrs)
+    }
+
+    /// Issues `KFD_IOCTL_SVM_OP_SET_ATTR` with `attrs` laid out right after the `SvmArgs`
+    /// header, as the ioctl's trailing flexible array expects.
+    fn svm_set_attrs(
+        device: &KfdDevice,
+        start: u64,
+        size: u64,
+        attrs: &[SvmAttribute],
+    ) -> Result<(), MemoryError> {
+        if !start.is_multiple_of(PAGE_SIZE as u64) || !size.is_multiple_of(PAGE_SIZE as u64) {
+            return Err(MemoryError::UnalignedRequest);
+        }
+
+        let mut buf = SvmArgs::buffer(start, size, KFD_IOCTL_SVM_OP_SET_ATTR, attrs);
+        let args = buf.as_mut_ptr().cast::<SvmArgs>();
+        unsafe { device.svm(&mut *args).map_err(MemoryError::SvmFailed) }
+    }
+
+    /// Reserves `size` bytes of contiguous SVM address space and returns its base.
+    ///
+    /// This is the first half of a two-phase model for runtimes that sub-allocate one large
+    /// range themselves:
+    ///
+    /// 1. `reserve_svm_range` carves the VA out of the SVM aperture. Nothing is allocated in
+    ///    KFD, so no memory is consumed and the range is not accessible yet.
+    /// 2. [`Self::commit_svm_range`] backs the whole range with one KFD allocation. Dropping
+    ///    the returned `Allocation` frees the memory but keeps the VA reserved, so the range
+    ///    can be committed again.
+    ///
+    /// [`Self::release_svm_range`] hands the VA back once no commit of it is alive.
+    ///
+    /// # Errors
+    /// Fails with `ApertureExhausted` if no free range is large enough.
+    pub fn reserve_svm_range(&mut self, size: usize, align: usize) -> Result<u64, MemoryError> {
+        let size = size.max(1).next_multiple_of(PAGE_SIZE);
+        let base = self
+            .svm_aperture
+            .allocate_va(size, align.max(SVM_DEFAULT_ALIGN))
+            .ok_or(MemoryError::ApertureExhausted)?;
+        self.svm_reservations.insert(base, size);
+        Ok(base)
+    }
+
+    /// Backs the range reserved at `base` with memory on `node_id` and maps it.
+    ///
+    /// The aperture-selecting flags (`scratch`, `lds`, `coherent`, ...) have no effect on the
+    /// address, which was fixed by the reservation.
+    ///
+    /// # Errors
+    /// Fails with `RangeUnavailable` if `base` is not a reservation or is already committed,
+    /// and otherwise like [`Self::allocate`].
+    pub fn commit_svm_range(
+        &mut self,
+        device: &KfdDevice,
+        base: u64,
+        flags: AllocFlags,
+        node_id: u32,
+        drm_fd: RawFd,
+    ) -> Result<Allocation, MemoryError> {
+        let size = *self
+            .svm_reservations
+            .get(&base)
+            .ok_or(MemoryError::RangeUnavailable {
+                addr: base,
+                size: 0,
+            })?;
+        // A second KFD allocation at the same VA would alias the first one's mapping.
+        if self.is_committed(base) {
+            return Err(MemoryError::RangeUnavailable { addr: base, size });
+        }
+        self.back_reserved_va(device, base, size, flags, node_id, drm_fd)
+    }
+
+    /// Returns a reserved range to the SVM aperture.
+    ///
+    /// Any `Allocation` committed on the range must have been dropped first.
+    ///
+    /// # Errors
+    /// Fails with `RangeUnavailable` if `base`/`size` do not match a reservation or the
+    /// range is still committed.
+    pub fn release_svm_range(&mut self, base: u64, size: usize) -> Result<(), MemoryError> {
+        let size = size.max(1).next_multiple_of(PAGE_SIZE);
+        if self.svm_reservations.get(&base) != Some(&size) || self.is_committed(base) {
+            return Err(MemoryError::RangeUnavailable { addr: base, size });
+        }
+        self.svm_reservations.remove(&base);
+        self.svm_aperture.free_va(base, size);
+        Ok(())
+    }
+
+    /// Whether a live allocation backs the reservation at `base`.
+    fn is_committed(&self, base: u64) -> bool {
+        self.allocations.values().any(|r| r.gpu_va == base)
+    }
+
+    /// Frees the allocation behind `handle`: CPU mapping, GPU mappings, the KFD buffer and
+    /// its VA range.
+    ///
+    /// Every step runs even if an earlier one fails, so a failed KFD free does not also
+    /// leak the VA range. The `Allocation` owning `handle` becomes inert; dropping it later
+    /// does nothing.
+    ///
+    /// # Errors
+    /// Returns `UnknownHandle` if `handle` is not tracked (e.g. already freed), otherwise the
+    /// first of `MunmapFailed` or `FreeFailed`.
+    pub fn free_memory(&mut self, device: &KfdDevice, handle: u64) -> Result<(), MemoryError> {
+        let record = self
+            .untrack(handle)
+            .ok_or(MemoryError::UnknownHandle(handle))?;
+        let mut result = Ok(());
+
+        // Userptr memory belongs to the caller.
+        if record.cpu_addr != 0
+            && !record.is_userptr
+            && unsafe { libc::munmap(record.cpu_addr as *mut libc::c_void, record.size) } != 0
+        {
+            result = Err(MemoryError::MunmapFailed(std::io::Error::last_os_error()));
+        }
+
+        // The buffer must be unmapped from every GPU before KFD lets go of it. Errors are
+        // ignored (e.g. already unmapped), the free below reports anything that matters.
+        if !record.mapped_gpus.is_empty() {
+            let mut unmap_args = UnmapMemoryFromGpuArgs {
+                handle,
+                device_ids_array_ptr: record.mapped_gpus.as_ptr() as u64,
+                n_devices: record.mapped_gpus.len() as u32,
+                n_success: 0,
+            };
+            let _ = device.unmap_memory_from_gpu(&mut unmap_args);
+        }
+
+        if let Err(e) = device.free_memory_of_gpu(handle) {
+            result = result.and(Err(MemoryError::FreeFailed(e)));
+        }
+
+        self.free_va_from_flags(record.gpu_va, record.size, &record.flags, record.node_id);
+        result
+    }
+
+    /// Allocates a `size`-byte GTT buffer and registers it with KFD as the scratch backing
+    /// memory of `node_id`, returning its VA.
+    ///
+    /// Kernels that spill to scratch fault until this is done. The manager keeps the buffer
+    /// until [`Self::release_scratch`] or shutdown.
+    ///
+    /// # Errors
+    /// Fails with `RangeUnavailable` if the node already has scratch backing, `UnknownNode`
+    /// for nodes without a GPU, or with the allocation or ioctl error.
+    pub fn setup_scratch(
+        &mut self,
+        device: &KfdDevice,
+        node_id: u32,
+        size: usize,
+        drm_fd: RawFd,
+    ) -> Result<u64, MemoryError> {
+        if let Some(existing) = self.scratch_backing.get(&node_id) {
+            return Err(MemoryError::RangeUnavailable {
+                addr: existing.va,
+                size: existing.size,
+            });
+        }
+        let gpu_id = self
+            .get_gpu_id(node_id)
+            .ok_or(MemoryError::UnknownNode(node_id))?;
+
+        let alloc = self.allocate_gtt(device, size, node_id, drm_fd)?;
+        let backing = ScratchBacking {
+            va: alloc.gpu_va,
+            size: alloc.size,
+            handle: alloc.handle,
+            gpu_id,
+        };
+        // The manager tracks the buffer by handle; dropping `alloc` would relock the manager.
+        alloc.disarm();
+
+        let mut args = backing.ioctl_args();
+        if let Err(e) = device.set_scratch_backing_va(&mut args) {
+            eprintln!("KFD SetScratchBackingVa failed: {e:?}");
+            self.free_memory(device, backing.handle).ok();
+            return Err(MemoryError::ScratchSetupFailed(e));
+        }
+
+        self.scratch_backing.insert(node_id, backing);
+        Ok(backing.va)
+    }
+
+    /// The scratch backing buffer of `node_id` set up by [`Self::setup_scratch`], if any.
+    #[must_use]
+    pub fn scratch_backing(&self, node_id: u32) -> Option<ScratchBacking> {
+        self.scratch_backing.get(&node_id).copied()
+    }
+
+    /// Frees the scratch backing buffer of `node_id` set up by [`Self::setup_scratch`].
+    ///
+    /// Returns `false` if the node had none.
+    ///
+    /// # Errors
+    /// Fails if the buffer cannot be freed.
+    pub fn release_scratch(&mut self, node_id: u32) -> Result<bool, MemoryError> {
+        let Some(backing) = self.scratch_backing.remove(&node_id) else {
+            return Ok(false);
+        };
+        let device = self.device.clone();
+        self.free_memory(&device, backing.handle)?;
+        Ok(true)
+    }
+
+    /// Frees the memory the manager owns itself: the scratch backing buffers and the MMIO
+    /// remap pages, whose topology banks are unpublished.
+    ///
+    /// Memory handed out as an `Allocation` is left alone and stays usable until that
+    /// `Allocation` is dropped. Runs automatically when the manager is dropped.
+    ///
+    /// # Errors
+    /// Every buffer is attempted; the first failure is returned.
+    pub fn shutdown(&mut self) -> Result<(), MemoryError> {
+        let device = self.device.clone();
+        let mut result = Ok(());
+
+        let mut handles: Vec<u64> = self
+            .scratch_backing
+            .drain()
+            .map(|(_, b)| b.handle)
+            .collect();
+        for (gpu_id, handle) in self.mmio_remap_pages.drain() {
+            topology::publish_mmio_aperture(gpu_id, None);
+            handles.push(handle);
+        }
+        for handle in handles {
+            result = result.and(self.free_memory(&device, handle));
+        }
+        result
+    }
+
+    /// Internal helper: reclaim VA space.
+    /// Public crate-wide so that `Allocation::drop` can call it.
+    pub fn free_va_from_flags(&mut self, addr: u64, size: usize, flags: &AllocFlags, node_id: u32) {
+        // Commits of a reserved range leave the VA to `release_svm_range`.
+        if self.svm_reservations.contains_key(&addr) {
+            return;
+        }
+        let size = flags.va_reservation_size(size).unwrap_or(size);
+        if flags.userptr {
+            self.svm_aperture.free_va(addr, size);
+        } else if flags.scratch {
+            if let Some(g) = self.gpu_apertures.get_mut(&node_id) {
+                g.scratch.free_va(addr, size);
+            }
+        } else if flags.lds {
+            if let Some(g) = self.gpu_apertures.get_mut(&node_id) {
+                g.lds.free_va(addr, size);
+            }
+        } else if flags.coherent || flags.uncached || flags.doorbell {
+            self.svm_alt_aperture.free_va(addr, size);
+        } else {
+            self.svm_aperture.free_va(addr, size);
+        }
+    }
+}
+
+impl Drop for MemoryManager {
+    /// Every `Allocation` holds the manager, so this only runs once none is left: whatever
+    /// is still tracked has no owner and is freed with the manager's own buffers.
+    fn drop(&mut self) {
+        let mut result = self.shutdown();
+        let device = self.device.clone();
+        let handles: Vec<u64> = self.allocations.keys().copied().collect();
+        for handle in handles {
+            result = result.and(self.free_memory(&device, handle));
+        }
+        if let Err(e) = result {
+            eprintln!("[MemoryManager::drop] Failed to release tracked allocations: {e}");
+        }
+    }
+}
+
+/// Classifies a VRAM allocation that KFD rejected with ENOMEM.
+///
+/// If less than `requested` is left the pool is exhausted; otherwise the memory exists
+/// but could not be handed out in one piece.
+const fn diagnose_vram_failure(requested: u64, available: u64) -> MemoryError {
+    if available < requested {
+        MemoryError::VramExhausted {
+            requested,
+            available,
+        }
+    } else {
+        MemoryError::VramFragmented {
+            requested,
+            available,
+        }
+    }
+}
+
+impl BuilderMemoryManager for MemoryManager {
+    fn allocate_gpu_memory(
+        &mut self,
+        device: &KfdDevice,
+        size: usize,
+        align: usize,
+        flags: AllocFlags,
+        drm_fd: RawFd,
+        node_id: u32,
+    ) -> Result<Allocation, MemoryError> {
+        self.allocate(device, size, align, flags, Some(node_id), drm_fd)
+    }
+
+    fn free_gpu_memory(&mut self, _device: &KfdDevice, _alloc: &Allocation) {
+        // No-op: The Allocation struct handles its own cleanup via Drop.
+        // We explicitly do not want to double-free.
+    }
+
+    fn map_doorbell(
+        &mut self,
+        device: &KfdDevice,
+        node_id: u32,
+        gpu_id: u32,
+        doorbell_offset: u64,
+        size: u64,
+    ) -> Result<Arc<Allocation>, MemoryError> {
+        self.map_doorbell_shared(device, node_id, gpu_id, doorbell_offset, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kfd::ioctl::AMDKFD_IOC_ALLOC_MEMORY_OF_GPU;
+    use crate::kfd::mock::Mock;
+
+    const GPU_ID: u32 = 0x5347;
+
+    /// A manager for a CPU node and one GPU node on `mock`.
+    fn manager(mock: &Mock) -> ArcManager {
+        let gpu = HsaNodeProperties {
+            kfd_gpu_id: GPU_ID,
+            ..HsaNodeProperties::default()
+        };
+        MemoryManager::new(&mock.device, &[HsaNodeProperties::default(), gpu]).unwrap()
+    }
+
+    #[test]
+    fn shutdown_frees_only_memory_without_an_allocation() {
+        let mock = Mock::with_gpu(GPU_ID);
+        let mgr = manager(&mock);
+        let mmio = *mock.state().buffers.keys().next().unwrap();
+        assert!(topology::mmio_aperture(GPU_ID).is_some());
+
+        let mut guard = mgr.lock().unwrap();
+        guard
+            .setup_scratch(&mock.device, 1, 1 << 16, mock.drm_fd())
+            .unwrap();
+        let scratch = guard.scratch_backing(1).unwrap().handle;
+        let a = guard
+            .allocate_gtt(&mock.device, 4096, 1, mock.drm_fd())
+            .unwrap();
+
+        guard.shutdown().unwrap();
+        drop(guard);
+        assert_eq!(mock.state().freed, [scratch, mmio]);
+        assert!(topology::mmio_aperture(GPU_ID).is_none());
+
+        assert!(mock.state().buffers.contains_key(&a.handle));
+        unsafe {
+            a.as_mut_ptr().write_bytes(0xa5, a.size);
+            assert_eq!(*a.as_mut_ptr().add(a.size - 1), 0xa5);
+        }
+
+        let handle = a.handle;
+        drop(a);
+        assert_eq!(mock.state().freed, [scratch, mmio, handle]);
+        assert!(mock.state().buffers.is_empty());
+    }
+
+    #[test]
+    fn adjacent_host_pages_register_separately() {
+        let mock = Mock::with_gpu(GPU_ID);
+        let mgr = manager(&mock);
+        let host = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                2 * PAGE_SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(host, libc::MAP_FAILED);
+        let host = host.cast::<u8>();
+
+        let mut guard = mgr.lock().unwrap();
+        let flags = AllocFlags::new();
+        let low = guard.register_userptr(&mock.device, host, PAGE_SIZE, 1, flags);
+        let high = unsafe { host.add(PAGE_SIZE) };
+        let high = guard.register_userptr(&mock.device, high, PAGE_SIZE, 1, flags);
+        drop(guard);
+
+        let (low, high) = (low.unwrap(), high.unwrap());
+        assert_eq!(low.gpu_va + PAGE_SIZE as u64, high.gpu_va);
+        assert_eq!(mock.state().buffers[&low.handle].mmap_offset, low.gpu_va);
+
+        drop((low, high));
+        let reserved = mgr.lock().unwrap().svm_aperture.iter_reserved().count();
+        assert_eq!(reserved, 0);
+        unsafe { libc::munmap(host.cast(), 2 * PAGE_SIZE) };
+    }
+
+    /// Fails VRAM allocations with ENOMEM, contiguous ones only unless `all_vram`.
+    fn vram_enomem(args: &AllocMemoryOfGpuArgs, all_vram: bool) -> Option<i32> {
+        let vram = args.flags & KFD_IOC_ALLOC_MEM_FLAGS_VRAM != 0;
+        let contiguous = args.flags & KFD_IOC_ALLOC_MEM_FLAGS_CONTIGUOUS_BEST_EFFORT != 0;
+        (vram && (contiguous || all_vram)).then_some(libc::ENOMEM)
+    }
+
+    fn allocate_with_fallback(
+        fail_alloc: fn(&AllocMemoryOfGpuArgs) -> Option<i32>,
+    ) -> (Result<MemoryTier, MemoryError>, usize) {
+        let mock = Mock::with_gpu(GPU_ID);
+        let mgr = manager(&mock);
+        mock.state().fail_alloc = Some(fail_alloc);
+        let before = mock.state().count(AMDKFD_IOC_ALLOC_MEMORY_OF_GPU);
+
+        let result = mgr.lock().unwrap().allocate_contiguous_or_fallback(
+            &mock.device,
+            1 << 20,
+            1,
+            mock.drm_fd(),
+        );
+        // Dropping the allocation locks the manager, so only after the guard is gone.
+        let result = result.map(|(_, tier)| tier);
+        let attempts = mock.state().count(AMDKFD_IOC_ALLOC_MEMORY_OF_GPU) - before;
+        (result, attempts)
+    }
+
+    #[test]
+    fn contiguous_vram_is_tried_first() {
+        let (tier, attempts) = allocate_with_fallback(|_| None);
+        assert_eq!(tier.unwrap(), MemoryTier::ContiguousVram);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn short_contiguous_vram_falls_back_to_vram() {
+        let (tier, attempts) = allocate_with_fallback(|args| vram_enomem(args, false));
+        assert_eq!(tier.unwrap(), MemoryTier::Vram);
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn short_vram_falls_back_to_gtt() {
+        let (tier, attempts) = allocate_with_fallback(|args| vram_enomem(args, true));
+        assert_eq!(tier.unwrap(), MemoryTier::Gtt);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn errors_other_than_out_of_memory_do_not_fall_back() {
+        let (tier, attempts) = allocate_with_fallback(|_| Some(libc::EINVAL));
+        assert!(matches!(tier, Err(MemoryError::KfdAllocFailed(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn vram_failure_below_request_is_exhaustion() {
+        let err = diagnose_vram_failure(8 << 20, 4 << 20);
+        assert!(matches!(
+            err,
+            MemoryError::VramExhausted {
+                requested: 0x80_0000,
+                available: 0x40_0000,
+            }
+        ));
+        assert!(err.is_out_of_memory());
+    }
+
+    #[test]
+    fn vram_failure_with_enough_free_is_fragmentation() {
+        for available in [8 << 20, 1 << 30] {
+            let err = diagnose_vram_failure(8 << 20, available);
+            assert!(
+                matches!(err, MemoryError::VramFragmented { requested, available: a }
+                    if requested == 8 << 20 && a == available),
+                "{err:?}"
+            );
+            assert!(err.is_out_of_memory());
+        }
     }
 }
@@ -2,14 +2,97 @@ pub mod aperture;
 pub mod manager;
 
 use crate::kfd::device::KfdDevice;
-use crate::kfd::ioctl::UnmapMemoryFromGpuArgs;
 use manager::AllocFlags;
-pub use manager::MemoryManager;
-use std::sync::{Arc, Mutex};
+pub use manager::{
+    AllocationRecord, CachePolicy, IpcShareHandle, MemoryManager, MemoryTier, ScratchBacking,
+};
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::{mem, ptr};
+use thiserror::Error;
 
 /// Type alias for the shared, thread-safe memory manager handle.
 pub type ArcManager = Arc<Mutex<MemoryManager>>;
 
+/// Errors returned by the `MemoryManager` allocation paths.
+#[derive(Error, Debug)]
+pub enum MemoryError {
+    #[error("No free VA range left in the aperture")]
+    ApertureExhausted,
+
+    #[error("Allocation of {size} bytes aligned to {align} overflows the address space")]
+    SizeOverflow { size: usize, align: usize },
+
+    #[error("KFD allocation failed: {0}")]
+    KfdAllocFailed(io::Error),
+
+    #[error("Mapping memory to the GPU failed: {0}")]
+    MapFailed(io::Error),
+
+    #[error("CPU mmap failed: {0}")]
+    MmapFailed(io::Error),
+
+    #[error("KFD query failed: {0}")]
+    QueryFailed(io::Error),
+
+    #[error("No GPU aperture for node {0}")]
+    UnknownNode(u32),
+
+    #[error("Address or size is not page-aligned")]
+    UnalignedRequest,
+
+    #[error("VA range 0x{addr:x}+0x{size:x} is outside the aperture or already reserved")]
+    RangeUnavailable { addr: u64, size: usize },
+
+    #[error("GPU (un)mapping stopped after {succeeded} devices, failed for {failed:?}: {source}")]
+    PartialMapping {
+        succeeded: u32,
+        failed: Vec<u32>,
+        source: io::Error,
+    },
+
+    #[error("VRAM exhausted: requested {requested} bytes, {available} available")]
+    VramExhausted { requested: u64, available: u64 },
+
+    #[error(
+        "VRAM fragmented: requested {requested} bytes, {available} available but not allocatable"
+    )]
+    VramFragmented { requested: u64, available: u64 },
+
+    #[error("No live allocation with handle 0x{0:x}")]
+    UnknownHandle(u64),
+
+    #[error("CPU munmap failed: {0}")]
+    MunmapFailed(io::Error),
+
+    #[error("KFD free failed: {0}")]
+    FreeFailed(io::Error),
+
+    #[error("KFD IPC handle export/import failed: {0}")]
+    IpcFailed(io::Error),
+
+    #[error("Registering scratch backing memory failed: {0}")]
+    ScratchSetupFailed(io::Error),
+
+    #[error("SVM attribute update failed: {0}")]
+    SvmFailed(io::Error),
+
+    #[error("MemoryManager has been dropped")]
+    ManagerDropped,
+}
+
+impl MemoryError {
+    /// Whether the allocation failed for lack of memory rather than a bad request.
+    #[must_use]
+    pub fn is_out_of_memory(&self) -> bool {
+        match self {
+            Self::VramExhausted { .. } | Self::VramFragmented { .. } => true,
+            Self::KfdAllocFailed(e) => e.raw_os_error() == Some(libc::ENOMEM),
+            _ => false,
+        }
+    }
+}
+
 /// Represents a successful memory allocation on the GPU (RAII).
 ///
 /// When dropped, it automatically:
@@ -39,56 +122,58 @@ impl Allocation {
     pub const fn as_mut_ptr(&self) -> *mut u8 {
         self.ptr
     }
+
+    /// Frees the allocation now and reports failures that `Drop` can only log.
+    ///
+    /// # Errors
+    /// See [`MemoryManager::free_memory`].
+    pub fn free(self) -> Result<(), MemoryError> {
+        let manager = self.manager_handle.clone();
+        let mut mgr = manager.lock().unwrap_or_else(PoisonError::into_inner);
+        // `Drop` finds the handle untracked and leaves it alone.
+        mgr.free_memory(&self.device, self.handle)
+    }
 }
 
-impl Drop for Allocation {
-    fn drop(&mut self) {
-        // 1. Munmap CPU memory if mapped
-        if !self.ptr.is_null() {
-            unsafe {
-                libc::munmap(self.ptr.cast(), self.size);
-            }
+impl Allocation {
+    /// Forgets an allocation the manager already freed, without running `Drop`, which
+    /// would lock the manager. For use inside `MemoryManager` methods.
+    pub(crate) fn disarm(self) {
+        let this = mem::ManuallyDrop::new(self);
+        // SAFETY: each field is read exactly once and `this` is never used again.
+        unsafe {
+            drop(ptr::read(&this.device));
+            drop(ptr::read(&this.manager_handle));
         }
+    }
+}
 
-        // 2. Acquire lock to reclaim resources
+impl Drop for Allocation {
+    fn drop(&mut self) {
         match self.manager_handle.lock() {
-            Ok(mut mgr) => {
-                // A. Reclaim Virtual Address Space
-                mgr.free_va_from_flags(self.gpu_va, self.size, &self.flags, self.node_id);
-
-                // B. Unmap from GPU (Fix for ResourceBusy)
-                // We must unmap the memory from the device before freeing the handle.
-                if let Some(gpu_id) = mgr.get_gpu_id(self.node_id) {
-                    let mut unmap_args = UnmapMemoryFromGpuArgs {
-                        handle: self.handle,
-                        device_ids_array_ptr: &raw const gpu_id as u64,
-                        n_devices: 1,
-                        n_success: 0,
-                    };
-                    // Attempt unmap. We ignore errors here (e.g. if somehow already unmapped)
-                    // because we must proceed to free the handle to avoid leaking VRAM.
-                    let _ = self.device.unmap_memory_from_gpu(&mut unmap_args);
-                }
-
-                // C. Free GPU resource (KFD Handle)
-                if self.handle != 0
-                    && let Err(e) = self.device.free_memory_of_gpu(self.handle)
-                {
-                    // Ignore PermissionDenied (Os { code: 1 }) as this happens
-                    // for pinned resources like Event Pages during cleanup.
-                    if e.raw_os_error() != Some(1) {
-                        eprintln!(
-                            "[Allocation::drop] Failed to free KFD handle {}: {:?}",
-                            self.handle, e
-                        );
-                    }
+            Ok(mut mgr) => match mgr.free_memory(&self.device, self.handle) {
+                // Already released through `Allocation::free` or `MemoryManager::free_memory`.
+                Ok(()) | Err(MemoryError::UnknownHandle(_)) => {}
+                // Ignore PermissionDenied (Os { code: 1 }) as this happens
+                // for pinned resources like Event Pages during cleanup.
+                Err(MemoryError::FreeFailed(e)) if e.raw_os_error() == Some(1) => {}
+                Err(e) => {
+                    eprintln!(
+                        "[Allocation::drop] Failed to free KFD handle {}: {e}",
+                        self.handle
+                    );
                 }
-            }
+            },
             Err(e) => {
                 eprintln!(
                     "[Allocation::drop] Failed to acquire MemoryManager lock: {e}. VA space leaked."
                 );
                 // Emergency cleanup attempt if lock is poisoned
+                if !self.ptr.is_null() && !self.is_userptr {
+                    unsafe {
+                        libc::munmap(self.ptr.cast(), self.size);
+                    }
+                }
                 if self.handle != 0 {
                     let _ = self.device.free_memory_of_gpu(self.handle);
                 }
@@ -102,6 +187,10 @@ pub trait ApertureAllocator {
     /// Reserve a virtual address range within this aperture.
     fn allocate_va(&mut self, size: usize, align: usize) -> Option<u64>;
 
+    /// Reserve the exact range `[addr, addr + size)`. Returns `false` if it is outside
+    /// the aperture or overlaps an existing reservation.
+    fn reserve_va(&mut self, addr: u64, size: usize) -> bool;
+
     /// Free a previously reserved virtual address range.
     fn free_va(&mut self, addr: u64, size: usize);
 
@@ -1,6 +1,12 @@
 pub mod context;
+pub mod criu;
+pub mod debug;
 pub mod events;
 pub mod memory;
+pub mod monitor;
+pub mod pc_sample;
 pub mod queues;
 pub mod signal;
+pub mod smi;
+pub mod spm;
 pub mod topology;
@@ -0,0 +1,158 @@
+use crate::kfd::device::KfdDevice;
+use std::collections::HashMap;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Direction of the last change in available memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+/// What the monitor knows about one GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemorySample {
+    /// Bytes KFD reported available at the last sample.
+    pub available: u64,
+    /// Lowest `available` seen since the monitor started.
+    pub low_water_mark: u64,
+    pub trend: Trend,
+}
+
+impl MemorySample {
+    const fn first(available: u64) -> Self {
+        Self {
+            available,
+            low_water_mark: available,
+            trend: Trend::Steady,
+        }
+    }
+
+    const fn update(self, available: u64) -> Self {
+        let trend = if available > self.available {
+            Trend::Rising
+        } else if available < self.available {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        };
+        Self {
+            available,
+            low_water_mark: if available < self.low_water_mark {
+                available
+            } else {
+                self.low_water_mark
+            },
+            trend,
+        }
+    }
+}
+
+/// Samples `get_available_memory` for a set of GPUs on a background thread.
+///
+/// The thread takes one sample per GPU right away and then every `interval`. It stops,
+/// and is joined, when the monitor is dropped.
+#[derive(Debug)]
+pub struct MemoryMonitor {
+    samples: Arc<Mutex<HashMap<u32, MemorySample>>>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MemoryMonitor {
+    /// Starts sampling the GPUs KFD knows as `gpu_ids`.
+    #[must_use]
+    pub fn new(device: &KfdDevice, gpu_ids: Vec<u32>, interval: Duration) -> Self {
+        let device = device.clone();
+        Self::with_source(gpu_ids, interval, move |gpu_id| {
+            device.get_available_memory(gpu_id)
+        })
+    }
+
+    /// Starts sampling with a custom source of available-memory readings.
+    ///
+    /// Failed readings are skipped and leave the GPU's previous sample in place.
+    #[must_use]
+    pub fn with_source<F>(gpu_ids: Vec<u32>, interval: Duration, mut source: F) -> Self
+    where
+        F: FnMut(u32) -> io::Result<u64> + Send + 'static,
+    {
+        let samples = Arc::new(Mutex::new(HashMap::new()));
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let shared = Arc::clone(&samples);
+        let thread = thread::spawn(move || {
+            loop {
+                for &gpu_id in &gpu_ids {
+                    let Ok(available) = source(gpu_id) else {
+                        continue;
+                    };
+                    let mut samples = shared.lock().unwrap();
+                    let sample = samples.get(&gpu_id).map_or_else(
+                        || MemorySample::first(available),
+                        |s: &MemorySample| s.update(available),
+                    );
+                    samples.insert(gpu_id, sample);
+                }
+                // Any message or a dropped sender means stop.
+                if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            samples,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Last available-memory reading per gpu id.
+    ///
+    /// # Panics
+    /// Panics if the sampling thread panicked while holding the samples lock.
+    #[must_use]
+    pub fn latest(&self) -> HashMap<u32, u64> {
+        self.samples
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&gpu_id, s)| (gpu_id, s.available))
+            .collect()
+    }
+
+    /// Full sample for `gpu_id`, `None` until the first successful reading.
+    ///
+    /// # Panics
+    /// Panics if the sampling thread panicked while holding the samples lock.
+    #[must_use]
+    pub fn sample(&self, gpu_id: u32) -> Option<MemorySample> {
+        self.samples.lock().unwrap().get(&gpu_id).copied()
+    }
+
+    /// Lowest available memory seen on `gpu_id` since the monitor started.
+    #[must_use]
+    pub fn low_water_mark(&self, gpu_id: u32) -> Option<u64> {
+        self.sample(gpu_id).map(|s| s.low_water_mark)
+    }
+
+    #[must_use]
+    pub fn trend(&self, gpu_id: u32) -> Option<Trend> {
+        self.sample(gpu_id).map(|s| s.trend)
+    }
+}
+
+impl Drop for MemoryMonitor {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
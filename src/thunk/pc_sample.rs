@@ -0,0 +1,134 @@
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::{
+    KFD_IOCTL_PCS_METHOD_HOSTTRAP, KFD_IOCTL_PCS_OP_CREATE, KFD_IOCTL_PCS_OP_DESTROY,
+    KFD_IOCTL_PCS_OP_QUERY_CAPABILITIES, KFD_IOCTL_PCS_OP_START, KFD_IOCTL_PCS_OP_STOP,
+    KFD_IOCTL_PCS_TYPE_CLOCK_CYCLES, KFD_IOCTL_PCS_TYPE_TIME_US, PcSampleArgs, PcSampleInfo,
+};
+use std::io;
+
+/// Lists the PC sampling configurations `gpu_id` supports: one entry per method and interval
+/// unit, with the allowed `interval_min..=interval_max`.
+///
+/// If a session already exists on the GPU, only its configuration is reported.
+///
+/// # Errors
+/// Fails if the ioctl fails, e.g. `EOPNOTSUPP` on GPUs without PC sampling.
+pub fn query_capabilities(device: &KfdDevice, gpu_id: u32) -> io::Result<Vec<PcSampleInfo>> {
+    // KFD answers a short buffer with ENOSPC and the number of entries it needs.
+    let mut args = PcSampleArgs {
+        op: KFD_IOCTL_PCS_OP_QUERY_CAPABILITIES,
+        gpu_id,
+        ..Default::default()
+    };
+    match device.pc_sample(&mut args) {
+        Ok(()) => {}
+        Err(e) if e.raw_os_error() == Some(libc::ENOSPC) => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut infos = vec![PcSampleInfo::default(); args.num_sample_info as usize];
+    if infos.is_empty() {
+        return Ok(infos);
+    }
+    args.sample_info_ptr = infos.as_mut_ptr() as u64;
+    device.pc_sample(&mut args)?;
+    infos.truncate(args.num_sample_info as usize);
+    Ok(infos)
+}
+
+/// Configuration requested when creating a trace: host-trap intervals are counted in
+/// microseconds, every other method in clock cycles.
+#[must_use]
+pub fn sample_info(interval: u64, method: u32) -> PcSampleInfo {
+    let type_ = if method == KFD_IOCTL_PCS_METHOD_HOSTTRAP {
+        KFD_IOCTL_PCS_TYPE_TIME_US
+    } else {
+        KFD_IOCTL_PCS_TYPE_CLOCK_CYCLES
+    };
+    PcSampleInfo {
+        interval,
+        method,
+        type_,
+        ..Default::default()
+    }
+}
+
+/// A PC sampling trace on one GPU.
+///
+/// Created stopped; `start` and `stop` toggle sampling. Dropping the session destroys the
+/// trace.
+#[derive(Debug)]
+pub struct PcSampleSession {
+    device: KfdDevice,
+    gpu_id: u32,
+    trace_id: u32,
+}
+
+impl PcSampleSession {
+    /// Creates a trace sampling every `interval` units with `method`
+    /// (`KFD_IOCTL_PCS_METHOD_*`).
+    ///
+    /// Host-trap intervals are in microseconds, stochastic ones in clock cycles, matching
+    /// what [`query_capabilities`] reports for each method.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails, e.g. `EBUSY` if another process owns PC sampling on the GPU.
+    pub fn create(device: &KfdDevice, gpu_id: u32, interval: u64, method: u32) -> io::Result<Self> {
+        let mut info = sample_info(interval, method);
+        let mut args = PcSampleArgs {
+            sample_info_ptr: (&raw mut info) as u64,
+            num_sample_info: 1,
+            op: KFD_IOCTL_PCS_OP_CREATE,
+            gpu_id,
+            ..Default::default()
+        };
+        device.pc_sample(&mut args)?;
+
+        Ok(Self {
+            device: device.clone(),
+            gpu_id,
+            trace_id: args.trace_id,
+        })
+    }
+
+    /// Id KFD assigned to the trace.
+    #[must_use]
+    pub const fn trace_id(&self) -> u32 {
+        self.trace_id
+    }
+
+    /// Starts sampling.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn start(&self) -> io::Result<()> {
+        self.op(KFD_IOCTL_PCS_OP_START)
+    }
+
+    /// Stops sampling. The trace can be started again.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn stop(&self) -> io::Result<()> {
+        self.op(KFD_IOCTL_PCS_OP_STOP)
+    }
+
+    fn op(&self, op: u32) -> io::Result<()> {
+        let mut args = PcSampleArgs {
+            op,
+            gpu_id: self.gpu_id,
+            trace_id: self.trace_id,
+            ..Default::default()
+        };
+        self.device.pc_sample(&mut args)
+    }
+}
+
+impl Drop for PcSampleSession {
+    fn drop(&mut self) {
+        // KFD stops a running trace before destroying it.
+        if let Err(e) = self.op(KFD_IOCTL_PCS_OP_DESTROY) {
+            eprintln!("AMDKFD_IOC_PC_SAMPLE destroy failed: {e:?}");
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::error::{HsaError, HsaResult};
+use crate::thunk::queues::builder::{HsaQueue, QueueType};
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// ===============================================================================================
+// AQL Packet Definitions (HSA Platform System Architecture, 2.9)
+// ===============================================================================================
+
+pub const AQL_PACKET_SIZE: usize = 64;
+
+pub const HSA_PACKET_TYPE_INVALID: u16 = 1;
+pub const HSA_PACKET_TYPE_KERNEL_DISPATCH: u16 = 2;
+pub const HSA_PACKET_TYPE_BARRIER_AND: u16 = 3;
+
+pub const HSA_PACKET_HEADER_BARRIER: u16 = 8;
+pub const HSA_PACKET_HEADER_ACQUIRE_FENCE_SCOPE: u16 = 9;
+pub const HSA_PACKET_HEADER_RELEASE_FENCE_SCOPE: u16 = 11;
+
+pub const HSA_FENCE_SCOPE_NONE: u16 = 0;
+pub const HSA_FENCE_SCOPE_AGENT: u16 = 1;
+pub const HSA_FENCE_SCOPE_SYSTEM: u16 = 2;
+
+/// Builds an AQL packet header from its type, barrier bit and fence scopes.
+#[must_use]
+pub const fn packet_header(packet_type: u16, barrier: bool, acquire: u16, release: u16) -> u16 {
+    packet_type
+        | ((barrier as u16) << HSA_PACKET_HEADER_BARRIER)
+        | (acquire << HSA_PACKET_HEADER_ACQUIRE_FENCE_SCOPE)
+        | (release << HSA_PACKET_HEADER_RELEASE_FENCE_SCOPE)
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KernelDispatchPacket {
+    pub header: u16,
+    /// Number of dimensions in bits 0..2, the rest is reserved.
+    pub setup: u16,
+    pub workgroup_size_x: u16,
+    pub workgroup_size_y: u16,
+    pub workgroup_size_z: u16,
+    pub reserved0: u16,
+    pub grid_size_x: u32,
+    pub grid_size_y: u32,
+    pub grid_size_z: u32,
+    pub private_segment_size: u32,
+    pub group_segment_size: u32,
+    pub kernel_object: u64,
+    pub kernarg_address: u64,
+    pub reserved2: u64,
+    pub completion_signal: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BarrierAndPacket {
+    pub header: u16,
+    pub reserved0: u16,
+    pub reserved1: u32,
+    pub dep_signal: [u64; 5],
+    pub reserved2: u64,
+    pub completion_signal: u64,
+}
+
+const _: () = assert!(mem::size_of::<KernelDispatchPacket>() == AQL_PACKET_SIZE);
+const _: () = assert!(mem::size_of::<BarrierAndPacket>() == AQL_PACKET_SIZE);
+
+// ===============================================================================================
+// AQL Ring
+// ===============================================================================================
+
+/// Typed producer for the packet ring of an AQL queue.
+///
+/// Packets are written in two steps as the packet processor expects: the body first, then
+/// the header and setup word with a release store, so the CP never observes a half-written
+/// packet. The write index is then bumped and the doorbell rung.
+pub struct AqlRing<'a> {
+    queue: Option<&'a HsaQueue>,
+    ring: *mut u8,
+    ring_size: u64,
+    write_ptr: *mut u64,
+    read_ptr: *mut u64,
+}
+
+impl<'a> AqlRing<'a> {
+    /// Wraps the builder-allocated ring of a `ComputeAql` queue.
+    ///
+    /// # Errors
+    /// Fails if the queue is not an AQL queue, or if its ring was supplied by the caller
+    /// (the ring has no CPU mapping known to the queue).
+    pub fn new(queue: &'a HsaQueue) -> HsaResult<Self> {
+        if queue.queue_type() != QueueType::ComputeAql {
+            return Err(HsaError::General(
+                "AqlRing requires a ComputeAql queue".into(),
+            ));
+        }
+        let (Some(ring), Some(write_ptr), Some(read_ptr)) = (
+            queue.ring_cpu_addr(),
+            queue.write_ptr_cpu_addr(),
+            queue.read_ptr_cpu_addr(),
+        ) else {
+            return Err(HsaError::General(
+                "AqlRing requires a builder-allocated ring".into(),
+            ));
+        };
+
+        Ok(Self {
+            queue: Some(queue),
+            ring,
+            ring_size: queue.ring_size() / AQL_PACKET_SIZE as u64,
+            write_ptr,
+            read_ptr,
+        })
+    }
+
+    /// Wraps a ring the caller mapped itself, e.g. a plain buffer to stage packets in.
+    ///
+    /// `ring_size` is in bytes. The read and write pointers hold packet indices. No doorbell
+    /// is rung, consumers must poll the write index.
+    ///
+    /// # Safety
+    /// `ring` must be valid for writes of `ring_size` bytes and `write_ptr` and `read_ptr`
+    /// for 64-bit accesses, all aligned and for as long as the returned ring is used.
+    #[must_use]
+    pub const unsafe fn from_raw_parts(
+        ring: *mut u8,
+        ring_size: u64,
+        write_ptr: *mut u64,
+        read_ptr: *mut u64,
+    ) -> Self {
+        Self {
+            queue: None,
+            ring,
+            ring_size: ring_size / AQL_PACKET_SIZE as u64,
+            write_ptr,
+            read_ptr,
+        }
+    }
+
+    /// Number of packet slots in the ring.
+    #[must_use]
+    pub const fn capacity(&self) -> u64 {
+        self.ring_size
+    }
+
+    /// Enqueues a kernel dispatch packet.
+    ///
+    /// # Errors
+    /// Fails if the ring is full.
+    pub fn write_kernel_dispatch(&mut self, packet: &KernelDispatchPacket) -> HsaResult<u64> {
+        let header = u32::from(packet.header) | (u32::from(packet.setup) << 16);
+        self.write_packet(ptr::from_ref(packet).cast(), header)
+    }
+
+    /// Enqueues a barrier-AND packet.
+    ///
+    /// # Errors
+    /// Fails if the ring is full.
+    pub fn write_barrier_and(&mut self, packet: &BarrierAndPacket) -> HsaResult<u64> {
+        let header = u32::from(packet.header) | (u32::from(packet.reserved0) << 16);
+        self.write_packet(ptr::from_ref(packet).cast(), header)
+    }
+
+    /// Copies a 64-byte packet into the next slot and returns its write index.
+    fn write_packet(&mut self, packet: *const u8, header: u32) -> HsaResult<u64> {
+        unsafe {
+            let write_index = AtomicU64::from_ptr(self.write_ptr);
+            let index = write_index.load(Ordering::Acquire);
+            let read = ptr::read_volatile(self.read_ptr);
+            if index - read >= self.ring_size {
+                return Err(HsaError::General("AQL ring is full".into()));
+            }
+
+            let slot = self
+                .ring
+                .add((index % self.ring_size) as usize * AQL_PACKET_SIZE);
+
+            // Body first (everything after the 32-bit header/setup word) ...
+            ptr::copy_nonoverlapping(packet.add(4), slot.add(4), AQL_PACKET_SIZE - 4);
+            // ... then publish the header, which hands the slot over to the packet processor.
+            AtomicU32::from_ptr(slot.cast()).store(header, Ordering::Release);
+
+            write_index.fetch_add(1, Ordering::AcqRel);
+            if let Some(queue) = self.queue {
+                queue.ring_doorbell(index);
+            }
+            Ok(index)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const SLOTS: usize = 8;
+
+    /// A ring of invalid packets with its write and read indices.
+    struct Buffer {
+        ring: Vec<[u64; AQL_PACKET_SIZE / 8]>,
+        write: u64,
+        read: u64,
+    }
+
+    impl Buffer {
+        fn new() -> Box<Self> {
+            let invalid = [u64::from(HSA_PACKET_TYPE_INVALID); AQL_PACKET_SIZE / 8];
+            Box::new(Self {
+                ring: vec![invalid; SLOTS],
+                write: 0,
+                read: 0,
+            })
+        }
+
+        fn aql_ring(&mut self) -> AqlRing<'static> {
+            unsafe {
+                AqlRing::from_raw_parts(
+                    self.ring.as_mut_ptr().cast(),
+                    (SLOTS * AQL_PACKET_SIZE) as u64,
+                    &raw mut self.write,
+                    &raw mut self.read,
+                )
+            }
+        }
+    }
+
+    fn dispatch(tag: u64) -> KernelDispatchPacket {
+        KernelDispatchPacket {
+            header: packet_header(HSA_PACKET_TYPE_KERNEL_DISPATCH, true, 0, 0),
+            setup: 3,
+            kernel_object: tag,
+            completion_signal: !tag,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn packets_land_in_consecutive_slots() {
+        let mut buffer = Buffer::new();
+        let mut ring = buffer.aql_ring();
+        assert_eq!(ring.capacity(), SLOTS as u64);
+
+        buffer.read = 5;
+        buffer.write = 5;
+        for tag in 0..4 {
+            assert_eq!(ring.write_kernel_dispatch(&dispatch(tag)).unwrap(), 5 + tag);
+        }
+        assert_eq!(buffer.write, 9);
+
+        // Index 8 wraps to slot 0; slot 4 was never written.
+        for (slot, tag) in [(5, 0), (6, 1), (7, 2), (0, 3)] {
+            let words = buffer.ring[slot];
+            let header = dispatch(tag).header;
+            assert_eq!(
+                words[0] as u32,
+                u32::from(header) | (3 << 16),
+                "slot {slot}"
+            );
+            assert_eq!(words[4], tag);
+            assert_eq!(words[7], !tag);
+        }
+        assert_eq!(buffer.ring[4][0], u64::from(HSA_PACKET_TYPE_INVALID));
+    }
+
+    #[test]
+    fn full_ring_is_rejected_until_a_packet_is_read() {
+        let mut buffer = Buffer::new();
+        let mut ring = buffer.aql_ring();
+        for tag in 0..SLOTS as u64 {
+            ring.write_kernel_dispatch(&dispatch(tag)).unwrap();
+        }
+        assert!(
+            ring.write_barrier_and(&BarrierAndPacket::default())
+                .is_err()
+        );
+        assert_eq!(buffer.write, SLOTS as u64);
+
+        buffer.read = 1;
+        assert_eq!(
+            ring.write_barrier_and(&BarrierAndPacket::default())
+                .unwrap(),
+            8
+        );
+    }
+
+    /// A consumer that trusts the header must always see the complete body.
+    #[test]
+    fn header_is_published_after_the_body() {
+        const PACKETS: u64 = 10_000;
+        let mut buffer = Buffer::new();
+        let mut ring = buffer.aql_ring();
+        let base = buffer.ring.as_mut_ptr() as usize;
+        let read = &raw mut buffer.read as usize;
+
+        let consumer = thread::spawn(move || {
+            for tag in 0..PACKETS {
+                let slot = (base + (tag as usize % SLOTS) * AQL_PACKET_SIZE) as *mut u64;
+                unsafe {
+                    let header = AtomicU32::from_ptr(slot.cast());
+                    while header.load(Ordering::Acquire) == u32::from(HSA_PACKET_TYPE_INVALID) {
+                        thread::yield_now();
+                    }
+                    assert_eq!(ptr::read_volatile(slot.add(4)), tag);
+                    assert_eq!(ptr::read_volatile(slot.add(7)), !tag);
+                    ptr::write_volatile(slot.add(4), 0);
+                    ptr::write_volatile(slot.add(7), 0);
+                    header.store(u32::from(HSA_PACKET_TYPE_INVALID), Ordering::Release);
+                    AtomicU64::from_ptr(read as *mut u64).store(tag + 1, Ordering::Release);
+                }
+            }
+        });
+
+        for tag in 0..PACKETS {
+            while ring.write_kernel_dispatch(&dispatch(tag)).is_err() {
+                thread::yield_now();
+            }
+        }
+        consumer.join().unwrap();
+    }
+}
@@ -3,14 +3,60 @@
 use crate::error::{HsaError, HsaResult};
 use crate::kfd::device::KfdDevice;
 use crate::kfd::ioctl::{
-    CreateQueueArgs, KFD_IOC_QUEUE_TYPE_COMPUTE, KFD_IOC_QUEUE_TYPE_COMPUTE_AQL,
-    KFD_IOC_QUEUE_TYPE_SDMA, KFD_IOC_QUEUE_TYPE_SDMA_XGMI,
+    AllocQueueGwsArgs, CreateQueueArgs, GetQueueWaveStateArgs, KFD_IOC_QUEUE_TYPE_COMPUTE,
+    KFD_IOC_QUEUE_TYPE_COMPUTE_AQL, KFD_IOC_QUEUE_TYPE_SDMA, KFD_IOC_QUEUE_TYPE_SDMA_BY_ENG_ID,
+    KFD_IOC_QUEUE_TYPE_SDMA_XGMI, SetCuMaskArgs, UpdateQueueArgs,
 };
 use crate::kfd::sysfs::HsaNodeProperties;
-use crate::thunk::memory::Allocation;
+use crate::thunk::memory::manager::AllocFlags;
+use crate::thunk::memory::{Allocation, MemoryError};
 use crate::thunk::queues::cwsr;
+use crate::thunk::topology;
+use std::collections::HashMap;
+use std::io;
 use std::os::fd::RawFd;
 use std::ptr;
+use std::sync::atomic::{Ordering, fence};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Live SDMA queues per GPU (keyed by KFD gpu_id), checked against the node's budget.
+static LIVE_SDMA_QUEUES: Mutex<Option<HashMap<u32, u32>>> = Mutex::new(None);
+
+/// One reserved entry in `LIVE_SDMA_QUEUES`, released on drop.
+#[derive(Debug)]
+struct SdmaQueueSlot {
+    gpu_id: u32,
+}
+
+impl SdmaQueueSlot {
+    /// Reserves a slot, `None` if `limit` queues are already live. A `limit` of 0 means
+    /// the node does not report a budget and is not checked.
+    fn reserve(gpu_id: u32, limit: u32) -> Option<Self> {
+        let mut live = LIVE_SDMA_QUEUES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let count = live
+            .get_or_insert_with(HashMap::new)
+            .entry(gpu_id)
+            .or_insert(0);
+        if limit != 0 && *count >= limit {
+            return None;
+        }
+        *count += 1;
+        Some(Self { gpu_id })
+    }
+}
+
+impl Drop for SdmaQueueSlot {
+    fn drop(&mut self) {
+        let mut live = LIVE_SDMA_QUEUES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = live.as_mut().and_then(|m| m.get_mut(&self.gpu_id)) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QueueType {
@@ -31,6 +77,84 @@ pub enum QueuePriority {
     Maximum = 3,
 }
 
+/// Memory placement for a buffer the builder allocates on the caller's behalf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Placement {
+    /// Device-local memory. Lowest fetch latency for the GPU.
+    Vram,
+    /// System memory. Always CPU-writable, required on small-BAR systems.
+    Gtt,
+    /// VRAM on large-BAR dGPUs, where the CPU can reach all of it (falling back to GTT if
+    /// the allocation fails), GTT on small-BAR dGPUs and on APUs.
+    #[default]
+    Auto,
+}
+
+/// Where the builder places the per-queue buffers it allocates besides the ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlacementPolicy {
+    pub eop: Placement,
+    pub cwsr: Placement,
+    /// The read/write pointer page. Polled by the CPU, so VRAM needs a large BAR.
+    pub pointers: Placement,
+}
+
+impl PlacementPolicy {
+    /// EOP in VRAM on large-BAR dGPUs (GTT elsewhere), CWSR and pointers in GTT.
+    pub const DEFAULT: Self = Self {
+        eop: Placement::Auto,
+        cwsr: Placement::Gtt,
+        pointers: Placement::Gtt,
+    };
+}
+
+impl Default for PlacementPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Wave state of a queue as last saved by the CP, see [`HsaQueue::capture_wave_state`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveStateSnapshot {
+    /// Bytes of the control stack KFD copied out (`ctl_stack_used_size`).
+    pub control_stack_size: u32,
+    /// Bytes of the save area in use by saved waves (`save_area_used_size`).
+    pub wave_state_size: u32,
+    /// Used portion of the control stack, copied out of the CWSR area by KFD.
+    pub control_stack: Vec<u8>,
+}
+
+impl WaveStateSnapshot {
+    /// Builds a snapshot from the buffer passed to `GET_QUEUE_WAVE_STATE` and the used
+    /// sizes KFD returned, keeping only the part of `buffer` KFD wrote.
+    ///
+    /// # Errors
+    /// Returns `InvalidData` if `ctl_stack_used_size` exceeds the buffer.
+    pub fn from_kfd(
+        mut buffer: Vec<u8>,
+        ctl_stack_used_size: u32,
+        save_area_used_size: u32,
+    ) -> io::Result<Self> {
+        let used = ctl_stack_used_size as usize;
+        if used > buffer.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "KFD reported {used} control stack bytes for a {}-byte buffer",
+                    buffer.len()
+                ),
+            ));
+        }
+        buffer.truncate(used);
+        Ok(Self {
+            control_stack_size: ctl_stack_used_size,
+            wave_state_size: save_area_used_size,
+            control_stack: buffer,
+        })
+    }
+}
+
 /// A RAII-wrapper around a KFD Queue and its resources.
 ///
 /// This struct takes ownership of the queue ID and associated memory allocations (EOP, CWSR).
@@ -44,13 +168,29 @@ pub struct HsaQueue {
     pub queue_err_reason: u64,
 
     device: KfdDevice,
+    queue_type: QueueType,
+    ring_base: u64,
+    ring_size: u64,
+    gfx_version: u32,
+    /// Width of the doorbell register in bytes, see [`doorbell_size`].
+    doorbell_size: usize,
+    /// Control stack size passed to KFD, 0 if the queue has no CWSR area.
+    ctl_stack_size: u32,
     eop_mem: Option<Allocation>,
     cwsr_mem: Option<Allocation>,
     ptr_mem: Option<Allocation>,
-    _doorbell_mem: Option<Allocation>,
+    ring_mem: Option<Allocation>,
+    _doorbell_mem: Option<Arc<Allocation>>,
+    _sdma_slot: Option<SdmaQueueSlot>,
 }
 
 impl HsaQueue {
+    /// Returns the CPU pointer to the ring buffer if it was allocated by the builder.
+    #[must_use]
+    pub fn ring_cpu_addr(&self) -> Option<*mut u8> {
+        self.ring_mem.as_ref().map(Allocation::as_mut_ptr)
+    }
+
     /// Returns the CPU pointer to the Queue Write Index.
     /// The Write Index is at offset 8 in the ptr_mem allocation.
     pub fn write_ptr_cpu_addr(&self) -> Option<*mut u64> {
@@ -58,6 +198,128 @@ impl HsaQueue {
             .as_ref()
             .map(|alloc| unsafe { alloc.ptr.add(8).cast::<u64>() })
     }
+
+    /// Returns the CPU pointer to the Queue Read Index written back by the hardware.
+    /// The Read Index is at offset 0 in the ptr_mem allocation.
+    pub fn read_ptr_cpu_addr(&self) -> Option<*mut u64> {
+        self.ptr_mem.as_ref().map(|alloc| alloc.ptr.cast::<u64>())
+    }
+
+    #[must_use]
+    pub const fn queue_type(&self) -> QueueType {
+        self.queue_type
+    }
+
+    /// Ring buffer size in bytes.
+    #[must_use]
+    pub const fn ring_size(&self) -> u64 {
+        self.ring_size
+    }
+
+    /// `gfx_target_version` of the node the queue was created on.
+    #[must_use]
+    pub const fn gfx_version(&self) -> u32 {
+        self.gfx_version
+    }
+
+    /// Width of the queue doorbell in bytes: 8 on SOC15 (GFX9+), 4 on older ASICs.
+    #[must_use]
+    pub const fn doorbell_size(&self) -> usize {
+        self.doorbell_size
+    }
+
+    /// Writes `value` to the queue doorbell.
+    ///
+    /// A release fence first makes every packet written so far visible to the CP. 32-bit
+    /// doorbells receive the low half of `value`.
+    pub fn ring_doorbell(&self, value: u64) {
+        fence(Ordering::Release);
+        unsafe {
+            if self.doorbell_size == 8 {
+                ptr::write_volatile(self.queue_doorbell as *mut u64, value);
+            } else {
+                ptr::write_volatile(self.queue_doorbell as *mut u32, value as u32);
+            }
+        }
+    }
+
+    /// Restricts the queue to a subset of compute units.
+    ///
+    /// Bit `i` of the mask enables CU `i`: bit 0 of `mask[0]` is CU 0, bit 0 of `mask[1]` is
+    /// CU 32, and so on. CUs are numbered across shader engines the way KFD distributes
+    /// them; bits beyond the node's CU count are ignored.
+    pub fn set_cu_mask(&self, mask: &[u32]) -> io::Result<()> {
+        if mask.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "CU mask must not be empty",
+            ));
+        }
+        let mut args = SetCuMaskArgs {
+            queue_id: self.queue_id,
+            num_cu_mask: (mask.len() * 32) as u32,
+            cu_mask_ptr: mask.as_ptr() as u64,
+        };
+        self.device.set_cu_mask(&mut args)
+    }
+
+    /// Changes the scheduling priority and CU time share of the live queue.
+    ///
+    /// The ring stays where it is; `percentage` is the share of CU time (1-100) the queue
+    /// may use, as at creation.
+    ///
+    /// # Errors
+    /// Returns `InvalidInput` for a percentage above 100, otherwise fails if the ioctl does.
+    pub fn update(&self, priority: QueuePriority, percentage: u32) -> io::Result<()> {
+        let mut args = update_queue_args(
+            self.queue_id,
+            self.ring_base,
+            self.ring_size,
+            priority,
+            percentage,
+        )?;
+        self.device.update_queue(&mut args)
+    }
+
+    /// Attaches `num_gws` global wave sync entries to the queue and returns the index of the
+    /// first one, for kernels that use GWS barriers.
+    ///
+    /// # Errors
+    /// Returns `InvalidInput` without calling KFD if `num_gws` is 0 or exceeds the node's
+    /// `num_gws`, otherwise fails if the ioctl does (e.g. GWS is already in use).
+    pub fn alloc_gws(&self, num_gws: u32, node_props: &HsaNodeProperties) -> io::Result<u32> {
+        let mut args = alloc_gws_args(self.queue_id, num_gws, node_props)?;
+        self.device.alloc_queue_gws(&mut args)?;
+        Ok(args.first_gws)
+    }
+
+    /// Captures the saved wave state of the queue.
+    ///
+    /// KFD copies the used part of the control stack into a buffer owned by the snapshot;
+    /// the queue's CWSR area is left untouched. The data is only meaningful while the queue
+    /// is preempted (e.g. hung or suspended). On multi-XCC GPUs this describes the first XCC.
+    ///
+    /// # Errors
+    /// Returns `Unsupported` if the queue was created without a CWSR area, the ioctl error
+    /// if KFD rejects the request, and `InvalidData` if the reported size exceeds the buffer.
+    pub fn capture_wave_state(&self) -> io::Result<WaveStateSnapshot> {
+        if self.ctl_stack_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "queue has no CWSR area",
+            ));
+        }
+
+        let mut buffer = vec![0u8; self.ctl_stack_size as usize];
+        let mut args = GetQueueWaveStateArgs {
+            ctl_stack_address: buffer.as_mut_ptr() as u64,
+            queue_id: self.queue_id,
+            ..Default::default()
+        };
+        self.device.get_queue_wave_state(&mut args)?;
+
+        WaveStateSnapshot::from_kfd(buffer, args.ctl_stack_used_size, args.save_area_used_size)
+    }
 }
 
 impl Drop for HsaQueue {
@@ -69,41 +331,150 @@ impl Drop for HsaQueue {
             );
         }
 
-        // 2. Free associated GPU memory resources
-        // Note: This calls the KFD free ioctl via the device.
-        // If the MemoryManager tracks VA ranges, those ranges effectively leak here
-        // unless the MemoryManager is shared/singleton. For a simple thunk, this
-        // ensures the physical/backing memory is returned to the OS.
-        if let Some(alloc) = &self.eop_mem {
-            self.device.free_memory_of_gpu(alloc.handle).ok();
-        }
-        if let Some(alloc) = &self.cwsr_mem {
-            self.device.free_memory_of_gpu(alloc.handle).ok();
-        }
-        if let Some(alloc) = &self.ptr_mem {
-            self.device.free_memory_of_gpu(alloc.handle).ok();
+        // 2. Free associated GPU memory resources now that the CP no longer uses them.
+        let buffers = [
+            ("EOP", self.eop_mem.take()),
+            ("CWSR", self.cwsr_mem.take()),
+            ("pointer", self.ptr_mem.take()),
+            ("ring", self.ring_mem.take()),
+        ];
+        for (name, alloc) in buffers {
+            if let Some(alloc) = alloc
+                && let Err(e) = alloc.free()
+            {
+                eprintln!(
+                    "[HsaQueue] Failed to free {name} buffer of queue ID {}: {e}",
+                    self.queue_id
+                );
+            }
         }
     }
 }
 
+/// Size of the per-process doorbell page KFD assigns on a GPU.
+#[must_use]
+pub const fn doorbell_page_size(gfx_version: u32) -> usize {
+    if gfx_version >= 90000 {
+        8 * 1024
+    } else {
+        4 * 1024
+    }
+}
+
+/// Size of one queue doorbell in bytes.
+///
+/// SOC15 (GFX9+) doorbells are 64-bit, older ASICs use 32-bit doorbells.
+#[must_use]
+pub const fn doorbell_size(gfx_version: u32) -> usize {
+    if gfx_version >= 90000 { 8 } else { 4 }
+}
+
+/// Splits the `doorbell_offset` returned by `CREATE_QUEUE` into the page-aligned mmap
+/// offset and the byte offset of the queue's doorbell within that page.
+///
+/// On SOC15 the kernel folds the in-page offset into `kernel_offset`. Older ASICs only
+/// return the page's mmap cookie, so the doorbell is located by `queue_id` instead.
+#[must_use]
+pub const fn doorbell_offsets(kernel_offset: u64, queue_id: u32, gfx_version: u32) -> (u64, usize) {
+    let mask = (doorbell_page_size(gfx_version) - 1) as u64;
+    let mmap_offset = kernel_offset & !mask;
+    if gfx_version >= 90000 {
+        (mmap_offset, (kernel_offset & mask) as usize)
+    } else {
+        (mmap_offset, queue_id as usize * doorbell_size(gfx_version))
+    }
+}
+
+/// Flags for a builder-allocated ring buffer.
+///
+/// AQL rings are tagged as AQL queue memory, which some ASICs require for the packet
+/// processor to read the ring coherently.
+#[must_use]
+pub fn ring_alloc_flags(queue_type: QueueType, vram: bool) -> AllocFlags {
+    let flags = AllocFlags::queue_buffer(vram, true);
+    if queue_type == QueueType::ComputeAql {
+        flags.aql_queue_mem()
+    } else {
+        flags
+    }
+}
+
+/// Arguments of the `UPDATE_QUEUE` ioctl behind [`HsaQueue::update`].
+///
+/// # Errors
+/// Returns `InvalidInput` for a `percentage` above 100.
+pub(crate) fn update_queue_args(
+    queue_id: u32,
+    ring_base: u64,
+    ring_size: u64,
+    priority: QueuePriority,
+    percentage: u32,
+) -> io::Result<UpdateQueueArgs> {
+    if percentage > 100 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("queue percentage {percentage} exceeds 100"),
+        ));
+    }
+    Ok(UpdateQueueArgs {
+        ring_base_address: ring_base,
+        queue_id,
+        ring_size: ring_size as u32,
+        queue_percentage: percentage,
+        queue_priority: QueueBuilder::map_priority(priority),
+    })
+}
+
+/// Arguments of the `ALLOC_QUEUE_GWS` ioctl behind [`HsaQueue::alloc_gws`].
+///
+/// # Errors
+/// Returns `InvalidInput` if `num_gws` is 0 or exceeds the node's `num_gws`.
+pub(crate) fn alloc_gws_args(
+    queue_id: u32,
+    num_gws: u32,
+    node_props: &HsaNodeProperties,
+) -> io::Result<AllocQueueGwsArgs> {
+    if num_gws == 0 || num_gws > node_props.num_gws {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "requested {num_gws} GWS entries, node {} has {}",
+                node_props.node_id, node_props.num_gws
+            ),
+        ));
+    }
+    Ok(AllocQueueGwsArgs {
+        queue_id,
+        num_gws,
+        ..Default::default()
+    })
+}
+
+/// Number of queue doorbells that fit in one doorbell page.
+#[must_use]
+pub const fn doorbells_per_page(gfx_version: u32) -> usize {
+    doorbell_page_size(gfx_version) / doorbell_size(gfx_version)
+}
+
 /// Abstraction for the Flat Memory Model manager needed by the builder.
 pub trait MemoryManager {
-    /// Allocate GPU accessible memory (GTT or VRAM)
+    /// Allocate GPU accessible memory (GTT or VRAM) with the given flags.
     fn allocate_gpu_memory(
         &mut self,
         device: &KfdDevice,
         size: usize,
         align: usize,
-        vram: bool,
-        public: bool,
+        flags: AllocFlags,
         drm_fd: RawFd,
         node_id: u32,
-    ) -> HsaResult<Allocation>;
+    ) -> Result<Allocation, MemoryError>;
 
     /// Free allocated memory
     fn free_gpu_memory(&mut self, device: &KfdDevice, alloc: &Allocation);
 
-    /// Map a doorbell index to a CPU virtual address
+    /// Map a doorbell page to a CPU virtual address.
+    ///
+    /// Queues on the same doorbell page may share the returned mapping.
     fn map_doorbell(
         &mut self,
         device: &KfdDevice,
@@ -111,7 +482,7 @@ pub trait MemoryManager {
         gpu_id: u32,
         doorbell_offset: u64,
         size: u64,
-    ) -> HsaResult<Allocation>;
+    ) -> Result<Arc<Allocation>, MemoryError>;
 }
 
 pub struct QueueBuilder<'a> {
@@ -127,11 +498,17 @@ pub struct QueueBuilder<'a> {
     priority: QueuePriority,
     ring_base: u64,
     ring_size: u64,
-    sdma_engine_id: u32,
+    ring_placement: Placement,
+    placement_policy: PlacementPolicy,
+    sdma_engine_id: Option<u32>,
     user_cwsr: Option<(u64, u32, u32)>, // (address, size, ctl_stack_size)
 }
 
 impl<'a> QueueBuilder<'a> {
+    /// Creates a builder for a queue on `node_id`.
+    ///
+    /// Passing a `ring_base` of 0 makes the builder allocate a zeroed ring of `ring_size`
+    /// bytes itself (see [`Self::with_ring_placement`]); the queue then owns and frees it.
     pub fn new(
         device: &'a KfdDevice,
         mem_mgr: &'a mut dyn MemoryManager,
@@ -149,10 +526,12 @@ impl<'a> QueueBuilder<'a> {
             drm_fd,
             ring_base,
             ring_size,
+            ring_placement: Placement::Auto,
+            placement_policy: PlacementPolicy::DEFAULT,
             queue_type: QueueType::Compute,
             percentage: 100,
             priority: QueuePriority::Normal,
-            sdma_engine_id: 0,
+            sdma_engine_id: None,
             user_cwsr: None,
         }
     }
@@ -169,6 +548,46 @@ impl<'a> QueueBuilder<'a> {
         self
     }
 
+    /// Selects where a builder-allocated ring buffer is placed.
+    ///
+    /// Only affects queues created with a `ring_base` of 0.
+    #[must_use]
+    pub const fn with_ring_placement(mut self, placement: Placement) -> Self {
+        self.ring_placement = placement;
+        self
+    }
+
+    /// Makes the builder allocate and zero a `ring_size`-byte ring in VRAM or GTT, replacing
+    /// any `ring_base` passed to [`Self::new`]. The queue owns the ring and frees it on drop.
+    #[must_use]
+    pub const fn with_allocated_ring(mut self, ring_size: u64, vram: bool) -> Self {
+        self.ring_base = 0;
+        self.ring_size = ring_size;
+        self.ring_placement = if vram {
+            Placement::Vram
+        } else {
+            Placement::Gtt
+        };
+        self
+    }
+
+    /// Selects where the EOP buffer, CWSR area and queue pointers are placed.
+    #[must_use]
+    pub const fn with_placement_policy(mut self, policy: PlacementPolicy) -> Self {
+        self.placement_policy = policy;
+        self
+    }
+
+    /// Pins an SDMA queue to a specific engine.
+    ///
+    /// Engine ids cover the PCIe SDMA engines first, followed by the XGMI engines. Only
+    /// honoured for `QueueType::Sdma`, which is then created as `SDMA_BY_ENG_ID`.
+    #[must_use]
+    pub const fn with_sdma_engine(mut self, engine_id: u32) -> Self {
+        self.sdma_engine_id = Some(engine_id);
+        self
+    }
+
     /// Manually specify the Context Save/Restore area.
     ///
     /// This is required for creating AQL queues if the library's automatic allocation
@@ -197,6 +616,39 @@ impl<'a> QueueBuilder<'a> {
         let gfx_version = self.node_props.gfx_target_version;
         let is_compute = matches!(self.queue_type, QueueType::Compute | QueueType::ComputeAql);
 
+        let sdma_engine = self
+            .sdma_engine_id
+            .filter(|_| self.queue_type == QueueType::Sdma);
+        if let Some(engine_id) = sdma_engine {
+            let num_engines =
+                self.node_props.num_sdma_engines + self.node_props.num_sdma_xgmi_engines;
+            if engine_id >= num_engines {
+                return Err(HsaError::General(format!(
+                    "SDMA engine {engine_id} out of range, node {} has {num_engines} engines",
+                    self.node_id
+                )));
+            }
+        }
+
+        // Kernel reservations can make KFD refuse queues earlier, but never later.
+        let sdma_slot = if matches!(self.queue_type, QueueType::Sdma | QueueType::SdmaXgmi) {
+            let limit = self.node_props.sdma_queue_budget();
+            let slot = SdmaQueueSlot::reserve(self.node_props.kfd_gpu_id, limit).ok_or(
+                HsaError::TooManyQueues {
+                    node_id: self.node_id,
+                    limit,
+                },
+            )?;
+            Some(slot)
+        } else {
+            None
+        };
+
+        let ring_mem = self.alloc_ring()?;
+        if let Some(ring) = &ring_mem {
+            self.ring_base = ring.gpu_va;
+        }
+
         let eop_mem = self.alloc_eop(gfx_version, is_compute)?;
 
         let (cwsr_mem, cwsr_sizes) = if self.user_cwsr.is_some() {
@@ -217,13 +669,14 @@ impl<'a> QueueBuilder<'a> {
             ring_size: self.ring_size as u32,
             queue_type: match self.queue_type {
                 QueueType::Compute => KFD_IOC_QUEUE_TYPE_COMPUTE,
+                QueueType::Sdma if sdma_engine.is_some() => KFD_IOC_QUEUE_TYPE_SDMA_BY_ENG_ID,
                 QueueType::Sdma => KFD_IOC_QUEUE_TYPE_SDMA,
                 QueueType::ComputeAql => KFD_IOC_QUEUE_TYPE_COMPUTE_AQL,
                 QueueType::SdmaXgmi => KFD_IOC_QUEUE_TYPE_SDMA_XGMI,
             },
             queue_percentage: self.percentage,
             queue_priority: Self::map_priority(self.priority),
-            sdma_engine_id: self.sdma_engine_id,
+            sdma_engine_id: sdma_engine.unwrap_or(0),
             ..Default::default()
         };
 
@@ -248,11 +701,11 @@ impl<'a> QueueBuilder<'a> {
 
         if let Err(e) = self.device.create_queue(&mut args) {
             eprintln!("KFD CreateQueue failed: {e:?}");
-            return Err(HsaError::from(e));
+            return Err(HsaError::from_ioctl(e));
         }
 
         let (doorbell_alloc, doorbell_ptr) =
-            self.resolve_doorbell_ptr(args.doorbell_offset, gfx_version)?;
+            self.resolve_doorbell_ptr(args.doorbell_offset, args.queue_id, gfx_version)?;
 
         Ok(HsaQueue {
             queue_id: args.queue_id,
@@ -262,43 +715,101 @@ impl<'a> QueueBuilder<'a> {
             queue_err_reason: 0,
 
             device: self.device.clone(),
+            queue_type: self.queue_type,
+            ring_base: self.ring_base,
+            ring_size: self.ring_size,
+            gfx_version,
+            doorbell_size: doorbell_size(gfx_version),
+            ctl_stack_size: args.ctl_stack_size,
 
             eop_mem,
             cwsr_mem,
             ptr_mem,
+            ring_mem,
             _doorbell_mem: Some(doorbell_alloc),
+            _sdma_slot: sdma_slot,
         })
     }
 
-    fn alloc_eop(&mut self, gfx_version: u32, is_compute: bool) -> HsaResult<Option<Allocation>> {
-        let eop_size = Self::calculate_eop_size(gfx_version, is_compute);
-        if eop_size > 0 {
-            let mut alloc_res = self.mem_mgr.allocate_gpu_memory(
+    /// Resolves `Placement::Auto` against the node: VRAM on large-BAR dGPUs, GTT otherwise.
+    ///
+    /// The builder zeroes and initializes its buffers through the CPU, which small-BAR
+    /// systems can only do for a sliver of VRAM.
+    fn resolve_placement(&self, placement: Placement) -> Placement {
+        match placement {
+            Placement::Auto if topology::is_large_bar(self.node_id) => Placement::Vram,
+            Placement::Auto => Placement::Gtt,
+            p => p,
+        }
+    }
+
+    fn alloc_ring(&mut self) -> HsaResult<Option<Allocation>> {
+        if self.ring_base != 0 {
+            return Ok(None);
+        }
+        let ring_size = self.ring_size as usize;
+        let queue_type = self.queue_type;
+        if ring_size == 0 {
+            return Err(HsaError::General(
+                "Ring size must be non-zero when the builder allocates the ring".into(),
+            ));
+        }
+
+        let alloc = self
+            .alloc_placed(ring_size, self.ring_placement, |vram| {
+                ring_alloc_flags(queue_type, vram)
+            })
+            .inspect_err(|e| {
+                eprintln!("Failed to allocate queue ring buffer: {e:?}");
+            })?;
+
+        unsafe {
+            ptr::write_bytes(alloc.ptr, 0, ring_size);
+        }
+        Ok(Some(alloc))
+    }
+
+    /// Allocates `size` bytes at `placement`. `Auto` falls back to GTT if the VRAM
+    /// allocation fails, e.g. for lack of CPU-visible VRAM on small-BAR systems.
+    /// `flags_for` builds the allocation flags for a VRAM (`true`) or GTT placement.
+    fn alloc_placed(
+        &mut self,
+        size: usize,
+        placement: Placement,
+        flags_for: impl Fn(bool) -> AllocFlags,
+    ) -> Result<Allocation, MemoryError> {
+        let vram = self.resolve_placement(placement) == Placement::Vram;
+        let alloc_res = self.mem_mgr.allocate_gpu_memory(
+            self.device,
+            size,
+            4096,
+            flags_for(vram),
+            self.drm_fd,
+            self.node_id,
+        );
+        if vram && alloc_res.is_err() && placement == Placement::Auto {
+            return self.mem_mgr.allocate_gpu_memory(
                 self.device,
-                eop_size,
+                size,
                 4096,
-                true, // Try VRAM first
-                true,
+                flags_for(false),
                 self.drm_fd,
                 self.node_id,
             );
+        }
+        alloc_res
+    }
 
-            if alloc_res.is_err() {
-                // Fallback to GTT
-                alloc_res = self.mem_mgr.allocate_gpu_memory(
-                    self.device,
-                    eop_size,
-                    4096,
-                    false, // VRAM=false -> GTT
-                    true,
-                    self.drm_fd,
-                    self.node_id,
-                );
-            }
-
-            let alloc = alloc_res.inspect_err(|_e| {
-                eprintln!("Failed to allocate EOP buffer");
-            })?;
+    fn alloc_eop(&mut self, gfx_version: u32, is_compute: bool) -> HsaResult<Option<Allocation>> {
+        let eop_size = Self::calculate_eop_size(gfx_version, is_compute);
+        if eop_size > 0 {
+            let alloc = self
+                .alloc_placed(eop_size, self.placement_policy.eop, |vram| {
+                    AllocFlags::queue_buffer(vram, true)
+                })
+                .inspect_err(|_e| {
+                    eprintln!("Failed to allocate EOP buffer");
+                })?;
 
             unsafe {
                 ptr::write_bytes(alloc.ptr, 0, eop_size);
@@ -318,16 +829,12 @@ impl<'a> QueueBuilder<'a> {
             && is_compute
             && let Some(sizes) = cwsr::calculate_sizes(self.node_props)
         {
+            // CPU-mapped so the save area header can be initialized below.
             let alloc = self
-                .mem_mgr
-                .allocate_gpu_memory(
-                    self.device,
+                .alloc_placed(
                     sizes.total_mem_alloc_size as usize,
-                    4096,
-                    false,
-                    false,
-                    self.drm_fd,
-                    self.node_id,
+                    self.placement_policy.cwsr,
+                    |vram| AllocFlags::queue_buffer(vram, true),
                 )
                 .inspect_err(|_e| {
                     eprintln!("Failed to allocate CWSR");
@@ -344,16 +851,9 @@ impl<'a> QueueBuilder<'a> {
 
     fn alloc_pointers(&mut self) -> HsaResult<Allocation> {
         let ptr_alloc = self
-            .mem_mgr
-            .allocate_gpu_memory(
-                self.device,
-                4096,
-                4096,
-                false,
-                true,
-                self.drm_fd,
-                self.node_id,
-            )
+            .alloc_placed(4096, self.placement_policy.pointers, |vram| {
+                AllocFlags::queue_buffer(vram, true)
+            })
             .inspect_err(|e| {
                 eprintln!("Failed to allocate queue pointers: {e:?}");
             })?;
@@ -397,21 +897,11 @@ impl<'a> QueueBuilder<'a> {
     fn resolve_doorbell_ptr(
         &mut self,
         kernel_offset: u64,
+        queue_id: u32,
         gfx_version: u32,
-    ) -> HsaResult<(Allocation, *mut u32)> {
-        let is_soc15 = gfx_version >= 90000;
-
-        let doorbell_page_size = if gfx_version >= 90000 { 8 } else { 4 } * 1024;
-
-        let mask = (doorbell_page_size - 1) as u64;
-
-        let mmap_offset = if is_soc15 {
-            kernel_offset & !mask
-        } else {
-            kernel_offset
-        };
-
-        let ptr_offset = if is_soc15 { kernel_offset & mask } else { 0 };
+    ) -> HsaResult<(Arc<Allocation>, *mut u32)> {
+        let doorbell_page_size = doorbell_page_size(gfx_version);
+        let (mmap_offset, ptr_offset) = doorbell_offsets(kernel_offset, queue_id, gfx_version);
 
         let allocation = self.mem_mgr.map_doorbell(
             self.device,
@@ -426,8 +916,357 @@ impl<'a> QueueBuilder<'a> {
         #[allow(clippy::cast_ptr_alignment)]
         unsafe {
             // Calculate the specific u32 pointer from the allocation's base ptr
-            let byte_ptr = allocation.as_mut_ptr().add(ptr_offset as usize);
+            let byte_ptr = allocation.as_mut_ptr().add(ptr_offset);
             Ok((allocation, byte_ptr.cast::<u32>()))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kfd::ioctl::{
+        KFD_IOC_ALLOC_MEM_FLAGS_GTT, KFD_IOC_ALLOC_MEM_FLAGS_PUBLIC, KFD_IOC_ALLOC_MEM_FLAGS_VRAM,
+    };
+    use crate::kfd::mock::Mock;
+    use crate::thunk::memory::ArcManager;
+    use crate::thunk::memory::manager::MemoryManager as Fmm;
+    use crate::thunk::topology::HSA_HEAPTYPE_FRAME_BUFFER_PUBLIC;
+
+    #[test]
+    fn update_carries_the_ring_and_maps_priority() {
+        let args = update_queue_args(5, 0x7000_0000, 0x10_0000, QueuePriority::Normal, 50).unwrap();
+        assert_eq!(args.queue_id, 5);
+        assert_eq!(args.ring_base_address, 0x7000_0000);
+        assert_eq!(args.ring_size, 0x10_0000);
+        assert_eq!(args.queue_percentage, 50);
+        assert_eq!(args.queue_priority, 7);
+    }
+
+    #[test]
+    fn update_priority_spans_the_kfd_range() {
+        let priority = |p| {
+            update_queue_args(0, 0, 0, p, 100)
+                .map(|a| a.queue_priority)
+                .unwrap()
+        };
+        assert_eq!(priority(QueuePriority::Minimum), 0);
+        assert_eq!(priority(QueuePriority::Maximum), 15);
+        assert!(priority(QueuePriority::Low) < priority(QueuePriority::High));
+    }
+
+    #[test]
+    fn update_rejects_percentage_above_100() {
+        assert!(update_queue_args(0, 0, 0, QueuePriority::Normal, 100).is_ok());
+        let err = update_queue_args(0, 0, 0, QueuePriority::Normal, 101).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn sdma_budget_counts_pcie_and_xgmi_engines() {
+        let node = HsaNodeProperties {
+            num_sdma_engines: 2,
+            num_sdma_xgmi_engines: 6,
+            num_sdma_queues_per_engine: 8,
+            ..Default::default()
+        };
+        assert_eq!(node.sdma_queue_budget(), 64);
+
+        let unreported = HsaNodeProperties {
+            num_sdma_queues_per_engine: 0,
+            ..node
+        };
+        assert_eq!(unreported.sdma_queue_budget(), 0);
+        assert_eq!(HsaNodeProperties::default().sdma_queue_budget(), 0);
+    }
+
+    // Each test uses its own gpu_id, as the live counts are process-wide.
+    #[test]
+    fn sdma_slot_rejects_queues_over_budget_until_one_is_released() {
+        let gpu_id = 0x5D0A_0001;
+        let first = SdmaQueueSlot::reserve(gpu_id, 2).unwrap();
+        let _second = SdmaQueueSlot::reserve(gpu_id, 2).unwrap();
+        assert!(SdmaQueueSlot::reserve(gpu_id, 2).is_none());
+
+        drop(first);
+        let _third = SdmaQueueSlot::reserve(gpu_id, 2).unwrap();
+        assert!(SdmaQueueSlot::reserve(gpu_id, 2).is_none());
+
+        // Other GPUs have their own count.
+        assert!(SdmaQueueSlot::reserve(gpu_id + 1, 2).is_some());
+    }
+
+    #[test]
+    fn sdma_slot_without_budget_is_unbounded() {
+        let gpu_id = 0x5D0A_0003;
+        let slots: Vec<_> = (0..100)
+            .map(|_| SdmaQueueSlot::reserve(gpu_id, 0).unwrap())
+            .collect();
+        assert_eq!(slots.len(), 100);
+        drop(slots);
+        assert_eq!(
+            LIVE_SDMA_QUEUES.lock().unwrap().as_ref().unwrap()[&gpu_id],
+            0
+        );
+    }
+
+    #[test]
+    fn gws_request_is_bounded_by_the_node() {
+        let node = HsaNodeProperties {
+            node_id: 1,
+            num_gws: 64,
+            ..Default::default()
+        };
+        let args = alloc_gws_args(3, 64, &node).unwrap();
+        assert_eq!(args.queue_id, 3);
+        assert_eq!(args.num_gws, 64);
+
+        for num_gws in [0, 65] {
+            let err = alloc_gws_args(3, num_gws, &node).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn node_without_gws_rejects_every_request() {
+        let node = HsaNodeProperties::default();
+        assert!(alloc_gws_args(0, 1, &node).is_err());
+    }
+
+    const VEGA20_GPU_ID: u32 = 33333;
+
+    /// Hands every request to a real manager on the mock device and records it.
+    struct RecordingManager {
+        inner: ArcManager,
+        /// KFD flags of every allocation, in order.
+        allocations: Vec<u32>,
+    }
+
+    impl RecordingManager {
+        fn new(mock: &Mock, nodes: &[HsaNodeProperties]) -> Self {
+            Self {
+                inner: Fmm::new(&mock.device, nodes).unwrap(),
+                allocations: Vec::new(),
+            }
+        }
+    }
+
+    impl MemoryManager for RecordingManager {
+        fn allocate_gpu_memory(
+            &mut self,
+            device: &KfdDevice,
+            size: usize,
+            align: usize,
+            flags: AllocFlags,
+            drm_fd: RawFd,
+            node_id: u32,
+        ) -> Result<Allocation, MemoryError> {
+            self.allocations.push(flags.to_kfd_ioctl_flags());
+            self.inner
+                .lock()
+                .unwrap()
+                .allocate_gpu_memory(device, size, align, flags, drm_fd, node_id)
+        }
+
+        fn free_gpu_memory(&mut self, device: &KfdDevice, alloc: &Allocation) {
+            self.inner.lock().unwrap().free_gpu_memory(device, alloc);
+        }
+
+        fn map_doorbell(
+            &mut self,
+            device: &KfdDevice,
+            node_id: u32,
+            gpu_id: u32,
+            doorbell_offset: u64,
+            size: u64,
+        ) -> Result<Arc<Allocation>, MemoryError> {
+            self.inner.lock().unwrap().map_doorbell_shared(
+                device,
+                node_id,
+                gpu_id,
+                doorbell_offset,
+                size,
+            )
+        }
+    }
+
+    /// The single-dGPU fixture on `mock`, its frame buffer bank turned public if
+    /// `large_bar`. Returns the properties of every node.
+    fn install_vega20(mock: &Mock, large_bar: bool) -> Vec<HsaNodeProperties> {
+        topology::install_fixture("vega20_dgpu", &mock.device, |sysfs| {
+            if large_bar {
+                sysfs.nodes[1].mem_banks[0].heap_type = HSA_HEAPTYPE_FRAME_BUFFER_PUBLIC;
+            }
+        })
+    }
+
+    /// KFD flags `Placement::Auto` resolves a ring to on the fixture dGPU.
+    fn auto_ring_flags(large_bar: bool) -> u32 {
+        let mock = Mock::with_gpu(VEGA20_GPU_ID);
+        let nodes = install_vega20(&mock, large_bar);
+        let mut mem = RecordingManager::new(&mock, &nodes);
+
+        let mut builder =
+            QueueBuilder::new(&mock.device, &mut mem, &nodes[1], 1, mock.drm_fd(), 0, 4096)
+                .with_type(QueueType::ComputeAql);
+        let ring = builder.alloc_ring().unwrap();
+        drop((builder, ring));
+        topology::release_system_properties();
+        mem.allocations[0]
+    }
+
+    #[test]
+    fn auto_places_the_ring_in_vram_on_large_bar() {
+        let flags = auto_ring_flags(true);
+        assert_ne!(flags & KFD_IOC_ALLOC_MEM_FLAGS_VRAM, 0);
+        assert_ne!(flags & KFD_IOC_ALLOC_MEM_FLAGS_PUBLIC, 0);
+    }
+
+    #[test]
+    fn auto_places_the_ring_in_gtt_on_small_bar() {
+        let flags = auto_ring_flags(false);
+        assert_ne!(flags & KFD_IOC_ALLOC_MEM_FLAGS_GTT, 0);
+        assert_eq!(flags & KFD_IOC_ALLOC_MEM_FLAGS_VRAM, 0);
+    }
+
+    /// KFD flags of the EOP, CWSR and pointer allocations of a compute queue created with
+    /// `policy` on the fixture dGPU.
+    fn placement_flags(policy: PlacementPolicy, large_bar: bool) -> [u32; 3] {
+        let mock = Mock::with_gpu(VEGA20_GPU_ID);
+        let nodes = install_vega20(&mock, large_bar);
+        let mut mem = RecordingManager::new(&mock, &nodes);
+
+        let queue = QueueBuilder::new(
+            &mock.device,
+            &mut mem,
+            &nodes[1],
+            1,
+            mock.drm_fd(),
+            0x7000_0000,
+            4096,
+        )
+        .with_type(QueueType::ComputeAql)
+        .with_placement_policy(policy)
+        .create()
+        .unwrap();
+        drop(queue);
+        topology::release_system_properties();
+        mem.allocations.try_into().unwrap()
+    }
+
+    #[test]
+    fn placement_policy_selects_each_buffer_heap() {
+        let vram = |flags: u32| flags & KFD_IOC_ALLOC_MEM_FLAGS_VRAM != 0;
+        let gtt = |flags: u32| flags & KFD_IOC_ALLOC_MEM_FLAGS_GTT != 0;
+        let all = |placement| PlacementPolicy {
+            eop: placement,
+            cwsr: placement,
+            pointers: placement,
+        };
+
+        for large_bar in [false, true] {
+            assert_eq!(
+                placement_flags(all(Placement::Vram), large_bar).map(vram),
+                [true; 3]
+            );
+            assert_eq!(
+                placement_flags(all(Placement::Gtt), large_bar).map(gtt),
+                [true; 3]
+            );
+
+            let [eop, cwsr, pointers] = placement_flags(PlacementPolicy::DEFAULT, large_bar);
+            assert_eq!(vram(eop), large_bar);
+            assert!(gtt(cwsr) && gtt(pointers));
+        }
+
+        let mixed = PlacementPolicy {
+            eop: Placement::Gtt,
+            cwsr: Placement::Vram,
+            pointers: Placement::Auto,
+        };
+        let [eop, cwsr, pointers] = placement_flags(mixed, true);
+        assert!(gtt(eop) && vram(cwsr) && vram(pointers));
+    }
+
+    #[test]
+    fn allocated_ring_is_owned_and_freed_by_the_queue() {
+        let mock = Mock::with_gpu(VEGA20_GPU_ID);
+        let nodes = install_vega20(&mock, false);
+        let mut mem = RecordingManager::new(&mock, &nodes);
+
+        // The allocated ring replaces the `ring_base` given to `new`.
+        let queue = QueueBuilder::new(
+            &mock.device,
+            &mut mem,
+            &nodes[1],
+            1,
+            mock.drm_fd(),
+            0x7000_0000,
+            4096,
+        )
+        .with_type(QueueType::ComputeAql)
+        .with_allocated_ring(8192, false)
+        .create()
+        .unwrap();
+
+        let ring = queue.ring_mem.as_ref().unwrap();
+        let handle = ring.handle;
+        assert_eq!(queue.ring_base, ring.gpu_va);
+        assert_eq!(queue.ring_size, 8192);
+        assert_ne!(mem.allocations[0] & KFD_IOC_ALLOC_MEM_FLAGS_GTT, 0);
+        let args = mock.state().buffers[&handle];
+        assert_eq!(args.size, 8192);
+        assert_eq!(mock.state().queues.len(), 1);
+
+        drop(queue);
+        topology::release_system_properties();
+        let state = mock.state();
+        assert!(state.queues.is_empty());
+        assert!(state.freed.contains(&handle));
+        assert!(!state.buffers.contains_key(&handle));
+    }
+
+    #[test]
+    fn create_refuses_sdma_queues_over_the_node_budget() {
+        let mock = Mock::with_gpu(VEGA20_GPU_ID);
+        let nodes = install_vega20(&mock, false);
+        let node = HsaNodeProperties {
+            num_sdma_engines: 1,
+            num_sdma_xgmi_engines: 0,
+            num_sdma_queues_per_engine: 1,
+            ..nodes[1].clone()
+        };
+        let mut mem = RecordingManager::new(&mock, &nodes);
+        let mut create = || {
+            QueueBuilder::new(
+                &mock.device,
+                &mut mem,
+                &node,
+                1,
+                mock.drm_fd(),
+                0x7000_0000,
+                4096,
+            )
+            .with_type(QueueType::Sdma)
+            .create()
+        };
+
+        let first = create().unwrap();
+        let err = create().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                HsaError::TooManyQueues {
+                    node_id: 1,
+                    limit: 1
+                }
+            ),
+            "{err:?}"
+        );
+        assert_eq!(mock.state().queues.len(), 1);
+
+        drop(first);
+        let _second = create().unwrap();
+        topology::release_system_properties();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::kfd::sysfs::HsaNodeProperties;
+use crate::kfd::sysfs::{self, HsaNodeProperties};
 use std::mem;
 
 const HWREG_SIZE_PER_CU: u32 = 0x1000;
@@ -71,28 +71,7 @@ const fn get_vgpr_size_per_cu(gfx_version: u32) -> u32 {
     let minor = (gfx_version / 100) % 100;
     let step = gfx_version % 100;
 
-    // GFX_VERSION_ALDEBARAN (9.4.2)
-    // GFX_VERSION_ARCTURUS  (9.0.8)
-    // GFX_VERSION_AQUA_VANJARAM (9.4.3 is common, logic checks & ~(0xff))
-    // GFX_VERSION_GFX950    (9.5.0)
-    #[rustfmt::skip]
-    let is_large_vgpr_gfx9 = major == 9
-        && (
-            (minor == 0 && step == 8) ||    // Arcturus
-            (minor == 4) ||                 // Aldebaran (9.4.2) & Aqua Vanjaram family
-            (minor == 5 && step == 0)       // GFX950
-        );
-
-    // GFX11+ (Plum Bonito, Wheat Nas, GFX12)
-    let is_gfx11_plus = major >= 11;
-
-    if is_large_vgpr_gfx9 {
-        0x80000 // 512 KB
-    } else if is_gfx11_plus {
-        0x60000 // 384 KB
-    } else {
-        0x40000 // 256 KB (Default for GFX8, GFX9, GFX10)
-    }
+    sysfs::get_vgpr_size_per_cu(major, minor, step)
 }
 
 /// Control stack bytes per wave
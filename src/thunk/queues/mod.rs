@@ -1,2 +1,4 @@
+pub mod aql;
 pub mod builder;
 pub mod cwsr;
+pub mod sdma;
@@ -0,0 +1,243 @@
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::error::{HsaError, HsaResult};
+use crate::thunk::queues::builder::{HsaQueue, QueueType};
+use crate::thunk::signal::Signal;
+use std::ptr;
+use std::sync::atomic::{Ordering, fence};
+
+// ===============================================================================================
+// Packet Encoding (SDMA v3+, linear copies only)
+// ===============================================================================================
+
+pub const SDMA_OP_NOP: u32 = 0;
+pub const SDMA_OP_COPY: u32 = 1;
+pub const SDMA_OP_FENCE: u32 = 5;
+pub const SDMA_OP_TRAP: u32 = 6;
+pub const SDMA_OP_ATOMIC: u32 = 10;
+
+pub const SDMA_SUBOP_COPY_LINEAR: u32 = 0;
+pub const SDMA_ATOMIC_ADD64: u32 = 47;
+
+/// Largest byte count a single `COPY_LINEAR` packet may move (the 22-bit count field,
+/// kept 32-byte aligned as the runtime does).
+pub const SDMA_MAX_LINEAR_COPY_SIZE: u64 = 0x3f_ffe0;
+
+/// Builds a `COPY_LINEAR` packet. `size` must not exceed [`SDMA_MAX_LINEAR_COPY_SIZE`].
+///
+/// SDMA 4.0+ (GFX9 and later) encodes the count as `bytes - 1`, older engines as `bytes`.
+#[must_use]
+pub const fn copy_linear(dst_va: u64, src_va: u64, size: u64, gfx_version: u32) -> [u32; 7] {
+    let count = if gfx_version >= 90000 { size - 1 } else { size };
+    [
+        SDMA_OP_COPY | (SDMA_SUBOP_COPY_LINEAR << 8),
+        count as u32,
+        0,
+        src_va as u32,
+        (src_va >> 32) as u32,
+        dst_va as u32,
+        (dst_va >> 32) as u32,
+    ]
+}
+
+/// Builds a `FENCE` packet writing the 32-bit `data` to `addr` (dword aligned).
+#[must_use]
+pub const fn fence_packet(addr: u64, data: u32) -> [u32; 4] {
+    [SDMA_OP_FENCE, addr as u32, (addr >> 32) as u32, data]
+}
+
+/// Builds an `ATOMIC` packet adding `value` to the 64-bit word at `addr`.
+#[must_use]
+pub const fn atomic_add64(addr: u64, value: i64) -> [u32; 8] {
+    let value = value as u64;
+    [
+        SDMA_OP_ATOMIC | (SDMA_ATOMIC_ADD64 << 25),
+        addr as u32,
+        (addr >> 32) as u32,
+        value as u32,
+        (value >> 32) as u32,
+        0,
+        0,
+        0,
+    ]
+}
+
+/// Builds a `TRAP` packet raising an interrupt that KFD routes to `event_id`.
+#[must_use]
+pub const fn trap(event_id: u32) -> [u32; 2] {
+    [SDMA_OP_TRAP, event_id & 0x0fff_ffff]
+}
+
+/// Encodes a linear copy of `size` bytes, split into as many packets as the count field needs.
+#[must_use]
+pub fn encode_linear_copy(dst_va: u64, src_va: u64, size: u64, gfx_version: u32) -> Vec<[u32; 7]> {
+    let mut packets = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        let chunk = (size - offset).min(SDMA_MAX_LINEAR_COPY_SIZE);
+        packets.push(copy_linear(
+            dst_va + offset,
+            src_va + offset,
+            chunk,
+            gfx_version,
+        ));
+        offset += chunk;
+    }
+    packets
+}
+
+// ===============================================================================================
+// Submission
+// ===============================================================================================
+
+impl HsaQueue {
+    /// Copies `size` bytes from `src_va` to `dst_va` on this SDMA queue.
+    ///
+    /// The copy is split into `COPY_LINEAR` packets, followed by an atomic decrement of
+    /// `completion` and a trap that wakes anyone sleeping on it. The call returns once the
+    /// packets are submitted; wait on `completion` for the copy to finish.
+    ///
+    /// # Errors
+    /// Fails if the queue is not an SDMA queue with a builder-allocated ring, or if the ring
+    /// does not have room for the packets.
+    pub fn sdma_copy(
+        &mut self,
+        dst_va: u64,
+        src_va: u64,
+        size: u64,
+        completion: &Signal,
+    ) -> HsaResult<()> {
+        if !matches!(self.queue_type(), QueueType::Sdma | QueueType::SdmaXgmi) {
+            return Err(HsaError::General("sdma_copy requires an SDMA queue".into()));
+        }
+        let (Some(ring), Some(wptr), Some(rptr)) = (
+            self.ring_cpu_addr(),
+            self.write_ptr_cpu_addr(),
+            self.read_ptr_cpu_addr(),
+        ) else {
+            return Err(HsaError::General(
+                "sdma_copy requires a builder-allocated ring".into(),
+            ));
+        };
+
+        let gfx_version = self.gfx_version();
+        let mut packets: Vec<&[u32]> = Vec::new();
+        let copies = encode_linear_copy(dst_va, src_va, size, gfx_version);
+        packets.extend(copies.iter().map(<[u32; 7]>::as_slice));
+        let decrement = atomic_add64(completion.value_gpu_address(), -1);
+        packets.push(&decrement);
+        let interrupt = trap(completion.event_id());
+        packets.push(&interrupt);
+
+        let ring_size = self.ring_size();
+        unsafe {
+            let start = ptr::read_volatile(wptr);
+            let read = ptr::read_volatile(rptr);
+            let end = write_packets(ring, ring_size, start, read, &packets)
+                .ok_or_else(|| HsaError::General("SDMA ring is full".into()))?;
+
+            fence(Ordering::Release);
+            ptr::write_volatile(wptr, end);
+            self.ring_doorbell(end);
+        }
+        Ok(())
+    }
+}
+
+/// Copies `packets` into the ring at byte offset `start` and returns the new write pointer,
+/// or `None` (writing nothing) if they don't fit ahead of the read pointer `read`.
+///
+/// Packets may not wrap around the end of the ring, the gap is padded with NOPs instead.
+///
+/// # Safety
+/// `ring` must be valid for writes of `ring_size` bytes.
+unsafe fn write_packets(
+    ring: *mut u8,
+    ring_size: u64,
+    start: u64,
+    read: u64,
+    packets: &[&[u32]],
+) -> Option<u64> {
+    let mut end = start;
+    for p in packets {
+        let len = (p.len() * 4) as u64;
+        if end % ring_size + len > ring_size {
+            end += ring_size - end % ring_size;
+        }
+        end += len;
+    }
+    if end - read > ring_size {
+        return None;
+    }
+
+    let mut cur = start;
+    for p in packets {
+        let len = (p.len() * 4) as u64;
+        if cur % ring_size + len > ring_size {
+            while !cur.is_multiple_of(ring_size) {
+                unsafe {
+                    ptr::write_volatile(ring.add((cur % ring_size) as usize).cast(), SDMA_OP_NOP);
+                }
+                cur += 4;
+            }
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(
+                p.as_ptr(),
+                ring.add((cur % ring_size) as usize).cast::<u32>(),
+                p.len(),
+            );
+        }
+        cur += len;
+    }
+    Some(cur)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RING_DWORDS: usize = 16;
+    const RING_SIZE: u64 = RING_DWORDS as u64 * 4;
+
+    fn write(
+        ring: &mut [u32; RING_DWORDS],
+        start: u64,
+        read: u64,
+        packets: &[&[u32]],
+    ) -> Option<u64> {
+        unsafe { write_packets(ring.as_mut_ptr().cast(), RING_SIZE, start, read, packets) }
+    }
+
+    #[test]
+    fn packet_crossing_the_end_is_moved_behind_nop_padding() {
+        let mut ring = [0xdead_beef; RING_DWORDS];
+        let copy = [7; 7];
+        // 12 dwords in: the 7-dword copy does not fit the last 4 dwords.
+        let end = write(&mut ring, 48, 48, &[&copy]).unwrap();
+
+        assert_eq!(end, RING_SIZE + 28);
+        assert_eq!(ring[12..], [SDMA_OP_NOP; 4]);
+        assert_eq!(ring[..7], copy);
+        assert_eq!(ring[7], 0xdead_beef);
+    }
+
+    #[test]
+    fn packet_ending_at_the_end_is_not_padded() {
+        let mut ring = [0; RING_DWORDS];
+        let end = write(&mut ring, 56, 56, &[&[1, 2]]).unwrap();
+        assert_eq!(end, RING_SIZE);
+        assert_eq!(ring[14..], [1, 2]);
+    }
+
+    #[test]
+    fn full_ring_writes_nothing() {
+        let mut ring = [0; RING_DWORDS];
+        let copy = [7; 7];
+        // After the padding, the copy reuses the first 7 dwords, which must have been read.
+        assert_eq!(write(&mut ring, 48, 24, &[&copy]), None);
+        assert_eq!(ring, [0; RING_DWORDS]);
+
+        assert_eq!(write(&mut ring, 48, 28, &[&copy]), Some(RING_SIZE + 28));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::{
+    KFD_SMI_EVENT_MIGRATE_END, KFD_SMI_EVENT_MIGRATE_START, KFD_SMI_EVENT_THERMAL_THROTTLE,
+    KFD_SMI_EVENT_VMFAULT, SmiEventsArgs,
+};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+
+/// Size of the KFD SMI fifo; a single read never returns more.
+const SMI_READ_SIZE: usize = 1024;
+
+// ===============================================================================================
+// Events
+// ===============================================================================================
+
+/// One event read from an SMI stream.
+///
+/// KFD writes each event as a text line, `<event id in hex> <payload>`. The payload format
+/// depends on the event, e.g. `<pid>:<task name>` for VM faults and
+/// `<throttle bitmask>:<counter>` for thermal throttling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmiEvent {
+    /// `KFD_SMI_EVENT_*` id.
+    pub event_id: u32,
+    /// GPU the stream was opened on; KFD does not repeat it in the event.
+    pub gpu_id: u32,
+    pub payload: String,
+}
+
+impl SmiEvent {
+    /// Decodes one event line (with or without its trailing newline).
+    #[must_use]
+    pub fn parse(gpu_id: u32, line: &str) -> Option<Self> {
+        let line = line.trim_end_matches('\n');
+        let (id, payload) = line.split_once(' ').unwrap_or((line, ""));
+        let event_id = u32::from_str_radix(id, 16).ok()?;
+        Some(Self {
+            event_id,
+            gpu_id,
+            payload: payload.to_string(),
+        })
+    }
+
+    #[must_use]
+    pub const fn is_thermal_throttle(&self) -> bool {
+        self.event_id == KFD_SMI_EVENT_THERMAL_THROTTLE
+    }
+
+    #[must_use]
+    pub const fn is_vm_fault(&self) -> bool {
+        self.event_id == KFD_SMI_EVENT_VMFAULT
+    }
+
+    #[must_use]
+    pub const fn is_migration(&self) -> bool {
+        matches!(
+            self.event_id,
+            KFD_SMI_EVENT_MIGRATE_START | KFD_SMI_EVENT_MIGRATE_END
+        )
+    }
+
+    /// Throttle reason bitmask of a thermal throttle event (`<bitmask>:<counter>`).
+    #[must_use]
+    pub fn throttle_bitmask(&self) -> Option<u32> {
+        if !self.is_thermal_throttle() {
+            return None;
+        }
+        let (mask, _) = self.payload.split_once(':')?;
+        u32::from_str_radix(mask.trim(), 16).ok()
+    }
+}
+
+// ===============================================================================================
+// Event Stream
+// ===============================================================================================
+
+/// The anon fd KFD hands out for SMI events on one GPU.
+///
+/// No events are delivered until a mask is set with [`set_event_mask`](Self::set_event_mask).
+#[derive(Debug)]
+pub struct SmiEventStream {
+    file: File,
+    gpu_id: u32,
+    /// Bytes read from the fd that do not form a complete line yet.
+    pending: Vec<u8>,
+}
+
+impl SmiEventStream {
+    /// Opens an SMI event stream for the GPU KFD knows as `gpu_id`.
+    ///
+    /// # Errors
+    /// Fails if KFD rejects the request, e.g. for an unknown `gpu_id`.
+    pub fn open(device: &KfdDevice, gpu_id: u32) -> io::Result<Self> {
+        let mut args = SmiEventsArgs { gpu_id, anon_fd: 0 };
+        device.smi_events(&mut args)?;
+        #[allow(clippy::cast_possible_wrap)]
+        let file = unsafe { File::from_raw_fd(args.anon_fd as RawFd) };
+        Ok(Self::from_file(file, gpu_id))
+    }
+
+    /// Wraps an SMI fd that is already open, e.g. one received from another process.
+    #[must_use]
+    pub const fn from_file(file: File, gpu_id: u32) -> Self {
+        Self {
+            file,
+            gpu_id,
+            pending: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn gpu_id(&self) -> u32 {
+        self.gpu_id
+    }
+
+    /// Selects the events to deliver, built from `kfd_smi_event_mask_from_index`.
+    ///
+    /// # Errors
+    /// Fails if the write to the fd fails.
+    pub fn set_event_mask(&mut self, mask: u64) -> io::Result<()> {
+        self.file.write_all(&mask.to_ne_bytes())
+    }
+
+    /// Blocks until the next event arrives.
+    ///
+    /// # Errors
+    /// Fails if polling or reading the fd fails, or with `UnexpectedEof` once KFD closes
+    /// the stream.
+    pub fn next_event(&mut self) -> io::Result<SmiEvent> {
+        loop {
+            if let Some(event) = self.poll(-1)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Returns the next event, waiting at most `timeout_ms` (0 returns immediately, -1 waits
+    /// forever). `None` if no event arrived in time.
+    ///
+    /// # Errors
+    /// Fails if polling or reading the fd fails, or with `UnexpectedEof` if the fd polls
+    /// readable but has nothing left to read, i.e. KFD closed the stream.
+    pub fn poll(&mut self, timeout_ms: i32) -> io::Result<Option<SmiEvent>> {
+        if let Some(event) = self.take_pending() {
+            return Ok(Some(event));
+        }
+
+        let mut pfd = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&raw mut pfd, 1, timeout_ms) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        if ret == 0 {
+            return Ok(None);
+        }
+
+        // KFD only polls readable with data in its fifo, so reading nothing here is EOF.
+        // Retrying would spin on the hung-up fd forever.
+        let mut buf = [0u8; SMI_READ_SIZE];
+        let n = self.file.read(&mut buf)?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "SMI event stream closed",
+            ));
+        }
+        self.pending.extend_from_slice(&buf[..n]);
+        Ok(self.take_pending())
+    }
+
+    /// Pops the first complete line off the pending buffer, skipping lines that don't parse.
+    fn take_pending(&mut self) -> Option<SmiEvent> {
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            if let Some(event) = SmiEvent::parse(self.gpu_id, &String::from_utf8_lossy(&line)) {
+                return Some(event);
+            }
+        }
+        None
+    }
+}
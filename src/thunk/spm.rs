@@ -0,0 +1,145 @@
+use crate::kfd::device::KfdDevice;
+use crate::kfd::ioctl::{
+    KFD_IOCTL_SPM_OP_ACQUIRE, KFD_IOCTL_SPM_OP_RELEASE, KFD_IOCTL_SPM_OP_SET_DEST_BUF, SpmArgs,
+    SpmBufferHeader,
+};
+use std::io;
+use std::mem;
+
+/// Counter data streamed into one destination buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct SpmChunk<'a> {
+    pub data: &'a [u8],
+    /// The RLC dropped samples because the buffer was not swapped in time.
+    pub has_data_loss: bool,
+}
+
+impl<'a> SpmChunk<'a> {
+    /// Extracts the samples from a filled destination buffer.
+    ///
+    /// Newer kernels start the buffer with an [`SpmBufferHeader`] (non-zero `version`) whose
+    /// counts take precedence; older ones only report through the ioctl arguments.
+    #[must_use]
+    pub fn parse(buffer: &'a [u8], bytes_copied: u32, has_data_loss: bool) -> Self {
+        let filled = &buffer[..(bytes_copied as usize).min(buffer.len())];
+        let header_len = mem::size_of::<SpmBufferHeader>();
+        if filled.len() >= header_len {
+            let header = unsafe { filled.as_ptr().cast::<SpmBufferHeader>().read_unaligned() };
+            if header.version != 0 {
+                let end = (header_len + header.bytes_copied as usize).min(filled.len());
+                return Self {
+                    data: &filled[header_len..end],
+                    has_data_loss: has_data_loss || header.has_data_loss != 0,
+                };
+            }
+        }
+        Self {
+            data: filled,
+            has_data_loss,
+        }
+    }
+}
+
+/// Continuous capture from the RLC streaming performance monitor of one GPU.
+///
+/// KFD copies samples into a user buffer and swaps buffers on every `SET_DEST_BUF`: each call
+/// hands over the next buffer and returns the byte count of the previous one. The capture
+/// keeps two buffers and alternates between them. Dropping it releases the SPM.
+#[derive(Debug)]
+pub struct SpmCapture {
+    device: KfdDevice,
+    gpu_id: u32,
+    buffers: [Vec<u8>; 2],
+    /// Index of the buffer KFD is currently streaming into.
+    active: usize,
+    acquired: bool,
+}
+
+impl SpmCapture {
+    /// Acquires the SPM of `gpu_id` and starts streaming into a `buf_size`-byte buffer.
+    ///
+    /// # Errors
+    /// Fails if the SPM is already owned by another process or the ioctl fails.
+    pub fn start(device: &KfdDevice, gpu_id: u32, buf_size: u32) -> io::Result<Self> {
+        spm_op(device, gpu_id, KFD_IOCTL_SPM_OP_ACQUIRE, 0, 0, 0)?;
+        let mut capture = Self {
+            device: device.clone(),
+            gpu_id,
+            buffers: [vec![0; buf_size as usize], vec![0; buf_size as usize]],
+            active: 0,
+            acquired: true,
+        };
+        let first = capture.buffers[0].as_mut_ptr() as u64;
+        spm_op(
+            device,
+            gpu_id,
+            KFD_IOCTL_SPM_OP_SET_DEST_BUF,
+            first,
+            buf_size,
+            0,
+        )?;
+        Ok(capture)
+    }
+
+    /// Swaps buffers and returns what was streamed into the previous one, waiting up to
+    /// `timeout_ms` for it to fill.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn read(&mut self, timeout_ms: u32) -> io::Result<SpmChunk<'_>> {
+        let next = 1 - self.active;
+        let args = spm_op(
+            &self.device,
+            self.gpu_id,
+            KFD_IOCTL_SPM_OP_SET_DEST_BUF,
+            self.buffers[next].as_mut_ptr() as u64,
+            self.buffers[next].len() as u32,
+            timeout_ms,
+        )?;
+        let filled = mem::replace(&mut self.active, next);
+        Ok(SpmChunk::parse(
+            &self.buffers[filled],
+            args.bytes_copied,
+            args.has_data_loss != 0,
+        ))
+    }
+
+    /// Stops streaming and releases the SPM.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.acquired = false;
+        spm_op(&self.device, self.gpu_id, KFD_IOCTL_SPM_OP_RELEASE, 0, 0, 0).map(drop)
+    }
+}
+
+impl Drop for SpmCapture {
+    fn drop(&mut self) {
+        if self.acquired
+            && let Err(e) = spm_op(&self.device, self.gpu_id, KFD_IOCTL_SPM_OP_RELEASE, 0, 0, 0)
+        {
+            eprintln!("AMDKFD_IOC_RLC_SPM release failed: {e:?}");
+        }
+    }
+}
+
+fn spm_op(
+    device: &KfdDevice,
+    gpu_id: u32,
+    op: u32,
+    dest_buf: u64,
+    buf_size: u32,
+    timeout: u32,
+) -> io::Result<SpmArgs> {
+    let mut args = SpmArgs {
+        dest_buf,
+        buf_size,
+        op,
+        timeout,
+        gpu_id,
+        ..Default::default()
+    };
+    device.spm(&mut args)?;
+    Ok(args)
+}
@@ -1,16 +1,20 @@
+use crate::error::DriverReloadedError;
 use crate::kfd::device::KfdDevice;
 use crate::kfd::ioctl::{
-    GetProcessAperturesArgs, GetProcessAperturesNewArgs, NUM_OF_SUPPORTED_GPUS,
-    ProcessDeviceApertures,
+    GetClockCountersArgs, GetProcessAperturesArgs, GetProcessAperturesNewArgs,
+    NUM_OF_SUPPORTED_GPUS, ProcessDeviceApertures,
 };
 use crate::kfd::sysfs::{self, Topology as SysfsTopology};
 pub use crate::kfd::sysfs::{
-    HsaCacheProperties, HsaIoLinkProperties, HsaMemoryProperties, HsaNodeProperties,
+    HeapType, HsaCacheProperties, HsaIoLinkProperties, HsaMemoryProperties, HsaNodeProperties,
     HsaSystemProperties,
 };
+use crate::thunk::memory::manager::{SVM_MIN_BASE, SVM_RESERVATION_LIMIT};
 use std::collections::HashMap;
 use std::io;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 // ===============================================================================================
 // Constants (Thunk Specific)
@@ -32,14 +36,59 @@ const GFX_VERSION_KAVERI: u32 = 70000;
 // Extended Topology Data
 // ===============================================================================================
 
-#[derive(Debug, Clone, Default)]
-struct NodeApertures {
-    lds_base: u64,
-    lds_limit: u64,
-    scratch_base: u64,
-    scratch_limit: u64,
-    gpuvm_base: u64,
-    gpuvm_limit: u64,
+/// An inclusive virtual address range `[base, limit]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VaRange {
+    pub base: u64,
+    pub limit: u64,
+}
+
+impl VaRange {
+    #[must_use]
+    pub const fn new(base: u64, limit: u64) -> Self {
+        Self { base, limit }
+    }
+
+    /// A range is empty when the driver reports no aperture (`limit <= base`).
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.limit <= self.base
+    }
+
+    /// Size in bytes, 0 for an empty range.
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.limit - self.base + 1
+        }
+    }
+
+    #[must_use]
+    pub const fn contains(&self, addr: u64) -> bool {
+        !self.is_empty() && addr >= self.base && addr <= self.limit
+    }
+}
+
+/// The per-GPU apertures KFD assigned to this process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeApertures {
+    pub lds: VaRange,
+    pub scratch: VaRange,
+    pub gpuvm: VaRange,
+}
+
+/// Which aperture a GPU virtual address falls in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+    Lds,
+    Scratch,
+    /// The shared virtual memory range the memory manager allocates from.
+    Svm,
+    /// GPUVM space outside the SVM range.
+    Gpuvm,
+    Unknown,
 }
 
 /// The runtime topology snapshot.
@@ -48,10 +97,33 @@ pub struct Topology {
     inner: SysfsTopology,
     apertures: HashMap<u32, NodeApertures>,
     is_dgpu: bool,
+    /// KFD `generation_id` the snapshot was taken at.
+    generation_id: u32,
+    /// Handle the snapshot was taken with, probed to detect a driver reload.
+    device: KfdDevice,
 }
 
 static GLOBAL_TOPOLOGY: Mutex<Option<Arc<Topology>>> = Mutex::new(None);
 
+/// MMIO remap pages mapped by the memory manager, keyed by gpu id.
+static MMIO_APERTURES: Mutex<Option<HashMap<u32, VaRange>>> = Mutex::new(None);
+
+/// One simultaneous reading of the GPU, CPU and system clocks of a GPU.
+///
+/// Anchors GPU timestamps (e.g. `SharedSignal::sdma_start_ts`) to the system clock so
+/// profilers can put GPU and CPU events on one timeline. The GPU counter runs at the GPU
+/// reference clock, not at `system_clock_freq`, so its rate is measured against the system
+/// counter over two samples, as ROCr does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+    pub gpu_clock_counter: u64,
+    pub cpu_clock_counter: u64,
+    pub system_clock_counter: u64,
+    pub system_clock_freq: u64,
+    /// Measured GPU counter rate in Hz.
+    pub gpu_clock_freq: u64,
+}
+
 // ===============================================================================================
 // Implementation
 // ===============================================================================================
@@ -69,25 +141,24 @@ impl Topology {
             let gen_end = SysfsTopology::get_generation_id().unwrap_or(0);
 
             if gen_start == gen_end || retries > 5 {
-                let is_dgpu = sys_topo
-                    .nodes
-                    .iter()
-                    .any(|n| n.properties.simd_count > 0 && n.properties.cpu_cores_count == 0);
+                let is_dgpu = sys_topo.nodes.iter().any(sysfs::Node::is_dgpu);
 
-                let apertures = Self::fetch_apertures(&sys_topo.nodes)?;
+                let device = KfdDevice::open()?;
+                let apertures = Self::fetch_apertures(&device, &sys_topo.nodes);
 
                 return Ok(Self {
                     inner: sys_topo,
                     apertures,
                     is_dgpu,
+                    generation_id: gen_end,
+                    device,
                 });
             }
             retries += 1;
         }
     }
 
-    fn fetch_apertures(nodes: &[sysfs::Node]) -> io::Result<HashMap<u32, NodeApertures>> {
-        let kfd = KfdDevice::open()?;
+    fn fetch_apertures(kfd: &KfdDevice, nodes: &[sysfs::Node]) -> HashMap<u32, NodeApertures> {
         let mut map = HashMap::new();
 
         let gpu_nodes: Vec<u32> = nodes
@@ -97,7 +168,7 @@ impl Topology {
             .collect();
 
         if gpu_nodes.is_empty() {
-            return Ok(map);
+            return map;
         }
 
         #[allow(clippy::cast_possible_truncation)]
@@ -132,18 +203,120 @@ impl Topology {
             }
         }
 
-        Ok(map)
+        map
+    }
+
+    /// Sentinel ioctl: after an amdgpu reload, every ioctl on the old fd fails with `ENODEV`.
+    fn driver_reloaded(&self) -> bool {
+        self.device
+            .get_version()
+            .is_err_and(|e| e.raw_os_error() == Some(libc::ENODEV))
+    }
+
+    /// Returns `true` if KFD's `generation_id` moved since this snapshot was taken, i.e. a
+    /// GPU was hot-plugged or removed.
+    ///
+    /// # Errors
+    /// Fails if `generation_id` cannot be read.
+    pub fn is_stale(&self) -> io::Result<bool> {
+        Ok(SysfsTopology::get_generation_id()? != self.generation_id)
     }
 
     const fn convert_aperture(src: &ProcessDeviceApertures) -> NodeApertures {
         NodeApertures {
-            lds_base: src.lds_base,
-            lds_limit: src.lds_limit,
-            scratch_base: src.scratch_base,
-            scratch_limit: src.scratch_limit,
-            gpuvm_base: src.gpuvm_base,
-            gpuvm_limit: src.gpuvm_limit,
+            lds: VaRange::new(src.lds_base, src.lds_limit),
+            scratch: VaRange::new(src.scratch_base, src.scratch_limit),
+            gpuvm: VaRange::new(src.gpuvm_base, src.gpuvm_limit),
+        }
+    }
+
+    /// Returns the apertures of the GPU at `node_id`, `None` for CPU nodes.
+    #[must_use]
+    pub fn node_apertures(&self, node_id: u32) -> Option<NodeApertures> {
+        let node = self.inner.nodes.get(node_id as usize)?;
+        self.apertures.get(&node.properties.kfd_gpu_id).copied()
+    }
+
+    /// The SVM range the memory manager carves its apertures from.
+    ///
+    /// Starts at 256 MiB and ends at the largest GPUVM limit of any GPU, capped at 47 bits.
+    #[must_use]
+    pub fn svm_bounds(&self) -> VaRange {
+        let max_gpuvm = self
+            .apertures
+            .values()
+            .map(|ap| ap.gpuvm.limit)
+            .max()
+            .unwrap_or(0);
+        let limit = if max_gpuvm > 0 {
+            max_gpuvm.min(SVM_RESERVATION_LIMIT)
+        } else {
+            SVM_RESERVATION_LIMIT
+        };
+        VaRange::new(SVM_MIN_BASE, limit)
+    }
+
+    /// Classifies `va` against the apertures of the GPU at `node_id`.
+    ///
+    /// The SVM range lies inside GPUVM, so `Gpuvm` is only reported for the parts of it the
+    /// memory manager does not use. Without apertures for `node_id` only `Svm` and
+    /// `Unknown` are possible.
+    #[must_use]
+    pub fn classify_address(&self, node_id: u32, va: u64) -> AddressKind {
+        let ap = self.node_apertures(node_id);
+        if ap.is_some_and(|ap| ap.lds.contains(va)) {
+            AddressKind::Lds
+        } else if ap.is_some_and(|ap| ap.scratch.contains(va)) {
+            AddressKind::Scratch
+        } else if self.svm_bounds().contains(va) {
+            AddressKind::Svm
+        } else if ap.is_some_and(|ap| ap.gpuvm.contains(va)) {
+            AddressKind::Gpuvm
+        } else {
+            AddressKind::Unknown
+        }
+    }
+
+    /// Filters `node_ids` down to the GPUs that can share one `MapMemoryToGpu` call and
+    /// returns their gpu_ids, ready for `MemoryManager::map_to_gpus`.
+    ///
+    /// The first GPU with apertures (i.e. known to this process's KFD context) anchors the
+    /// group. Later nodes are kept if they also have apertures and an enabled direct link to
+    /// the anchor in either direction. CPU nodes, unknown ids and duplicates are dropped.
+    #[must_use]
+    pub fn mappable_group(&self, node_ids: &[u32]) -> Vec<u32> {
+        let mut anchor = None;
+        let mut gpu_ids = Vec::new();
+
+        for &node_id in node_ids {
+            let Some(node) = self.inner.nodes.get(node_id as usize) else {
+                continue;
+            };
+            let gpu_id = node.properties.kfd_gpu_id;
+            if gpu_id == 0 || !self.apertures.contains_key(&gpu_id) || gpu_ids.contains(&gpu_id) {
+                continue;
+            }
+
+            match anchor {
+                None => anchor = Some(node_id),
+                Some(anchor) if !self.is_peer_linked(anchor, node_id) => continue,
+                Some(_) => {}
+            }
+            gpu_ids.push(gpu_id);
         }
+
+        gpu_ids
+    }
+
+    fn is_peer_linked(&self, a: u32, b: u32) -> bool {
+        let linked = |from: u32, to: u32| {
+            self.inner.nodes.get(from as usize).is_some_and(|n| {
+                n.io_links
+                    .iter()
+                    .any(|l| l.node_to == to && l.link_flags().enabled())
+            })
+        };
+        linked(a, b) || linked(b, a)
     }
 
     const fn is_svm_needed(&self, props: &HsaNodeProperties) -> bool {
@@ -156,21 +329,200 @@ impl Topology {
     }
 }
 
+impl ClockSync {
+    /// Interval between the two clock samples used to measure the GPU counter rate.
+    const CALIBRATION_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Reads the clock counters of the GPU KFD knows as `gpu_id`.
+    ///
+    /// Takes two readings [`Self::CALIBRATION_INTERVAL`] apart to measure the GPU counter
+    /// rate, and anchors at the second one.
+    ///
+    /// # Errors
+    /// Fails if the ioctl fails, or as [`Self::from_samples`] does.
+    pub fn sample(device: &KfdDevice, gpu_id: u32) -> io::Result<Self> {
+        let read = || {
+            let mut args = GetClockCountersArgs {
+                gpu_id,
+                ..Default::default()
+            };
+            device.get_clock_counters(&mut args).map(|()| args)
+        };
+        let first = read()?;
+        thread::sleep(Self::CALIBRATION_INTERVAL);
+        let second = read()?;
+        Self::from_samples(&first, &second)
+    }
+
+    /// Builds a sync point from two clock counter readings, anchored at `second`.
+    ///
+    /// # Errors
+    /// Fails with `InvalidData` if KFD reports no system clock frequency, or if either
+    /// counter did not advance between the readings.
+    pub fn from_samples(
+        first: &GetClockCountersArgs,
+        second: &GetClockCountersArgs,
+    ) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+        if second.system_clock_freq == 0 {
+            return Err(invalid("KFD reported a zero system clock frequency"));
+        }
+        let gpu_delta = second
+            .gpu_clock_counter
+            .wrapping_sub(first.gpu_clock_counter);
+        let sys_delta = second
+            .system_clock_counter
+            .wrapping_sub(first.system_clock_counter);
+        if gpu_delta == 0 || sys_delta == 0 {
+            return Err(invalid("clock counters did not advance between samples"));
+        }
+        let gpu_clock_freq =
+            u128::from(gpu_delta) * u128::from(second.system_clock_freq) / u128::from(sys_delta);
+
+        Ok(Self {
+            gpu_clock_counter: second.gpu_clock_counter,
+            cpu_clock_counter: second.cpu_clock_counter,
+            system_clock_counter: second.system_clock_counter,
+            system_clock_freq: second.system_clock_freq,
+            gpu_clock_freq: u64::try_from(gpu_clock_freq).unwrap_or(u64::MAX),
+        })
+    }
+
+    /// Converts a span of `gpu_ticks` GPU counter ticks to nanoseconds at the measured rate.
+    #[must_use]
+    pub fn gpu_ticks_to_ns(&self, gpu_ticks: u64) -> u64 {
+        let ns = u128::from(gpu_ticks) * 1_000_000_000 / u128::from(self.gpu_clock_freq.max(1));
+        u64::try_from(ns).unwrap_or(u64::MAX)
+    }
+
+    /// Converts a GPU timestamp to nanoseconds in the system clock domain.
+    ///
+    /// The result is the system time at the sample point, shifted by how far `gpu_ticks` is
+    /// from the sampled GPU counter at the measured GPU rate. Timestamps before the epoch of
+    /// the system clock saturate to 0.
+    #[must_use]
+    pub fn gpu_to_wall_ns(&self, gpu_ticks: u64) -> u64 {
+        let to_ns = |ticks: i128, freq: u64| ticks * 1_000_000_000 / i128::from(freq.max(1));
+
+        let anchor_ns = to_ns(
+            i128::from(self.system_clock_counter),
+            self.system_clock_freq,
+        );
+        let delta_ns = to_ns(
+            i128::from(gpu_ticks) - i128::from(self.gpu_clock_counter),
+            self.gpu_clock_freq,
+        );
+        u64::try_from((anchor_ns + delta_ns).max(0)).unwrap_or(u64::MAX)
+    }
+}
+
 // ===============================================================================================
 // Public API Functions
 // ===============================================================================================
 
+/// Drops the cached topology if the driver was reloaded since it was captured.
+///
+/// The error carries [`DriverReloadedError`], which converts to `HsaError::DriverReloaded`.
+/// The next `acquire_system_properties` takes a fresh snapshot.
+fn check_driver(guard: &mut Option<Arc<Topology>>) -> io::Result<()> {
+    if guard.as_ref().is_some_and(|t| t.driver_reloaded()) {
+        guard.take();
+        return Err(DriverReloadedError.into());
+    }
+    Ok(())
+}
+
+/// Returns the global topology, capturing it on first use.
+fn acquire_topology() -> io::Result<Arc<Topology>> {
+    let mut guard = GLOBAL_TOPOLOGY.lock().unwrap();
+    check_driver(&mut guard)?;
+    if guard.is_none() {
+        let topo = Topology::new()?;
+        *guard = Some(Arc::new(topo));
+    }
+    Ok(guard.as_ref().unwrap().clone())
+}
+
+/// Returns the global topology without capturing it.
+///
+/// Fails with `NotConnected` if the topology was not acquired, or with a
+/// [`DriverReloadedError`] if the driver was reloaded since.
+fn acquired_topology() -> io::Result<Arc<Topology>> {
+    let mut guard = GLOBAL_TOPOLOGY.lock().unwrap();
+    check_driver(&mut guard)?;
+    guard
+        .clone()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+}
+
 /// Acquires and initializes the global system properties.
 ///
+/// # Errors
+/// Fails with a [`DriverReloadedError`] if the driver was reloaded since the topology was
+/// cached.
+///
 /// # Panics
 /// Panics if the global topology mutex is poisoned.
 pub fn acquire_system_properties() -> io::Result<HsaSystemProperties> {
+    Ok(acquire_topology()?.inner.system_props.clone())
+}
+
+/// Returns the node index of the GPU KFD knows as `gpu_id`.
+///
+/// Captures the topology if it was not acquired yet. Returns `None` if no node matches or
+/// the topology cannot be read.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+#[must_use]
+pub fn find_node_by_gpu_id(gpu_id: u32) -> Option<u32> {
+    if gpu_id == 0 {
+        return None;
+    }
+    find_node(|p| p.kfd_gpu_id == gpu_id)
+}
+
+/// Returns the node index of the GPU at PCI `domain` and `location_id` (bus/device/function).
+///
+/// Captures the topology if it was not acquired yet. Returns `None` if no node matches or
+/// the topology cannot be read.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+#[must_use]
+pub fn find_node_by_location(domain: u32, location_id: u32) -> Option<u32> {
+    find_node(|p| p.kfd_gpu_id != 0 && p.domain == domain && p.location_id == location_id)
+}
+
+fn find_node(pred: impl Fn(&HsaNodeProperties) -> bool) -> Option<u32> {
+    let topo = acquire_topology().ok()?;
+    let index = topo.inner.nodes.iter().position(|n| pred(&n.properties))?;
+    u32::try_from(index).ok()
+}
+
+/// Recaptures the global topology if its `generation_id` is outdated.
+///
+/// Does nothing if the topology was not captured yet. Long-lived applications can call this
+/// before operations that depend on the node list.
+/// Returns `true` if the topology was rebuilt. Snapshots handed out before the refresh stay
+/// valid but describe the old topology.
+///
+/// # Errors
+/// Fails if `generation_id` cannot be read or the new snapshot cannot be captured, in which
+/// case the old topology is kept.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+pub fn refresh_if_stale() -> io::Result<bool> {
     let mut guard = GLOBAL_TOPOLOGY.lock().unwrap();
-    if guard.is_none() {
-        let topo = Topology::new()?;
-        *guard = Some(Arc::new(topo));
+    check_driver(&mut guard)?;
+    match guard.as_ref() {
+        Some(topo) if topo.is_stale()? => {
+            *guard = Some(Arc::new(Topology::new()?));
+            Ok(true)
+        }
+        _ => Ok(false),
     }
-    Ok(guard.as_ref().unwrap().inner.system_props.clone())
 }
 
 /// Releases the global topology.
@@ -181,17 +533,57 @@ pub fn release_system_properties() {
     GLOBAL_TOPOLOGY.lock().unwrap().take();
 }
 
+/// Installs the sysfs fixture `tests/fixtures/<name>`, after `edit`, as the global
+/// topology, probing apertures and driver reloads through `device`. Returns the properties
+/// of every node.
+#[cfg(test)]
+pub(crate) fn install_fixture(
+    name: &str,
+    device: &KfdDevice,
+    edit: impl FnOnce(&mut SysfsTopology),
+) -> Vec<HsaNodeProperties> {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut inner = SysfsTopology::from_root(&root.join(name)).unwrap();
+    edit(&mut inner);
+    let nodes = inner.nodes.iter().map(|n| n.properties.clone()).collect();
+    let topology = Topology {
+        is_dgpu: inner.nodes.iter().any(sysfs::Node::is_dgpu),
+        apertures: Topology::fetch_apertures(device, &inner.nodes),
+        inner,
+        generation_id: 0,
+        device: device.clone(),
+    };
+    *GLOBAL_TOPOLOGY.lock().unwrap() = Some(Arc::new(topology));
+    nodes
+}
+
+/// Whether `node_id` is a dGPU whose whole VRAM is CPU-visible, see
+/// [`sysfs::Node::is_large_bar`].
+///
+/// `false` if the topology was not acquired or has no such node.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+#[must_use]
+pub fn is_large_bar(node_id: u32) -> bool {
+    acquired_topology().is_ok_and(|topo| {
+        topo.inner
+            .nodes
+            .get(node_id as usize)
+            .is_some_and(sysfs::Node::is_large_bar)
+    })
+}
+
 /// Returns properties for a node.
 ///
+/// # Errors
+/// Fails with a [`DriverReloadedError`] if the driver was reloaded since the topology was
+/// cached.
+///
 /// # Panics
 /// Panics if the global topology mutex is poisoned.
 pub fn get_node_properties(node_id: u32) -> io::Result<HsaNodeProperties> {
-    let topo = GLOBAL_TOPOLOGY
-        .lock()
-        .unwrap()
-        .as_ref()
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?
-        .clone();
+    let topo = acquired_topology()?;
 
     let node = topo
         .inner
@@ -202,9 +594,10 @@ pub fn get_node_properties(node_id: u32) -> io::Result<HsaNodeProperties> {
     let mut props = node.properties.clone();
 
     if props.kfd_gpu_id != 0 {
-        // Unified branch — was identical on both sides
         props.mem_banks_count += 3; // LDS + Scratch + SVM
-        props.mem_banks_count += 1; // MMIO
+        if mmio_aperture(props.kfd_gpu_id).is_some() {
+            props.mem_banks_count += 1;
+        }
     }
 
     Ok(props)
@@ -218,12 +611,7 @@ pub fn get_node_memory_properties(
     node_id: u32,
     num_banks: u32,
 ) -> io::Result<Vec<HsaMemoryProperties>> {
-    let topo = GLOBAL_TOPOLOGY
-        .lock()
-        .unwrap()
-        .as_ref()
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?
-        .clone();
+    let topo = acquired_topology()?;
 
     let node = topo
         .inner
@@ -245,13 +633,14 @@ pub fn get_node_memory_properties(
     }
 
     if let Some(ap) = topo.apertures.get(&node.properties.kfd_gpu_id) {
-        if props.len() < num_banks as usize && ap.lds_limit > ap.lds_base {
+        if props.len() < num_banks as usize && !ap.lds.is_empty() {
             props.push(HsaMemoryProperties {
                 heap_type: HSA_HEAPTYPE_GPU_LDS,
                 size_in_bytes: u64::from(node.properties.lds_size_in_kb) * 1024,
                 flags: 0,
                 width: 0,
                 mem_clk_max: 0,
+                virtual_base_address: 0,
             });
         }
 
@@ -269,25 +658,23 @@ pub fn get_node_memory_properties(
                 flags: 0,
                 width: 0,
                 mem_clk_max: 0,
+                virtual_base_address: 0,
             });
         }
 
-        if props.len() < num_banks as usize && ap.scratch_limit > ap.scratch_base {
+        if props.len() < num_banks as usize && !ap.scratch.is_empty() {
             props.push(HsaMemoryProperties {
                 heap_type: HSA_HEAPTYPE_GPU_SCRATCH,
-                size_in_bytes: (ap.scratch_limit - ap.scratch_base) + 1,
+                size_in_bytes: ap.scratch.size(),
                 flags: 0,
                 width: 0,
                 mem_clk_max: 0,
+                virtual_base_address: 0,
             });
         }
 
         if topo.is_svm_needed(&node.properties) && props.len() < num_banks as usize {
-            let size = if ap.gpuvm_limit > ap.gpuvm_base {
-                (ap.gpuvm_limit - ap.gpuvm_base) + 1
-            } else {
-                0
-            };
+            let size = ap.gpuvm.size();
             if size > 0 {
                 props.push(HsaMemoryProperties {
                     heap_type: HSA_HEAPTYPE_DEVICE_SVM,
@@ -295,24 +682,98 @@ pub fn get_node_memory_properties(
                     flags: 0,
                     width: 0,
                     mem_clk_max: 0,
+                    virtual_base_address: 0,
                 });
             }
         }
 
-        if props.len() < num_banks as usize {
-            props.push(HsaMemoryProperties {
-                heap_type: HSA_HEAPTYPE_MMIO_REMAP,
-                size_in_bytes: 4096,
-                flags: 0,
-                width: 0,
-                mem_clk_max: 0,
-            });
+        if let Some(bank) = mmio_aperture(node.properties.kfd_gpu_id).and_then(mmio_remap_bank)
+            && props.len() < num_banks as usize
+        {
+            props.push(bank);
         }
     }
 
     Ok(props)
 }
 
+/// Records (or, with `None`, forgets) the MMIO remap page mapped for `gpu_id`.
+pub(crate) fn publish_mmio_aperture(gpu_id: u32, range: Option<VaRange>) {
+    let mut guard = MMIO_APERTURES.lock().unwrap();
+    let map = guard.get_or_insert_with(HashMap::new);
+    match range {
+        Some(range) => map.insert(gpu_id, range),
+        None => map.remove(&gpu_id),
+    };
+}
+
+/// Returns the MMIO remap (HDP flush) page of `gpu_id`.
+///
+/// The page is allocated with `ALLOC_MEM_FLAGS_MMIO_REMAP` when a memory manager is
+/// created, so this is `None` before that and on kernels that refuse the allocation.
+///
+/// # Panics
+/// Panics if the internal mutex is poisoned.
+#[must_use]
+pub fn mmio_aperture(gpu_id: u32) -> Option<VaRange> {
+    MMIO_APERTURES
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|map| map.get(&gpu_id).copied())
+}
+
+/// The `MMIO_REMAP` memory bank describing `range`, `None` for an empty range.
+#[must_use]
+pub const fn mmio_remap_bank(range: VaRange) -> Option<HsaMemoryProperties> {
+    if range.is_empty() {
+        return None;
+    }
+    Some(HsaMemoryProperties {
+        heap_type: HSA_HEAPTYPE_MMIO_REMAP,
+        size_in_bytes: range.size(),
+        flags: 0,
+        width: 0,
+        mem_clk_max: 0,
+        virtual_base_address: range.base,
+    })
+}
+
+/// Returns the process apertures of a GPU node.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+pub fn get_node_apertures(node_id: u32) -> io::Result<NodeApertures> {
+    let topo = acquired_topology()?;
+
+    topo.node_apertures(node_id)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+/// Classifies a GPU virtual address of `node_id`, e.g. to report where a fault hit.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+pub fn classify_address(node_id: u32, va: u64) -> io::Result<AddressKind> {
+    let topo = acquired_topology()?;
+
+    Ok(topo.classify_address(node_id, va))
+}
+
+/// Filters `node_ids` down to GPUs that can be mapped in one ioctl, see
+/// [`Topology::mappable_group`].
+///
+/// # Errors
+/// Fails with `NotConnected` if the topology was not acquired.
+///
+/// # Panics
+/// Panics if the global topology mutex is poisoned.
+pub fn mappable_group(node_ids: &[u32]) -> io::Result<Vec<u32>> {
+    let topo = acquired_topology()?;
+
+    Ok(topo.mappable_group(node_ids))
+}
+
 /// Returns cache properties.
 ///
 /// # Panics
@@ -322,12 +783,7 @@ pub fn get_node_cache_properties(
     _proc_id: u32,
     num_caches: u32,
 ) -> io::Result<Vec<HsaCacheProperties>> {
-    let topo = GLOBAL_TOPOLOGY
-        .lock()
-        .unwrap()
-        .as_ref()
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?
-        .clone();
+    let topo = acquired_topology()?;
 
     let node = topo
         .inner
@@ -347,12 +803,7 @@ pub fn get_node_io_link_properties(
     node_id: u32,
     num_links: u32,
 ) -> io::Result<Vec<HsaIoLinkProperties>> {
-    let topo = GLOBAL_TOPOLOGY
-        .lock()
-        .unwrap()
-        .as_ref()
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?
-        .clone();
+    let topo = acquired_topology()?;
 
     let node = topo
         .inner
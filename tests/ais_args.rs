@@ -0,0 +1,48 @@
+use hsa_rs::kfd::ioctl::{AisArgs, AisArgsUnion, AisInArgs, AisOutArgs, KFD_IOC_AIS_OP_READ};
+
+fn completed(size_copied: u64, status: i32) -> AisArgs {
+    AisArgs {
+        data: AisArgsUnion {
+            out: AisOutArgs {
+                size_copied,
+                status,
+                pad: 0,
+            },
+        },
+    }
+}
+
+#[test]
+fn input_half_is_filled_in() {
+    let args = AisArgs::new(AisInArgs {
+        handle: 0xdead_beef,
+        handle_offset: 0x1000,
+        file_offset: -1,
+        size: 0x20_0000,
+        op: KFD_IOC_AIS_OP_READ,
+        fd: 7,
+    });
+    let input = unsafe { args.data.in_ };
+    assert_eq!(input.handle, 0xdead_beef);
+    assert_eq!(input.handle_offset, 0x1000);
+    assert_eq!(input.file_offset, -1);
+    assert_eq!(input.size, 0x20_0000);
+    assert_eq!(input.op, KFD_IOC_AIS_OP_READ);
+    assert_eq!(input.fd, 7);
+    assert_eq!(size_of::<AisArgs>(), 40);
+}
+
+#[test]
+fn success_reports_bytes_copied() {
+    assert_eq!(completed(4096, 0).result().unwrap(), 4096);
+    assert_eq!(completed(0, 0).result().unwrap(), 0);
+}
+
+#[test]
+fn negative_status_is_the_errno() {
+    let err = completed(0, -libc::EIO).result().unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EIO));
+
+    let err = completed(123, -libc::EINVAL).result().unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+}
@@ -0,0 +1,19 @@
+use hsa_rs::thunk::memory::manager::AllocFlags;
+
+#[test]
+fn mmap_prot_follows_access_flags() {
+    let rw = AllocFlags::new();
+    assert_eq!(rw.mmap_prot(), libc::PROT_READ | libc::PROT_WRITE);
+
+    let ro = AllocFlags::new().read_only();
+    assert_eq!(ro.mmap_prot(), libc::PROT_READ);
+
+    let rwx = AllocFlags::new().executable();
+    assert_eq!(
+        rwx.mmap_prot(),
+        libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC
+    );
+
+    let rx = AllocFlags::new().read_only().executable();
+    assert_eq!(rx.mmap_prot(), libc::PROT_READ | libc::PROT_EXEC);
+}
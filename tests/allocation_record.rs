@@ -0,0 +1,35 @@
+use hsa_rs::thunk::memory::manager::{AllocFlags, AllocationRecord};
+
+fn record(gpu_va: u64, size: usize) -> AllocationRecord {
+    AllocationRecord {
+        gpu_va,
+        cpu_addr: 0,
+        size,
+        handle: 0,
+        node_id: 0,
+        flags: AllocFlags::default(),
+        is_userptr: false,
+        mapped_gpus: Vec::new(),
+    }
+}
+
+#[test]
+fn contains_va_is_half_open() {
+    let r = record(0x1000, 0x2000);
+    assert!(!r.contains_va(0xfff));
+    assert!(r.contains_va(0x1000));
+    assert!(r.contains_va(0x2fff));
+    assert!(!r.contains_va(0x3000));
+}
+
+#[test]
+fn empty_record_contains_nothing() {
+    assert!(!record(0x1000, 0).contains_va(0x1000));
+}
+
+#[test]
+fn record_at_top_of_address_space() {
+    let r = record(u64::MAX - 0xfff, 0x1000);
+    assert!(r.contains_va(u64::MAX));
+    assert!(!r.contains_va(u64::MAX - 0x1000));
+}
@@ -0,0 +1,177 @@
+use hsa_rs::thunk::memory::aperture::Aperture;
+use hsa_rs::thunk::memory::manager::aligned_allocation_size;
+use hsa_rs::thunk::memory::{ApertureAllocator, MemoryError};
+
+const MIB: u64 = 1024 * 1024;
+
+#[test]
+fn reservations_are_reported_and_consume_free_bytes() {
+    // 64 MiB aperture, 4 KiB alignment, one guard page on each side of a reservation.
+    let mut ap = Aperture::new(0x1000_0000, 0x1000_0000 + 64 * MIB - 1, 4096, 1);
+    let initial = ap.free_bytes();
+    assert_eq!(initial, 64 * MIB);
+
+    let a = ap.allocate_va(MIB as usize, 0).unwrap();
+    let b = ap.allocate_va(2 * MIB as usize, 0).unwrap();
+    assert_eq!(ap.free_bytes(), initial - 3 * MIB - 4 * 4096);
+
+    let mut expected = vec![(a, MIB as usize), (b, 2 * MIB as usize)];
+    expected.sort_unstable();
+    assert_eq!(ap.iter_reserved().collect::<Vec<_>>(), expected);
+
+    ap.free_va(a, MIB as usize);
+    assert_eq!(ap.free_bytes(), initial - 2 * MIB - 2 * 4096);
+    assert_eq!(
+        ap.iter_reserved().collect::<Vec<_>>(),
+        vec![(b, 2 * MIB as usize)]
+    );
+}
+
+#[test]
+fn small_allocation_with_huge_alignment_reserves_the_full_alignment() {
+    const TWO_MIB: usize = 2 * MIB as usize;
+    let size = aligned_allocation_size(4097, TWO_MIB).unwrap();
+    assert_eq!(size, TWO_MIB);
+
+    let mut ap = Aperture::new(0x1000_0000, 0x1000_0000 + 64 * MIB - 1, 4096, 0);
+    let va = ap.allocate_va(size, TWO_MIB).unwrap();
+    assert!(va.is_multiple_of(TWO_MIB as u64));
+    assert_eq!(ap.iter_reserved().collect::<Vec<_>>(), vec![(va, TWO_MIB)]);
+}
+
+#[test]
+fn allocation_size_rounding() {
+    assert_eq!(aligned_allocation_size(0, 0), Some(4096));
+    assert_eq!(aligned_allocation_size(1, 16), Some(4096));
+    assert_eq!(aligned_allocation_size(8192, 4096), Some(8192));
+    assert_eq!(aligned_allocation_size(usize::MAX - 10, 4096), None);
+}
+
+#[test]
+fn full_width_aperture_does_not_overflow() {
+    let mut ap = Aperture::new(0, u64::MAX, 4096, 0);
+    assert_eq!(ap.size(), u64::MAX);
+    assert_eq!(ap.free_bytes(), u64::MAX);
+
+    let va = ap.allocate_va(MIB as usize, 0).unwrap();
+    ap.free_va(va, MIB as usize);
+    assert_eq!(ap.free_bytes(), u64::MAX);
+
+    let top = Aperture::new(u64::MAX - (MIB - 1), u64::MAX, 4096, 0);
+    assert_eq!(top.size(), MIB);
+}
+
+#[test]
+fn reserve_va_at_claims_the_exact_range() {
+    let base = 0x1000_0000;
+    let mut ap = Aperture::new(base, base + 16 * MIB - 1, 4096, 1);
+    let va = base + 4 * MIB;
+    ap.reserve_va_at(va, MIB as usize).unwrap();
+    assert_eq!(
+        ap.iter_reserved().collect::<Vec<_>>(),
+        vec![(va, MIB as usize)]
+    );
+
+    // Overlapping the range, or only its guard page, is refused.
+    assert!(matches!(
+        ap.reserve_va_at(va + MIB - 4096, 8192),
+        Err(MemoryError::RangeUnavailable { .. })
+    ));
+    assert!(matches!(
+        ap.reserve_va_at(va + MIB, 4096),
+        Err(MemoryError::RangeUnavailable { .. })
+    ));
+    ap.reserve_va_at(va + MIB + 2 * 4096, 4096).unwrap();
+
+    ap.free_va(va, MIB as usize);
+    ap.reserve_va_at(va, MIB as usize).unwrap();
+}
+
+#[test]
+fn unguarded_reservations_can_touch() {
+    let base = 0x1000_0000;
+    let mut ap = Aperture::new(base, base + 16 * MIB - 1, 4096, 1);
+    // Flush with the aperture base, where a leading guard page would not fit.
+    ap.reserve_va_unguarded(base, 4096).unwrap();
+    ap.reserve_va_unguarded(base + 4096, 8192).unwrap();
+    assert_eq!(
+        ap.iter_reserved().collect::<Vec<_>>(),
+        vec![(base, 4096), (base + 4096, 8192)]
+    );
+    assert_eq!(ap.free_bytes(), 16 * MIB - 3 * 4096);
+    assert!(matches!(
+        ap.reserve_va_unguarded(base + 8192, 4096),
+        Err(MemoryError::RangeUnavailable { .. })
+    ));
+
+    // A guarded neighbour still keeps its own guard page clear.
+    ap.reserve_va_at(base + 4 * 4096, 4096).unwrap();
+    assert_eq!(ap.free_bytes(), 16 * MIB - 6 * 4096);
+
+    ap.free_va(base, 4096);
+    ap.free_va(base + 4096, 8192);
+    ap.free_va(base + 4 * 4096, 4096);
+    assert_eq!(ap.free_bytes(), 16 * MIB);
+    assert_eq!(ap.iter_reserved().count(), 0);
+}
+
+#[test]
+fn reserve_va_at_rejects_bad_requests() {
+    let base = 0x1000_0000;
+    let mut ap = Aperture::new(base, base + 16 * MIB - 1, 4096, 1);
+    assert!(matches!(
+        ap.reserve_va_at(base + 0x800, 4096),
+        Err(MemoryError::UnalignedRequest)
+    ));
+    assert!(matches!(
+        ap.reserve_va_at(base + MIB, 100),
+        Err(MemoryError::UnalignedRequest)
+    ));
+    assert!(matches!(
+        ap.reserve_va_at(base + MIB, 0),
+        Err(MemoryError::UnalignedRequest)
+    ));
+    // The leading guard page would fall below the aperture.
+    assert!(ap.reserve_va_at(base, 4096).is_err());
+    assert!(ap.reserve_va_at(base + 16 * MIB, 4096).is_err());
+    assert!(ap.reserve_va_at(u64::MAX - 4095, 4096).is_err());
+    assert_eq!(ap.iter_reserved().count(), 0);
+}
+
+#[test]
+fn allocations_avoid_fixed_reservations() {
+    let base = 0x1000_0000;
+    let mut ap = Aperture::new(base, base + 4 * MIB - 1, 4096, 0);
+    ap.reserve_va_at(base, 2 * MIB as usize).unwrap();
+    let va = ap.allocate_va(MIB as usize, 0).unwrap();
+    assert!(va >= base + 2 * MIB);
+    assert!(ap.allocate_va(2 * MIB as usize, 0).is_none());
+}
+
+#[test]
+fn usable_bytes_excludes_guards_and_alignment_skew() {
+    let ap = Aperture::new(0x1000_0000, 0x1000_0000 + 64 * MIB - 1, 4096, 1);
+    assert_eq!(ap.usable_bytes(), 64 * MIB - 2 * 4096);
+
+    // A base 4 KiB past a 2 MiB boundary loses the rest of that 2 MiB to alignment.
+    let skewed = Aperture::new(0x1000_1000, 0x1000_1000 + 64 * MIB - 1, 2 * MIB, 0);
+    assert_eq!(skewed.usable_bytes(), 64 * MIB - (2 * MIB - 4096));
+}
+
+#[test]
+fn usable_bytes_tracks_reservations() {
+    let mut ap = Aperture::new(0x1000_0000, 0x1000_0000 + 64 * MIB - 1, 4096, 1);
+    let va = ap.allocate_va(MIB as usize, 0).unwrap();
+    assert!(ap.usable_bytes() < ap.free_bytes());
+    assert!(ap.usable_bytes() <= 64 * MIB - MIB - 4 * 4096);
+
+    ap.free_va(va, MIB as usize);
+    assert_eq!(ap.usable_bytes(), 64 * MIB - 2 * 4096);
+}
+
+#[test]
+fn usable_bytes_of_block_smaller_than_guards_is_zero() {
+    let ap = Aperture::new(0x1000_0000, 0x1000_0000 + 4096 - 1, 4096, 1);
+    assert_eq!(ap.free_bytes(), 4096);
+    assert_eq!(ap.usable_bytes(), 0);
+}
@@ -0,0 +1,33 @@
+use hsa_rs::kfd::ioctl::{KFD_IOC_CACHE_POLICY_COHERENT, KFD_IOC_CACHE_POLICY_NONCOHERENT};
+use hsa_rs::thunk::memory::CachePolicy;
+use hsa_rs::thunk::memory::aperture::Aperture;
+
+const MIB: u64 = 1024 * 1024;
+
+#[test]
+fn policy_args_cover_the_alternate_aperture() {
+    let alt = Aperture::new(0x4000_0000, 0x4000_0000 + 64 * MIB - 1, 4096, 0);
+    let args = CachePolicy::memory_policy_args(
+        11111,
+        CachePolicy::Coherent,
+        CachePolicy::NonCoherent,
+        &alt,
+    );
+    assert_eq!(args.gpu_id, 11111);
+    assert_eq!(args.alternate_aperture_base, 0x4000_0000);
+    assert_eq!(args.alternate_aperture_size, 64 * MIB);
+    assert_eq!(args.default_policy, KFD_IOC_CACHE_POLICY_COHERENT);
+    assert_eq!(args.alternate_policy, KFD_IOC_CACHE_POLICY_NONCOHERENT);
+    assert_eq!(args.misc_process_flag, 0);
+}
+
+#[test]
+fn empty_alternate_aperture_has_zero_size() {
+    let alt = Aperture::new(0x4000_0000, 0x4000_0000, 4096, 0);
+    let args =
+        CachePolicy::memory_policy_args(1, CachePolicy::NonCoherent, CachePolicy::Coherent, &alt);
+    assert_eq!(args.alternate_aperture_base, 0x4000_0000);
+    assert_eq!(args.alternate_aperture_size, 0);
+    assert_eq!(args.default_policy, KFD_IOC_CACHE_POLICY_NONCOHERENT);
+    assert_eq!(args.alternate_policy, KFD_IOC_CACHE_POLICY_COHERENT);
+}
@@ -0,0 +1,50 @@
+use hsa_rs::kfd::ioctl::GetClockCountersArgs;
+use hsa_rs::thunk::topology::ClockSync;
+use std::io;
+
+const SYS_FREQ: u64 = 1_000_000_000;
+
+fn counters(gpu: u64, system: u64) -> GetClockCountersArgs {
+    GetClockCountersArgs {
+        gpu_clock_counter: gpu,
+        system_clock_counter: system,
+        system_clock_freq: SYS_FREQ,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn measures_the_gpu_refclk_against_the_system_counter() {
+    // 10 ms apart: 10M system ticks at 1 GHz, 1M GPU ticks at a 100 MHz refclk.
+    let first = counters(1_000_000, 5_000_000_000);
+    let second = counters(2_000_000, 5_010_000_000);
+    let sync = ClockSync::from_samples(&first, &second).unwrap();
+
+    assert_eq!(sync.gpu_clock_freq, 100_000_000);
+    assert_eq!(sync.gpu_clock_counter, 2_000_000);
+    assert_eq!(sync.gpu_to_wall_ns(2_000_000), 5_010_000_000);
+    // 100 GPU ticks later is 1 us later, not 100 ns.
+    assert_eq!(sync.gpu_to_wall_ns(2_000_100), 5_010_001_000);
+    // Timestamps before the anchor map back onto the first sample.
+    assert_eq!(sync.gpu_to_wall_ns(1_000_000), 5_000_000_000);
+}
+
+#[test]
+fn tick_spans_convert_at_the_measured_rate() {
+    let sync = ClockSync::from_samples(&counters(0, 0), &counters(1_000_000, 10_000_000)).unwrap();
+    assert_eq!(sync.gpu_ticks_to_ns(0), 0);
+    assert_eq!(sync.gpu_ticks_to_ns(250), 2_500);
+    assert_eq!(sync.gpu_ticks_to_ns(u64::MAX), u64::MAX);
+}
+
+#[test]
+fn rejects_stalled_counters_and_missing_frequency() {
+    let first = counters(1_000, 1_000);
+    let err = ClockSync::from_samples(&first, &first).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut second = counters(2_000, 2_000);
+    second.system_clock_freq = 0;
+    let err = ClockSync::from_samples(&first, &second).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,88 @@
+use hsa_rs::kfd::ioctl::{CriuBoBucket, CriuDeviceBucket};
+use hsa_rs::thunk::criu::CriuImage;
+use std::io::ErrorKind;
+
+fn image() -> CriuImage {
+    CriuImage {
+        devices: vec![
+            CriuDeviceBucket {
+                user_gpu_id: 0x1111,
+                actual_gpu_id: 0x2222,
+                drm_fd: 5,
+                pad: 0,
+            },
+            CriuDeviceBucket {
+                user_gpu_id: 0x3333,
+                actual_gpu_id: 0x4444,
+                drm_fd: 6,
+                pad: 0,
+            },
+        ],
+        bos: vec![CriuBoBucket {
+            addr: 0x7f00_0000_0000,
+            size: 0x20_0000,
+            offset: 0x1000,
+            restored_offset: 0x2000,
+            gpu_id: 0x2222,
+            alloc_flags: 0x8000_0001,
+            dmabuf_fd: u32::MAX,
+            pad: 0,
+        }],
+        num_objects: 3,
+        priv_data: (0..=255).collect(),
+    }
+}
+
+#[test]
+fn round_trips() {
+    let original = image();
+    let bytes = original.to_bytes();
+    // Header, two device buckets, one BO bucket, private data.
+    assert_eq!(bytes.len(), 28 + 2 * 16 + 48 + 256);
+    assert_eq!(&bytes[..8], b"KFDCRIU1");
+
+    let parsed = CriuImage::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed.num_objects, 3);
+    assert_eq!(parsed.priv_data, original.priv_data);
+    assert_eq!(parsed.devices.len(), 2);
+    for (p, o) in parsed.devices.iter().zip(&original.devices) {
+        assert_eq!(
+            (p.user_gpu_id, p.actual_gpu_id, p.drm_fd),
+            (o.user_gpu_id, o.actual_gpu_id, o.drm_fd)
+        );
+    }
+    let (p, o) = (&parsed.bos[0], &original.bos[0]);
+    assert_eq!(
+        (p.addr, p.size, p.offset, p.restored_offset),
+        (o.addr, o.size, o.offset, o.restored_offset)
+    );
+    assert_eq!(
+        (p.gpu_id, p.alloc_flags, p.dmabuf_fd),
+        (o.gpu_id, o.alloc_flags, o.dmabuf_fd)
+    );
+}
+
+#[test]
+fn empty_image_round_trips() {
+    let bytes = CriuImage::default().to_bytes();
+    assert_eq!(bytes.len(), 28);
+    let parsed = CriuImage::from_bytes(&bytes).unwrap();
+    assert!(parsed.devices.is_empty() && parsed.bos.is_empty() && parsed.priv_data.is_empty());
+}
+
+#[test]
+fn rejects_bad_magic() {
+    let mut bytes = image().to_bytes();
+    bytes[0] = b'X';
+    let err = CriuImage::from_bytes(&bytes).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_truncated_images() {
+    let bytes = image().to_bytes();
+    for len in [0, 7, 20, 28, 60, bytes.len() - 1] {
+        let err = CriuImage::from_bytes(&bytes[..len]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "length {len}");
+    }
+}
@@ -0,0 +1,41 @@
+use hsa_rs::kfd::ioctl::{CrossMemoryCopyArgs, KFD_CROSS_MEMORY_RW_BIT, MemoryRange};
+
+#[test]
+fn pairs_become_ranges_in_order() {
+    let ranges = MemoryRange::from_pairs(&[(0x1000, 0x200), (0x8000, 0x10)]);
+    assert_eq!(
+        ranges,
+        [
+            MemoryRange {
+                va_addr: 0x1000,
+                size: 0x200,
+            },
+            MemoryRange {
+                va_addr: 0x8000,
+                size: 0x10,
+            },
+        ]
+    );
+    assert_eq!(size_of::<MemoryRange>(), 16);
+}
+
+#[test]
+fn read_args_point_at_the_range_arrays() {
+    let src = MemoryRange::from_pairs(&[(0x1000, 0x200), (0x8000, 0x10)]);
+    let dst = MemoryRange::from_pairs(&[(0x7f00_0000, 0x210)]);
+    let args = CrossMemoryCopyArgs::read_from(42, &src, &dst, 0);
+
+    assert_eq!(args.pid, 42);
+    assert_eq!(args.src_mem_range_array, src.as_ptr() as u64);
+    assert_eq!(args.src_mem_array_size, 2);
+    assert_eq!(args.dst_mem_range_array, dst.as_ptr() as u64);
+    assert_eq!(args.dst_mem_array_size, 1);
+    assert_eq!(args.bytes_copied, 0);
+}
+
+#[test]
+fn read_clears_the_write_bit() {
+    let args = CrossMemoryCopyArgs::read_from(1, &[], &[], KFD_CROSS_MEMORY_RW_BIT | 0x10);
+    assert_eq!(args.flags & KFD_CROSS_MEMORY_RW_BIT, 0);
+    assert_eq!(args.flags, 0x10);
+}
@@ -0,0 +1,28 @@
+use hsa_rs::thunk::queues::builder::{doorbell_offsets, doorbell_page_size};
+
+// mmap cookie shape used by KFD: doorbell type bits plus a GPU id, page aligned.
+const COOKIE: u64 = 0xC000_2B67_0000_0000;
+
+#[test]
+fn gfx8_doorbell_is_indexed_by_queue_id() {
+    let gfx = 80003;
+    assert_eq!(doorbell_page_size(gfx), 4096);
+
+    assert_eq!(doorbell_offsets(COOKIE, 0, gfx), (COOKIE, 0));
+    assert_eq!(doorbell_offsets(COOKIE, 5, gfx), (COOKIE, 20));
+    assert_eq!(doorbell_offsets(COOKIE, 1023, gfx), (COOKIE, 4092));
+}
+
+#[test]
+fn gfx9_doorbell_offset_comes_from_the_kernel() {
+    let gfx = 90010;
+    assert_eq!(doorbell_page_size(gfx), 8192);
+
+    // The queue id is ignored: the in-page offset is already part of kernel_offset.
+    assert_eq!(doorbell_offsets(COOKIE | 0x18, 3, gfx), (COOKIE, 0x18));
+    assert_eq!(doorbell_offsets(COOKIE | 0x1ff8, 0, gfx), (COOKIE, 0x1ff8));
+    assert_eq!(
+        doorbell_offsets(COOKIE | 0x2010, 0, gfx),
+        (COOKIE | 0x2000, 0x10)
+    );
+}
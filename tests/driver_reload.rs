@@ -0,0 +1,30 @@
+use hsa_rs::error::{DriverReloadedError, HsaError};
+use std::io;
+
+fn enodev() -> io::Error {
+    io::Error::from_raw_os_error(libc::ENODEV)
+}
+
+#[test]
+fn plain_enodev_is_an_io_error() {
+    // e.g. opening /dev/kfd on a machine without a GPU.
+    assert!(matches!(HsaError::from(enodev()), HsaError::Io(_)));
+}
+
+#[test]
+fn enodev_from_an_ioctl_means_the_driver_was_reloaded() {
+    assert!(matches!(
+        HsaError::from_ioctl(enodev()),
+        HsaError::DriverReloaded
+    ));
+    let other = io::Error::from_raw_os_error(libc::EINVAL);
+    assert!(matches!(HsaError::from_ioctl(other), HsaError::Io(_)));
+}
+
+#[test]
+fn cached_topology_reload_error_converts() {
+    // What the topology functions return once the sentinel ioctl on the cached fd fails.
+    let err: io::Error = DriverReloadedError.into();
+    assert!(err.raw_os_error().is_none());
+    assert!(matches!(HsaError::from(err), HsaError::DriverReloaded));
+}
@@ -0,0 +1,26 @@
+use hsa_rs::kfd::sysfs::EngineId;
+
+const fn gfx(major: u32, minor: u32, stepping: u32) -> EngineId {
+    EngineId {
+        major,
+        minor,
+        stepping,
+    }
+}
+
+#[test]
+fn xnack_targets() {
+    for id in [gfx(9, 0, 0), gfx(9, 0, 6), gfx(9, 0, 10), gfx(9, 4, 2)] {
+        assert!(id.has_xnack_target(), "{id:?}");
+    }
+    for stepping in 0..=3 {
+        assert!(gfx(10, 1, stepping).has_xnack_target());
+    }
+}
+
+#[test]
+fn no_xnack_targets() {
+    for id in [gfx(8, 0, 3), gfx(10, 3, 0), gfx(10, 1, 4), gfx(11, 0, 0)] {
+        assert!(!id.has_xnack_target(), "{id:?}");
+    }
+}
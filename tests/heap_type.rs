@@ -0,0 +1,44 @@
+use hsa_rs::kfd::sysfs::{HeapType, HsaMemoryProperties};
+use hsa_rs::thunk::topology::{
+    HSA_HEAPTYPE_DEVICE_SVM, HSA_HEAPTYPE_FRAME_BUFFER_PRIVATE, HSA_HEAPTYPE_FRAME_BUFFER_PUBLIC,
+    HSA_HEAPTYPE_GPU_GDS, HSA_HEAPTYPE_GPU_LDS, HSA_HEAPTYPE_GPU_SCRATCH, HSA_HEAPTYPE_MMIO_REMAP,
+    HSA_HEAPTYPE_SYSTEM,
+};
+
+fn bank(heap_type: u32) -> HsaMemoryProperties {
+    HsaMemoryProperties {
+        heap_type,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn every_heap_constant_decodes() {
+    let heaps = [
+        (HSA_HEAPTYPE_SYSTEM, HeapType::System),
+        (
+            HSA_HEAPTYPE_FRAME_BUFFER_PUBLIC,
+            HeapType::FrameBufferPublic,
+        ),
+        (
+            HSA_HEAPTYPE_FRAME_BUFFER_PRIVATE,
+            HeapType::FrameBufferPrivate,
+        ),
+        (HSA_HEAPTYPE_GPU_GDS, HeapType::GpuGds),
+        (HSA_HEAPTYPE_GPU_LDS, HeapType::GpuLds),
+        (HSA_HEAPTYPE_GPU_SCRATCH, HeapType::GpuScratch),
+        (HSA_HEAPTYPE_DEVICE_SVM, HeapType::DeviceSvm),
+        (HSA_HEAPTYPE_MMIO_REMAP, HeapType::MmioRemap),
+    ];
+    for (raw, heap) in heaps {
+        assert_eq!(bank(raw).heap(), Some(heap));
+        assert_eq!(heap as u32, raw);
+    }
+}
+
+#[test]
+fn unknown_heap_is_none() {
+    for raw in [HSA_HEAPTYPE_MMIO_REMAP + 1, u32::MAX] {
+        assert_eq!(bank(raw).heap(), None);
+    }
+}
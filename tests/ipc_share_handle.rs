@@ -0,0 +1,37 @@
+use hsa_rs::thunk::memory::manager::IpcShareHandle;
+
+#[test]
+fn encodes_little_endian_words_then_size() {
+    let handle = IpcShareHandle {
+        share_handle: [0x0403_0201, 0x0807_0605, 0x0c0b_0a09, 0x100f_0e0d],
+        size: 0x1122_3344_5566_7788,
+    };
+    let bytes = handle.to_bytes();
+    assert_eq!(bytes.len(), IpcShareHandle::ENCODED_LEN);
+    assert_eq!(&bytes[..16], &(1..=16).collect::<Vec<u8>>()[..]);
+    assert_eq!(
+        &bytes[16..24],
+        &[0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11]
+    );
+    assert_eq!(&bytes[24..], &[0; 8]);
+}
+
+#[test]
+fn round_trips() {
+    let handle = IpcShareHandle {
+        share_handle: [u32::MAX, 0, 0xdead_beef, 42],
+        size: 2 * 1024 * 1024,
+    };
+    assert_eq!(IpcShareHandle::from_bytes(&handle.to_bytes()), handle);
+}
+
+#[test]
+fn trailing_bytes_are_ignored_on_decode() {
+    let handle = IpcShareHandle {
+        share_handle: [1, 2, 3, 4],
+        size: 4096,
+    };
+    let mut bytes = handle.to_bytes();
+    bytes[24..].fill(0xff);
+    assert_eq!(IpcShareHandle::from_bytes(&bytes), handle);
+}
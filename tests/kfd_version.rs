@@ -0,0 +1,39 @@
+use hsa_rs::kfd::device::{KfdFeature, version_satisfies};
+use hsa_rs::kfd::ioctl::{KFD_IOCTL_MAJOR_VERSION, KFD_IOCTL_MINOR_VERSION};
+
+#[test]
+fn newer_minor_satisfies() {
+    assert!(version_satisfies((1, 14), (1, 14)));
+    assert!(version_satisfies((1, 18), (1, 14)));
+    assert!(!version_satisfies((1, 13), (1, 14)));
+}
+
+#[test]
+fn majors_must_match() {
+    assert!(!version_satisfies((2, 0), (1, 14)));
+    assert!(!version_satisfies((0, 99), (1, 0)));
+}
+
+#[test]
+fn features_are_within_the_bindings_version() {
+    let bindings = (KFD_IOCTL_MAJOR_VERSION, KFD_IOCTL_MINOR_VERSION);
+    for feature in [
+        KfdFeature::SmiEvents,
+        KfdFeature::Svm,
+        KfdFeature::Criu,
+        KfdFeature::AvailableMemory,
+        KfdFeature::ExportDmabuf,
+        KfdFeature::Debugger,
+        KfdFeature::ContiguousVram,
+        KfdFeature::SdmaByEngineId,
+    ] {
+        assert!(
+            version_satisfies(bindings, feature.min_version()),
+            "{feature:?}"
+        );
+    }
+    assert!(!version_satisfies(
+        (1, 12),
+        KfdFeature::Debugger.min_version()
+    ));
+}
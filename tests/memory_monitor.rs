@@ -0,0 +1,52 @@
+use hsa_rs::thunk::monitor::{MemoryMonitor, Trend};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Starts a monitor on GPU 1 (fed `readings`, one per sample) and GPU 2 (always failing),
+/// and returns once GPU 1 has run out of readings.
+fn monitor_with_readings(readings: &[u64]) -> MemoryMonitor {
+    let mut readings: VecDeque<u64> = readings.iter().copied().collect();
+    let (done, finished) = mpsc::channel();
+    let monitor = MemoryMonitor::with_source(vec![1, 2], Duration::from_millis(1), move |gpu| {
+        if gpu != 1 {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        readings.pop_front().ok_or_else(|| {
+            let _ = done.send(());
+            io::Error::from(io::ErrorKind::WouldBlock)
+        })
+    });
+    finished
+        .recv_timeout(Duration::from_secs(10))
+        .expect("sampler ran");
+    monitor
+}
+
+#[test]
+fn tracks_latest_low_water_mark_and_trend() {
+    let monitor = monitor_with_readings(&[1000, 800, 900]);
+    let sample = monitor.sample(1).unwrap();
+    assert_eq!(sample.available, 900);
+    assert_eq!(sample.low_water_mark, 800);
+    assert_eq!(sample.trend, Trend::Rising);
+    assert_eq!(monitor.latest().get(&1), Some(&900));
+}
+
+#[test]
+fn falling_and_steady_trends() {
+    let monitor = monitor_with_readings(&[1000, 600]);
+    assert_eq!(monitor.trend(1), Some(Trend::Falling));
+    assert_eq!(monitor.low_water_mark(1), Some(600));
+
+    let monitor = monitor_with_readings(&[700]);
+    assert_eq!(monitor.trend(1), Some(Trend::Steady));
+}
+
+#[test]
+fn failed_readings_leave_no_sample() {
+    let monitor = monitor_with_readings(&[1000]);
+    assert_eq!(monitor.sample(2), None);
+    assert!(!monitor.latest().contains_key(&2));
+}
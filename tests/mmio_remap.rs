@@ -0,0 +1,36 @@
+use hsa_rs::kfd::ioctl::{
+    KFD_IOC_ALLOC_MEM_FLAGS_COHERENT, KFD_IOC_ALLOC_MEM_FLAGS_MMIO_REMAP,
+    KFD_IOC_ALLOC_MEM_FLAGS_WRITABLE,
+};
+use hsa_rs::thunk::memory::manager::AllocFlags;
+use hsa_rs::thunk::topology::{HSA_HEAPTYPE_MMIO_REMAP, VaRange, mmio_aperture, mmio_remap_bank};
+
+#[test]
+fn bank_reports_the_mapped_page() {
+    let bank = mmio_remap_bank(VaRange::new(0x1_0000_0000, 0x1_0000_0fff)).expect("non-empty");
+    assert_eq!(bank.heap_type, HSA_HEAPTYPE_MMIO_REMAP);
+    assert_eq!(bank.virtual_base_address, 0x1_0000_0000);
+    assert_eq!(bank.size_in_bytes, 4096);
+}
+
+#[test]
+fn empty_range_has_no_bank() {
+    assert!(mmio_remap_bank(VaRange::new(0x1000, 0x1000)).is_none());
+    assert!(mmio_remap_bank(VaRange::default()).is_none());
+}
+
+#[test]
+fn no_aperture_without_a_memory_manager() {
+    assert_eq!(mmio_aperture(12345), None);
+}
+
+#[test]
+fn allocation_uses_the_mmio_remap_flag() {
+    let flags = AllocFlags::new().mmio_remap().coherent();
+    assert_eq!(
+        flags.to_kfd_ioctl_flags(),
+        KFD_IOC_ALLOC_MEM_FLAGS_MMIO_REMAP
+            | KFD_IOC_ALLOC_MEM_FLAGS_WRITABLE
+            | KFD_IOC_ALLOC_MEM_FLAGS_COHERENT
+    );
+}
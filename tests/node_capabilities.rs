@@ -0,0 +1,50 @@
+use hsa_rs::kfd::sysfs::{
+    HSA_CAP_AQL_QUEUE_DOUBLE_MAP, HSA_CAP_FLAGS_COHERENTHOSTACCESS, HSA_CAP_HOT_PLUGGABLE,
+    HSA_CAP_SVMAPI_SUPPORTED, HSA_CAP_TRAP_DEBUG_PRECISE_ALU_OPERATIONS_SUPPORTED,
+    HSA_CAP_WATCH_POINTS_SUPPORTED, HSA_CAP2_PER_SDMA_QUEUE_RESET_SUPPORTED, NodeCapabilities,
+};
+
+#[test]
+fn empty_words_decode_to_nothing() {
+    let caps = NodeCapabilities::from_raw(0, 0);
+    assert_eq!(caps, NodeCapabilities::default());
+}
+
+#[test]
+fn flag_bits_map_to_their_fields() {
+    let caps = NodeCapabilities::from_raw(
+        HSA_CAP_HOT_PLUGGABLE
+            | HSA_CAP_WATCH_POINTS_SUPPORTED
+            | HSA_CAP_AQL_QUEUE_DOUBLE_MAP
+            | HSA_CAP_SVMAPI_SUPPORTED
+            | HSA_CAP_FLAGS_COHERENTHOSTACCESS
+            | HSA_CAP_TRAP_DEBUG_PRECISE_ALU_OPERATIONS_SUPPORTED,
+        0,
+    );
+    assert!(caps.hot_pluggable);
+    assert!(caps.watch_points_supported);
+    assert!(caps.aql_queue_double_map);
+    assert!(caps.svm_api_supported);
+    assert!(caps.coherent_host_access);
+    assert!(caps.trap_debug_precise_alu_operations);
+    assert!(!caps.ats_present);
+    assert!(!caps.trap_debug_supported);
+    assert!(!caps.per_sdma_queue_reset);
+}
+
+#[test]
+fn multi_bit_fields_are_shifted_down() {
+    // 4 watch point address bits, doorbell type 2, ASIC revision 5.
+    let caps = NodeCapabilities::from_raw(0x0000_0400 | 0x0000_2000 | (5 << 22), 0);
+    assert_eq!(caps.watch_points_total_bits, 4);
+    assert_eq!(caps.doorbell_type, 2);
+    assert_eq!(caps.asic_revision, 5);
+}
+
+#[test]
+fn second_word_carries_sdma_queue_reset() {
+    let caps = NodeCapabilities::from_raw(0, HSA_CAP2_PER_SDMA_QUEUE_RESET_SUPPORTED);
+    assert!(caps.per_sdma_queue_reset);
+    // The same bit in the first word is hot plugging.
+    assert!(!caps.hot_pluggable);
+}
@@ -0,0 +1,73 @@
+use hsa_rs::kfd::sysfs::{EngineId, HsaNodeProperties, OccupancyLimiter, Topology};
+use std::path::Path;
+
+/// Vega20: 60 CUs of 4 SIMDs, 10 wave slots per SIMD, 256 KiB of VGPRs and 64 KiB of LDS
+/// per CU.
+fn vega20() -> HsaNodeProperties {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vega20_dgpu");
+    let topo = Topology::from_root(&root).expect("fixture parses");
+    topo.nodes[1].properties.clone()
+}
+
+#[test]
+fn trivial_kernel_is_limited_by_wave_slots() {
+    let est = vega20().occupancy(0, 0, 0, 0).unwrap();
+    assert_eq!(est.limiter, OccupancyLimiter::WaveSlots);
+    assert_eq!(est.waves_per_simd, 10);
+    assert_eq!(est.active_waves, 10 * 240);
+}
+
+#[test]
+fn each_resource_can_be_the_limiter() {
+    let gpu = vega20();
+
+    // 64 VGPRs x 64 lanes x 4 bytes = 16 KiB of the SIMD's 64 KiB.
+    let est = gpu.occupancy(64, 0, 0, 0).unwrap();
+    assert_eq!(
+        (est.limiter, est.waves_per_simd),
+        (OccupancyLimiter::Vgpr, 4)
+    );
+    assert_eq!(est.active_waves, 4 * 240);
+
+    // 1024 SGPRs x 4 bytes = 4 KiB of the SIMD's 8 KiB.
+    let est = gpu.occupancy(0, 1024, 0, 0).unwrap();
+    assert_eq!(
+        (est.limiter, est.waves_per_simd),
+        (OccupancyLimiter::Sgpr, 2)
+    );
+
+    // 8 KiB of the SIMD's 16 KiB share of LDS.
+    let est = gpu.occupancy(0, 0, 8192, 0).unwrap();
+    assert_eq!(
+        (est.limiter, est.waves_per_simd),
+        (OccupancyLimiter::Lds, 2)
+    );
+}
+
+#[test]
+fn narrower_waves_use_fewer_vgprs() {
+    let est = vega20().occupancy(64, 0, 0, 32).unwrap();
+    assert_eq!(
+        (est.limiter, est.waves_per_simd),
+        (OccupancyLimiter::Vgpr, 8)
+    );
+}
+
+#[test]
+fn sgprs_do_not_limit_gfx10() {
+    let gpu = HsaNodeProperties {
+        engine_id: EngineId {
+            major: 10,
+            minor: 3,
+            stepping: 0,
+        },
+        ..vega20()
+    };
+    let est = gpu.occupancy(0, 1024, 0, 0).unwrap();
+    assert_eq!(est.limiter, OccupancyLimiter::WaveSlots);
+}
+
+#[test]
+fn cpu_nodes_have_no_occupancy() {
+    assert_eq!(HsaNodeProperties::default().occupancy(32, 32, 0, 0), None);
+}
@@ -0,0 +1,30 @@
+use hsa_rs::kfd::ioctl::{
+    KFD_IOCTL_PCS_METHOD_HOSTTRAP, KFD_IOCTL_PCS_METHOD_STOCHASTIC,
+    KFD_IOCTL_PCS_TYPE_CLOCK_CYCLES, KFD_IOCTL_PCS_TYPE_TIME_US,
+};
+use hsa_rs::thunk::pc_sample::sample_info;
+
+#[test]
+fn host_trap_intervals_are_in_microseconds() {
+    let info = sample_info(250, KFD_IOCTL_PCS_METHOD_HOSTTRAP);
+    assert_eq!(info.interval, 250);
+    assert_eq!(info.method, KFD_IOCTL_PCS_METHOD_HOSTTRAP);
+    assert_eq!(info.type_, KFD_IOCTL_PCS_TYPE_TIME_US);
+}
+
+#[test]
+fn stochastic_intervals_are_in_clock_cycles() {
+    let info = sample_info(1 << 20, KFD_IOCTL_PCS_METHOD_STOCHASTIC);
+    assert_eq!(info.interval, 1 << 20);
+    assert_eq!(info.method, KFD_IOCTL_PCS_METHOD_STOCHASTIC);
+    assert_eq!(info.type_, KFD_IOCTL_PCS_TYPE_CLOCK_CYCLES);
+}
+
+#[test]
+fn capability_bounds_are_left_to_kfd() {
+    let info = sample_info(1, KFD_IOCTL_PCS_METHOD_HOSTTRAP);
+    assert_eq!(
+        (info.interval_min, info.interval_max, info.flags),
+        (0, 0, 0)
+    );
+}
@@ -0,0 +1,80 @@
+use hsa_rs::kfd::sysfs::{EngineId, HsaNodeProperties, Precision, flops_per_cu_per_clock};
+
+const fn gfx(major: u32, minor: u32, stepping: u32) -> EngineId {
+    EngineId {
+        major,
+        minor,
+        stepping,
+    }
+}
+
+#[test]
+fn mi200_rates() {
+    let mi200 = gfx(9, 0, 10);
+    assert_eq!(flops_per_cu_per_clock(mi200, Precision::Fp64), Some(128));
+    assert_eq!(flops_per_cu_per_clock(mi200, Precision::Fp32), Some(128));
+    assert_eq!(
+        flops_per_cu_per_clock(mi200, Precision::MatrixFp64),
+        Some(256)
+    );
+    assert_eq!(
+        flops_per_cu_per_clock(mi200, Precision::MatrixFp16),
+        Some(1024)
+    );
+}
+
+#[test]
+fn specific_gfx9_parts_win_over_the_family_default() {
+    assert_eq!(
+        flops_per_cu_per_clock(gfx(9, 0, 6), Precision::Fp64),
+        Some(64)
+    );
+    assert_eq!(
+        flops_per_cu_per_clock(gfx(9, 0, 0), Precision::Fp64),
+        Some(8)
+    );
+    assert_eq!(
+        flops_per_cu_per_clock(gfx(9, 4, 2), Precision::Fp32),
+        Some(256)
+    );
+}
+
+#[test]
+fn missing_units_and_unknown_parts_have_no_rate() {
+    // Vega has no matrix cores, RDNA3 has WMMA but no FP64 matrix unit.
+    assert_eq!(
+        flops_per_cu_per_clock(gfx(9, 0, 0), Precision::MatrixFp16),
+        None
+    );
+    assert_eq!(
+        flops_per_cu_per_clock(gfx(11, 0, 0), Precision::MatrixFp64),
+        None
+    );
+    assert_eq!(
+        flops_per_cu_per_clock(gfx(11, 0, 0), Precision::MatrixFp16),
+        Some(512)
+    );
+    assert_eq!(flops_per_cu_per_clock(gfx(7, 0, 0), Precision::Fp32), None);
+    assert_eq!(flops_per_cu_per_clock(gfx(0, 0, 0), Precision::Fp32), None);
+}
+
+#[test]
+fn peak_flops_scales_by_cus_and_clock() {
+    let props = HsaNodeProperties {
+        simd_count: 440,
+        simd_per_cu: 4,
+        max_engine_clk_fcompute: 1700,
+        engine_id: gfx(9, 0, 10),
+        // No such PCI device, so the KFD clock is used as is.
+        domain: 0xffff,
+        ..Default::default()
+    };
+    assert_eq!(
+        props.peak_flops(Precision::Fp64),
+        Some(110 * 1700 * 1_000_000 * 128)
+    );
+    assert_eq!(
+        HsaNodeProperties::default().peak_flops(Precision::Fp32),
+        None
+    );
+}
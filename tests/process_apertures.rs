@@ -0,0 +1,29 @@
+use hsa_rs::kfd::ioctl::{GetProcessAperturesArgs, NUM_OF_SUPPORTED_GPUS};
+
+fn legacy_args(num_of_nodes: u32) -> GetProcessAperturesArgs {
+    let mut args = GetProcessAperturesArgs {
+        num_of_nodes,
+        ..Default::default()
+    };
+    for (i, ap) in args.process_apertures.iter_mut().enumerate() {
+        ap.gpu_id = 1000 + i as u32;
+    }
+    args
+}
+
+#[test]
+fn keeps_the_reported_apertures() {
+    let args = legacy_args(2);
+    let ids: Vec<u32> = args.filled().iter().map(|ap| ap.gpu_id).collect();
+    assert_eq!(ids, [1000, 1001]);
+}
+
+#[test]
+fn no_gpus_yields_nothing() {
+    assert!(legacy_args(0).filled().is_empty());
+}
+
+#[test]
+fn count_is_clamped_to_the_fixed_array() {
+    assert_eq!(legacy_args(100).filled().len(), NUM_OF_SUPPORTED_GPUS);
+}
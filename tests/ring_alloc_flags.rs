@@ -0,0 +1,51 @@
+use hsa_rs::kfd::ioctl::{
+    KFD_IOC_ALLOC_MEM_FLAGS_AQL_QUEUE_MEM, KFD_IOC_ALLOC_MEM_FLAGS_GTT,
+    KFD_IOC_ALLOC_MEM_FLAGS_VRAM,
+};
+use hsa_rs::thunk::memory::ApertureAllocator;
+use hsa_rs::thunk::memory::aperture::Aperture;
+use hsa_rs::thunk::queues::builder::{QueueType, ring_alloc_flags};
+
+#[test]
+fn aql_ring_is_tagged_as_aql_queue_memory() {
+    for vram in [true, false] {
+        let flags = ring_alloc_flags(QueueType::ComputeAql, vram).to_kfd_ioctl_flags();
+        assert_ne!(flags & KFD_IOC_ALLOC_MEM_FLAGS_AQL_QUEUE_MEM, 0);
+    }
+}
+
+#[test]
+fn pm4_ring_is_not_tagged() {
+    for vram in [true, false] {
+        let flags = ring_alloc_flags(QueueType::Compute, vram).to_kfd_ioctl_flags();
+        assert_eq!(flags & KFD_IOC_ALLOC_MEM_FLAGS_AQL_QUEUE_MEM, 0);
+    }
+}
+
+#[test]
+fn ring_placement_is_preserved() {
+    let vram = ring_alloc_flags(QueueType::ComputeAql, true).to_kfd_ioctl_flags();
+    assert_ne!(vram & KFD_IOC_ALLOC_MEM_FLAGS_VRAM, 0);
+    let gtt = ring_alloc_flags(QueueType::ComputeAql, false).to_kfd_ioctl_flags();
+    assert_ne!(gtt & KFD_IOC_ALLOC_MEM_FLAGS_GTT, 0);
+}
+
+#[test]
+fn aql_ring_reserves_twice_its_size() {
+    const RING: usize = 64 * 1024;
+    let aql = ring_alloc_flags(QueueType::ComputeAql, false);
+    let pm4 = ring_alloc_flags(QueueType::Compute, false);
+    assert_eq!(aql.va_reservation_size(RING), Some(2 * RING));
+    assert_eq!(pm4.va_reservation_size(RING), Some(RING));
+    assert_eq!(aql.va_reservation_size(usize::MAX), None);
+
+    // The second mapping at va + size must stay inside the reservation, clear of the guard
+    // page and the next allocation.
+    let mut ap = Aperture::new(0x1000_0000, 0x1000_0000 + (16 << 20) - 1, 4096, 1);
+    let ring = ap
+        .allocate_va(aql.va_reservation_size(RING).unwrap(), 4096)
+        .unwrap();
+    let next = ap.allocate_va(RING, 4096).unwrap();
+    assert_eq!(ap.iter_reserved().next(), Some((ring, 2 * RING)));
+    assert!(ring + 2 * RING as u64 + 4096 <= next);
+}
@@ -0,0 +1,28 @@
+use hsa_rs::kfd::device::{RuntimeMode, runtime_enable_error};
+use hsa_rs::kfd::ioctl::{
+    KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK, KFD_RUNTIME_ENABLE_MODE_TTMP_SAVE_MASK,
+};
+use std::io;
+
+#[test]
+fn mode_masks() {
+    assert_eq!(RuntimeMode::Disable.mode_mask(), 0);
+    assert_eq!(
+        RuntimeMode::Enable.mode_mask(),
+        KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK
+    );
+    assert_eq!(
+        RuntimeMode::EnableWithTtmpSave.mode_mask(),
+        KFD_RUNTIME_ENABLE_MODE_ENABLE_MASK | KFD_RUNTIME_ENABLE_MODE_TTMP_SAVE_MASK
+    );
+}
+
+#[test]
+fn busy_error_is_explained() {
+    let raw = io::Error::from_raw_os_error(libc::EBUSY);
+    let err = runtime_enable_error(&raw);
+    assert_eq!(err.kind(), raw.kind());
+    let msg = err.to_string();
+    assert!(msg.starts_with("AMDKFD_IOC_RUNTIME_ENABLE failed"), "{msg}");
+    assert!(msg.contains("already enabled"), "{msg}");
+}
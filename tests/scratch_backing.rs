@@ -0,0 +1,15 @@
+use hsa_rs::thunk::memory::ScratchBacking;
+
+#[test]
+fn ioctl_receives_the_buffer_va_and_gpu_id() {
+    let backing = ScratchBacking {
+        va: 0x7fff_0000_0000,
+        size: 4 << 20,
+        handle: 0xdead_beef,
+        gpu_id: 45261,
+    };
+    let args = backing.ioctl_args();
+    assert_eq!(args.va_addr, 0x7fff_0000_0000);
+    assert_eq!(args.gpu_id, 45261);
+    assert_eq!(args.pad, 0);
+}
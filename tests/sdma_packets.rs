@@ -0,0 +1,68 @@
+use hsa_rs::thunk::queues::sdma::{
+    SDMA_ATOMIC_ADD64, SDMA_MAX_LINEAR_COPY_SIZE, SDMA_OP_ATOMIC, SDMA_OP_COPY, SDMA_OP_TRAP,
+    atomic_add64, copy_linear, encode_linear_copy, trap,
+};
+
+const GFX9: u32 = 90000;
+const GFX8: u32 = 80003;
+
+/// `(count, src, dst)` of a `COPY_LINEAR` packet.
+fn decode(packet: &[u32; 7]) -> (u32, u64, u64) {
+    let src = u64::from(packet[3]) | (u64::from(packet[4]) << 32);
+    let dst = u64::from(packet[5]) | (u64::from(packet[6]) << 32);
+    (packet[1], src, dst)
+}
+
+#[test]
+fn copy_count_is_bytes_minus_one_from_gfx9() {
+    assert_eq!(copy_linear(0, 0, 256, GFX9)[1], 255);
+    assert_eq!(copy_linear(0, 0, 256, GFX8)[1], 256);
+    assert_eq!(copy_linear(0, 0, 256, GFX9)[0], SDMA_OP_COPY);
+}
+
+#[test]
+fn copy_splits_at_the_count_limit() {
+    let (src, dst) = (0x1_0000_0000, 0x8000_1000);
+    let size = 2 * SDMA_MAX_LINEAR_COPY_SIZE + 100;
+    let packets = encode_linear_copy(dst, src, size, GFX9);
+
+    let max = SDMA_MAX_LINEAR_COPY_SIZE;
+    let expected = [
+        (max as u32 - 1, src, dst),
+        (max as u32 - 1, src + max, dst + max),
+        (99, src + 2 * max, dst + 2 * max),
+    ];
+    assert_eq!(packets.iter().map(decode).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn copy_of_exactly_the_limit_is_one_packet() {
+    let packets = encode_linear_copy(0, 0, SDMA_MAX_LINEAR_COPY_SIZE, GFX8);
+    assert_eq!(packets.len(), 1);
+    assert_eq!(u64::from(packets[0][1]), SDMA_MAX_LINEAR_COPY_SIZE);
+    assert!(encode_linear_copy(0, 0, 0, GFX9).is_empty());
+}
+
+#[test]
+fn atomic_add64_layout() {
+    let packet = atomic_add64(0x1234_5678_9abc_def0, -1);
+    assert_eq!(
+        packet,
+        [
+            SDMA_OP_ATOMIC | (SDMA_ATOMIC_ADD64 << 25),
+            0x9abc_def0,
+            0x1234_5678,
+            u32::MAX,
+            u32::MAX,
+            0,
+            0,
+            0,
+        ]
+    );
+}
+
+#[test]
+fn trap_layout_masks_the_event_id() {
+    assert_eq!(trap(42), [SDMA_OP_TRAP, 42]);
+    assert_eq!(trap(0xffff_ffff), [SDMA_OP_TRAP, 0x0fff_ffff]);
+}
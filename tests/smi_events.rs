@@ -0,0 +1,89 @@
+use hsa_rs::kfd::ioctl::{
+    KFD_SMI_EVENT_ALL_PROCESS, KFD_SMI_EVENT_MIGRATE_START, KFD_SMI_EVENT_NONE,
+    KFD_SMI_EVENT_THERMAL_THROTTLE, KFD_SMI_EVENT_VMFAULT, kfd_smi_event_mask_from_index,
+};
+use hsa_rs::thunk::smi::{SmiEvent, SmiEventStream};
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::os::fd::FromRawFd;
+
+fn pipe() -> (File, File) {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+}
+
+#[test]
+fn mask_bits_start_at_event_one() {
+    assert_eq!(kfd_smi_event_mask_from_index(KFD_SMI_EVENT_VMFAULT), 1);
+    assert_eq!(
+        kfd_smi_event_mask_from_index(KFD_SMI_EVENT_THERMAL_THROTTLE),
+        2
+    );
+    assert_eq!(
+        kfd_smi_event_mask_from_index(KFD_SMI_EVENT_ALL_PROCESS),
+        1 << 63
+    );
+}
+
+#[test]
+fn mask_of_invalid_index_is_empty() {
+    assert_eq!(kfd_smi_event_mask_from_index(KFD_SMI_EVENT_NONE), 0);
+    assert_eq!(kfd_smi_event_mask_from_index(65), 0);
+    assert_eq!(kfd_smi_event_mask_from_index(u32::MAX), 0);
+}
+
+#[test]
+fn parses_thermal_throttle() {
+    let event = SmiEvent::parse(7, "2 1f:42\n").expect("valid line");
+    assert_eq!(event.event_id, KFD_SMI_EVENT_THERMAL_THROTTLE);
+    assert_eq!(event.gpu_id, 7);
+    assert_eq!(event.payload, "1f:42");
+    assert!(event.is_thermal_throttle());
+    assert_eq!(event.throttle_bitmask(), Some(0x1f));
+}
+
+#[test]
+fn parses_hex_ids_and_bare_events() {
+    let event = SmiEvent::parse(1, "5").expect("valid line");
+    assert_eq!(event.event_id, KFD_SMI_EVENT_MIGRATE_START);
+    assert!(event.is_migration());
+    assert!(event.payload.is_empty());
+
+    let event = SmiEvent::parse(1, "a 1234:task").expect("valid line");
+    assert_eq!(event.event_id, 10);
+    assert_eq!(event.throttle_bitmask(), None);
+}
+
+#[test]
+fn rejects_lines_without_an_id() {
+    assert_eq!(SmiEvent::parse(1, ""), None);
+    assert_eq!(SmiEvent::parse(1, "vmfault 1:x"), None);
+}
+
+#[test]
+fn stream_splits_lines_and_skips_garbage() {
+    let (rx, mut tx) = pipe();
+    let mut stream = SmiEventStream::from_file(rx, 3);
+    tx.write_all(b"1 100:app\nbogus\n2 4:").unwrap();
+
+    let event = stream.poll(0).unwrap().expect("first line complete");
+    assert!(event.is_vm_fault());
+    assert_eq!(event.gpu_id, 3);
+    // The throttle event is still missing its newline.
+    assert_eq!(stream.poll(0).unwrap(), None);
+
+    tx.write_all(b"9\n").unwrap();
+    let event = stream.poll(0).unwrap().expect("second line complete");
+    assert_eq!(event.payload, "4:9");
+}
+
+#[test]
+fn closed_stream_ends_instead_of_spinning() {
+    let (rx, tx) = pipe();
+    let mut stream = SmiEventStream::from_file(rx, 3);
+    drop(tx);
+
+    let err = stream.next_event().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,55 @@
+use hsa_rs::kfd::ioctl::SpmBufferHeader;
+use hsa_rs::thunk::spm::SpmChunk;
+use std::mem;
+
+const HEADER_LEN: usize = mem::size_of::<SpmBufferHeader>();
+
+/// A destination buffer starting with a header, followed by `payload`.
+fn with_header(version: u32, bytes_copied: u32, has_data_loss: u32, payload: &[u8]) -> Vec<u8> {
+    let mut buffer = vec![0u8; HEADER_LEN];
+    buffer[0..4].copy_from_slice(&version.to_ne_bytes());
+    buffer[4..8].copy_from_slice(&bytes_copied.to_ne_bytes());
+    buffer[8..12].copy_from_slice(&has_data_loss.to_ne_bytes());
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+#[test]
+fn headerless_buffer_uses_ioctl_count() {
+    let buffer = [1u8, 2, 3, 4, 5, 6];
+    let chunk = SpmChunk::parse(&buffer, 4, true);
+    assert_eq!(chunk.data, &[1, 2, 3, 4]);
+    assert!(chunk.has_data_loss);
+}
+
+#[test]
+fn ioctl_count_is_clamped_to_buffer() {
+    let buffer = [7u8; 3];
+    let chunk = SpmChunk::parse(&buffer, 100, false);
+    assert_eq!(chunk.data.len(), 3);
+    assert!(!chunk.has_data_loss);
+}
+
+#[test]
+fn zero_version_header_is_treated_as_data() {
+    let buffer = with_header(0, 2, 1, &[9, 9]);
+    let chunk = SpmChunk::parse(&buffer, buffer.len() as u32, false);
+    assert_eq!(chunk.data.len(), HEADER_LEN + 2);
+    assert!(!chunk.has_data_loss);
+}
+
+#[test]
+fn versioned_header_takes_precedence() {
+    let buffer = with_header(1, 3, 1, &[10, 11, 12, 13]);
+    let chunk = SpmChunk::parse(&buffer, buffer.len() as u32, false);
+    assert_eq!(chunk.data, &[10, 11, 12]);
+    assert!(chunk.has_data_loss);
+}
+
+#[test]
+fn header_count_is_clamped_to_filled_bytes() {
+    let buffer = with_header(1, 1000, 0, &[1, 2]);
+    let chunk = SpmChunk::parse(&buffer, (HEADER_LEN + 1) as u32, false);
+    assert_eq!(chunk.data, &[1]);
+    assert!(!chunk.has_data_loss);
+}
@@ -0,0 +1,49 @@
+use hsa_rs::kfd::ioctl::{
+    KFD_IOCTL_SVM_ATTR_PREFERRED_LOC, KFD_IOCTL_SVM_ATTR_PREFETCH_LOC,
+    KFD_IOCTL_SVM_LOCATION_SYSMEM, KFD_IOCTL_SVM_OP_SET_ATTR, SvmArgs, SvmAttribute,
+};
+
+const fn split(word: u64) -> (u32, u32) {
+    (word as u32, (word >> 32) as u32)
+}
+
+#[test]
+fn header_is_followed_by_the_attributes() {
+    let attrs = [
+        SvmAttribute {
+            type_: KFD_IOCTL_SVM_ATTR_PREFERRED_LOC,
+            value: 0x1234,
+        },
+        SvmAttribute {
+            type_: KFD_IOCTL_SVM_ATTR_PREFETCH_LOC,
+            value: KFD_IOCTL_SVM_LOCATION_SYSMEM,
+        },
+    ];
+    let buf = SvmArgs::buffer(
+        0x7f00_0000_0000,
+        0x20_0000,
+        KFD_IOCTL_SVM_OP_SET_ATTR,
+        &attrs,
+    );
+
+    // 24-byte header plus two 8-byte attributes.
+    assert_eq!(buf.len(), 5);
+    assert_eq!(buf[0], 0x7f00_0000_0000);
+    assert_eq!(buf[1], 0x20_0000);
+    assert_eq!(split(buf[2]), (KFD_IOCTL_SVM_OP_SET_ATTR, 2));
+    assert_eq!(split(buf[3]), (KFD_IOCTL_SVM_ATTR_PREFERRED_LOC, 0x1234));
+    assert_eq!(
+        split(buf[4]),
+        (
+            KFD_IOCTL_SVM_ATTR_PREFETCH_LOC,
+            KFD_IOCTL_SVM_LOCATION_SYSMEM
+        )
+    );
+}
+
+#[test]
+fn header_only_without_attributes() {
+    let buf = SvmArgs::buffer(0x1000, 0x1000, KFD_IOCTL_SVM_OP_SET_ATTR, &[]);
+    assert_eq!(buf.len(), 3);
+    assert_eq!(split(buf[2]).1, 0);
+}
@@ -0,0 +1,49 @@
+use hsa_rs::kfd::ioctl::{
+    GetTileConfigArgs, KFD_MAX_MACRO_TILE_CONFIGS, KFD_MAX_TILE_CONFIGS, TileConfig,
+};
+
+fn full_buffers() -> (Vec<u32>, Vec<u32>) {
+    (
+        (0..KFD_MAX_TILE_CONFIGS as u32).collect(),
+        (100..100 + KFD_MAX_MACRO_TILE_CONFIGS as u32).collect(),
+    )
+}
+
+#[test]
+fn keeps_only_the_entries_kfd_wrote() {
+    let (tiles, macro_tiles) = full_buffers();
+    let args = GetTileConfigArgs {
+        num_tile_configs: 3,
+        num_macro_tile_configs: 2,
+        gb_addr_config: 0x2601_0042,
+        num_banks: 16,
+        num_ranks: 2,
+        ..Default::default()
+    };
+    let config = TileConfig::from_args(&args, tiles, macro_tiles);
+    assert_eq!(config.tile_config, [0, 1, 2]);
+    assert_eq!(config.macro_tile_config, [100, 101]);
+    assert_eq!(config.gb_addr_config, 0x2601_0042);
+    assert_eq!(config.num_banks, 16);
+    assert_eq!(config.num_ranks, 2);
+}
+
+#[test]
+fn gfx9_reports_empty_tables() {
+    let (tiles, macro_tiles) = full_buffers();
+    let config = TileConfig::from_args(&GetTileConfigArgs::default(), tiles, macro_tiles);
+    assert!(config.tile_config.is_empty());
+    assert!(config.macro_tile_config.is_empty());
+}
+
+#[test]
+fn count_beyond_the_buffer_keeps_the_whole_buffer() {
+    let args = GetTileConfigArgs {
+        num_tile_configs: 64,
+        num_macro_tile_configs: 64,
+        ..Default::default()
+    };
+    let config = TileConfig::from_args(&args, vec![7; 4], vec![9; 2]);
+    assert_eq!(config.tile_config, [7; 4]);
+    assert_eq!(config.macro_tile_config, [9; 2]);
+}
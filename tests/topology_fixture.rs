@@ -0,0 +1,178 @@
+use hsa_rs::kfd::sysfs::{HSA_IOLINKTYPE_PCIEXPRESS, HSA_IOLINKTYPE_XGMI, HeapType, Topology};
+use std::path::Path;
+
+fn fixture(name: &str) -> Topology {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    Topology::from_root(&root).expect("fixture parses")
+}
+
+/// One CPU node with two GPUs, each on PCIe to the CPU and on XGMI to each other.
+fn xgmi_2gpu() -> Topology {
+    fixture("xgmi_2gpu")
+}
+
+#[test]
+fn parses_nodes_and_banks() {
+    let topo = xgmi_2gpu();
+    assert_eq!(topo.nodes.len(), 3);
+    assert_eq!(topo.system_props.num_nodes, 3);
+
+    let cpu = &topo.nodes[0];
+    assert_eq!(cpu.properties.cpu_cores_count, 16);
+    assert_eq!(cpu.mem_banks.len(), 1);
+    assert_eq!(cpu.mem_banks[0].heap(), Some(HeapType::System));
+
+    for (node_id, gpu_id) in [(1, 11111), (2, 22222)] {
+        let gpu = &topo.nodes[node_id];
+        assert_eq!(gpu.properties.node_id, node_id as u32);
+        assert_eq!(gpu.properties.kfd_gpu_id, gpu_id);
+        assert_eq!(gpu.mem_banks.len(), 1);
+        assert_eq!(gpu.mem_banks[0].heap(), Some(HeapType::FrameBufferPrivate));
+    }
+}
+
+#[test]
+fn converts_cache_size_to_bytes() {
+    let topo = xgmi_2gpu();
+    let l3 = &topo.nodes[0].caches[0];
+    assert_eq!(l3.cache_level, 3);
+    assert_eq!(l3.cache_size, 32 * 1024 * 1024);
+    assert_eq!(l3.sibling_map.len(), 16);
+    assert_eq!(l3.sibling_map.iter().filter(|&&b| b != 0).count(), 8);
+}
+
+#[test]
+fn decodes_cache_siblings() {
+    let topo = xgmi_2gpu();
+    let l3 = &topo.nodes[0].caches[0];
+    assert_eq!(l3.sharing_processors(), (0..8).collect::<Vec<_>>());
+    assert!(l3.shares_cache_with(7));
+    assert!(!l3.shares_cache_with(8));
+    assert!(!l3.shares_cache_with(1000));
+}
+
+#[test]
+fn synthesizes_indirect_gpu_links_through_the_cpu() {
+    let topo = xgmi_2gpu();
+    for (from, to) in [(1, 2), (2, 1)] {
+        let links = &topo.nodes[from].io_links;
+        let direct = links
+            .iter()
+            .find(|l| l.node_to == to && l.type_ == HSA_IOLINKTYPE_XGMI)
+            .expect("direct XGMI link");
+        assert_eq!(direct.weight, 15);
+
+        // GPU -> CPU -> GPU over PCIe: the weights of both hops add up.
+        let indirect = links
+            .iter()
+            .find(|l| l.node_to == to && l.type_ == HSA_IOLINKTYPE_PCIEXPRESS)
+            .expect("indirect link via the CPU");
+        assert_eq!(indirect.weight, 40);
+        assert_eq!(indirect.max_bandwidth, 31872);
+    }
+}
+
+#[test]
+fn classifies_raven_as_apu() {
+    let topo = fixture("raven_apu");
+    let node = &topo.nodes[0];
+    assert!(node.properties.is_gpu());
+    assert!(!node.has_local_vram());
+    assert!(node.is_apu());
+    assert!(!node.is_dgpu());
+}
+
+#[test]
+fn classifies_vega20_as_dgpu() {
+    // Current kernels report `local_mem_size 0` for every node; VRAM only shows up as a
+    // frame buffer bank.
+    let topo = fixture("vega20_dgpu");
+    let cpu = &topo.nodes[0];
+    assert_eq!(cpu.properties.local_mem_size, 0);
+    assert!(!cpu.properties.is_gpu() && !cpu.is_apu() && !cpu.is_dgpu());
+
+    let gpu = &topo.nodes[1];
+    assert_eq!(gpu.properties.local_mem_size, 0);
+    assert!(gpu.properties.is_gpu());
+    assert!(gpu.has_local_vram());
+    assert!(!gpu.is_apu());
+    assert!(gpu.is_dgpu());
+}
+
+#[test]
+fn xgmi_gpus_are_dgpus() {
+    let topo = xgmi_2gpu();
+    assert!(topo.nodes[1].is_dgpu() && topo.nodes[2].is_dgpu());
+}
+
+#[test]
+fn gpu_without_frame_buffer_bank_is_an_apu() {
+    let mut node = fixture("vega20_dgpu").nodes.swap_remove(1);
+    node.mem_banks.clear();
+    assert!(node.is_apu());
+    assert!(!node.is_dgpu());
+}
+
+#[test]
+fn from_root_ignores_host_overrides() {
+    // Only this test touches the variable, and `from_root` must not read it anyway.
+    unsafe { std::env::set_var("HSA_OVERRIDE_GFX_VERSION", "10.3.0") };
+    let topo = fixture("vega20_dgpu");
+    unsafe { std::env::remove_var("HSA_OVERRIDE_GFX_VERSION") };
+
+    let gpu = &topo.nodes[1].properties;
+    assert_eq!(gpu.engine_id.major, 9);
+    assert_eq!(gpu.engine_id.stepping, 6);
+    assert!(!gpu.marketing_name.is_empty());
+}
+
+#[test]
+fn link_metrics_prefers_the_direct_link() {
+    let topo = xgmi_2gpu();
+    let metrics = topo.link_metrics(1, 2).expect("GPUs are connected");
+    assert!(metrics.direct);
+    assert_eq!(metrics.weight, 15);
+    assert_eq!(metrics.min_bandwidth_mbps, 50000);
+    assert_eq!(metrics.max_bandwidth_mbps, 100000);
+    assert_eq!(topo.link_metrics(2, 1), Some(metrics));
+
+    let to_cpu = topo.link_metrics(1, 0).expect("GPU is attached to the CPU");
+    assert!(to_cpu.direct);
+    assert_eq!(to_cpu.max_bandwidth_mbps, 31872);
+}
+
+#[test]
+fn link_metrics_falls_back_to_the_route_through_the_cpu() {
+    let mut topo = xgmi_2gpu();
+    topo.nodes[1]
+        .io_links
+        .retain(|l| l.type_ != HSA_IOLINKTYPE_XGMI);
+
+    let metrics = topo.link_metrics(1, 2).expect("indirect route");
+    assert!(!metrics.direct);
+    assert_eq!(metrics.weight, 40);
+    assert_eq!(metrics.max_bandwidth_mbps, 31872);
+}
+
+#[test]
+fn link_metrics_of_unconnected_nodes() {
+    let topo = xgmi_2gpu();
+    assert_eq!(topo.link_metrics(0, 0), None);
+    assert_eq!(topo.link_metrics(1, 7), None);
+    assert_eq!(topo.link_metrics(7, 1), None);
+}
+
+#[test]
+fn skips_a_node_without_properties() {
+    // Node 2 is a GPU caught mid hot-plug: its directory exists but `properties` does not.
+    let topo = fixture("hotplug_partial");
+    assert_eq!(topo.nodes.len(), 2);
+    assert_eq!(topo.system_props.num_nodes, 2);
+    assert!(topo.nodes.iter().all(|n| n.properties.kfd_gpu_id != 44444));
+
+    let gpu = &topo.nodes[1];
+    assert_eq!(gpu.properties.node_id, 1);
+    assert_eq!(gpu.properties.kfd_gpu_id, 33333);
+}
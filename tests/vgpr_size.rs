@@ -0,0 +1,29 @@
+use hsa_rs::kfd::sysfs::get_vgpr_size_per_cu;
+
+const KIB: u32 = 1024;
+
+#[test]
+fn unified_vgpr_file_on_cdna() {
+    assert_eq!(get_vgpr_size_per_cu(9, 0, 8), 512 * KIB); // Arcturus
+    assert_eq!(get_vgpr_size_per_cu(9, 0, 10), 512 * KIB); // Aldebaran
+    assert_eq!(get_vgpr_size_per_cu(9, 4, 2), 512 * KIB); // Aqua Vanjaram
+    assert_eq!(get_vgpr_size_per_cu(9, 5, 0), 512 * KIB);
+}
+
+#[test]
+fn large_vgpr_file_on_navi31_and_gfx12_dgpus() {
+    assert_eq!(get_vgpr_size_per_cu(11, 0, 0), 384 * KIB);
+    assert_eq!(get_vgpr_size_per_cu(11, 0, 1), 384 * KIB);
+    assert_eq!(get_vgpr_size_per_cu(12, 0, 1), 384 * KIB);
+    // Navi33 and the RDNA3 APUs keep the regular file.
+    assert_eq!(get_vgpr_size_per_cu(11, 0, 2), 256 * KIB);
+    assert_eq!(get_vgpr_size_per_cu(11, 5, 0), 256 * KIB);
+}
+
+#[test]
+fn everything_else_has_the_regular_file() {
+    assert_eq!(get_vgpr_size_per_cu(8, 0, 3), 256 * KIB);
+    assert_eq!(get_vgpr_size_per_cu(9, 0, 6), 256 * KIB);
+    assert_eq!(get_vgpr_size_per_cu(9, 0, 9), 256 * KIB);
+    assert_eq!(get_vgpr_size_per_cu(10, 3, 0), 256 * KIB);
+}
@@ -0,0 +1,24 @@
+use hsa_rs::thunk::queues::builder::WaveStateSnapshot;
+use std::io;
+
+#[test]
+fn keeps_only_the_used_control_stack() {
+    let mut buffer = vec![0u8; 64];
+    buffer[..4].copy_from_slice(&[1, 2, 3, 4]);
+    let snap = WaveStateSnapshot::from_kfd(buffer, 4, 4096).unwrap();
+    assert_eq!(snap.control_stack, [1, 2, 3, 4]);
+    assert_eq!(snap.control_stack_size, 4);
+    assert_eq!(snap.wave_state_size, 4096);
+}
+
+#[test]
+fn empty_control_stack_is_valid() {
+    let snap = WaveStateSnapshot::from_kfd(vec![0; 16], 0, 0).unwrap();
+    assert!(snap.control_stack.is_empty());
+}
+
+#[test]
+fn used_size_beyond_buffer_is_rejected() {
+    let err = WaveStateSnapshot::from_kfd(vec![0; 16], 17, 0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}